@@ -0,0 +1,585 @@
+/*
+ * Copyright 2020 fsyncd, Berlin, Germany.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A blocking ip(8) command client, for applications without an async runtime of their own.
+//!
+//! Each command is driven to completion on a small, current thread, Tokio runtime owned by
+//! the client (and shared by its clones), so commands issued concurrently run one at a time.
+//! Commands with streaming output (eg. monitor) are only available from the async client.
+
+use crate::command::address::*;
+use crate::command::batch::Batch;
+use crate::command::bridge_vlan::*;
+use crate::command::fou::*;
+use crate::command::link::*;
+use crate::command::namespace::{ExecOutput, Namespace};
+use crate::command::neighbor::*;
+use crate::command::route::*;
+use crate::command::rule::*;
+use crate::command::transform::{
+    TransformDirection, TransformPolicy, TransformPolicyCount, TransformSelector, TransformState,
+};
+use crate::command::tunnel::*;
+use crate::command::tuntap::*;
+use crate::{CancellationToken, Elevation, Error, Feature, IpVersion, RuntimeError};
+use snafu::ResultExt;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+
+/// Generate blocking methods, delegating to the async subcommand methods of the same name.
+macro_rules! blocking_methods {
+    ($subcommand:ident {
+        $($(#[$meta:meta])* fn $method:ident($($arg:ident: $arg_type:ty),*) -> $result:ty;)*
+    }) => {
+        $(
+            $(#[$meta])*
+            pub fn $method(&self, $($arg: $arg_type),*) -> Result<$result, Error> {
+                self.ip_command
+                    .block_on(self.ip_command.inner.$subcommand().$method($($arg),*))
+            }
+        )*
+    };
+}
+
+/// Blocking ip(8) command client.
+#[derive(Clone)]
+pub struct IpCommand {
+    inner: crate::IpCommand,
+    runtime: Arc<Mutex<Runtime>>,
+}
+
+impl IpCommand {
+    /// Create a new blocking ip(8) command client.
+    pub fn new() -> Result<Self, Error> {
+        Self::from_async(crate::IpCommand::new()?)
+    }
+
+    /// Create a new blocking ip(8) command client from a (configured) async client.
+    pub fn from_async(ip_command: crate::IpCommand) -> Result<Self, Error> {
+        let runtime = Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .context(RuntimeError {})?;
+        Ok(Self {
+            inner: ip_command,
+            runtime: Arc::new(Mutex::new(runtime)),
+        })
+    }
+
+    /// Create a new blocking ip(8) command client for the specified network namespace.
+    pub fn with_namespace(&self, namespace: &str) -> Self {
+        self.derive(self.inner.with_namespace(namespace))
+    }
+
+    /// Create a new blocking ip(8) command client which sets the environment variable for every
+    /// command.
+    pub fn with_env(&self, key: &str, value: &str) -> Self {
+        self.derive(self.inner.with_env(key, value))
+    }
+
+    /// Create a new blocking ip(8) command client whose commands are cancelled by the token.
+    pub fn with_cancellation(&self, cancellation: &CancellationToken) -> Self {
+        self.derive(self.inner.with_cancellation(cancellation))
+    }
+
+    /// Create a new blocking ip(8) command client which records the arguments of each command
+    /// instead of executing it (see `recorded_commands`).
+    pub fn dry_run(&self) -> Self {
+        self.derive(self.inner.dry_run())
+    }
+
+    /// Create a new blocking ip(8) command client which caches resolved routes.
+    pub fn with_route_cache(&self, capacity: usize, time_to_live: Duration) -> Self {
+        self.derive(self.inner.with_route_cache(capacity, time_to_live))
+    }
+
+    /// Create a new blocking ip(8) command client which runs every command with elevated
+    /// privilege.
+    pub fn with_elevation(&self, elevation: Elevation) -> Self {
+        self.derive(self.inner.with_elevation(elevation))
+    }
+
+    /// Create a new blocking ip(8) command client which lists links and addresses from
+    /// `-oneline` rather than json output.
+    pub fn with_oneline(&self, oneline: bool) -> Self {
+        self.derive(self.inner.with_oneline(oneline))
+    }
+
+    /// Create a new blocking ip(8) command client which runs at most `max_concurrency` commands
+    /// at once (along with the clients derived from it, including async ones).
+    pub fn with_max_concurrency(&self, max_concurrency: NonZeroUsize) -> Self {
+        self.derive(self.inner.with_max_concurrency(max_concurrency))
+    }
+
+    /// The arguments (including global options) of every command issued by a dry run client.
+    pub fn recorded_commands(&self) -> Vec<Vec<String>> {
+        self.inner.recorded_commands()
+    }
+
+    /// Return the current version of the ip(8) command.
+    pub fn version(&self) -> Result<String, Error> {
+        self.block_on(self.inner.version())
+    }
+
+    /// Run ip(8) with arbitrary arguments, writing `stdin` to its standard input.
+    pub fn raw_with_stdin(&self, args: &[&str], stdin: Vec<u8>) -> Result<String, Error> {
+        self.block_on(self.inner.raw_with_stdin(args, stdin))
+    }
+
+    /// Return the current version of the ip(8) command, parsed.
+    pub fn version_info(&self) -> Result<IpVersion, Error> {
+        self.block_on(self.inner.version_info())
+    }
+
+    /// Does the ip(8) command support the given feature?
+    pub fn supports(&self, feature: Feature) -> Result<bool, Error> {
+        self.block_on(self.inner.supports(feature))
+    }
+
+    /// Every device along with its protocol addresses, from a single command.
+    pub fn snapshot(&self) -> Result<Vec<Interface>, Error> {
+        self.block_on(self.inner.snapshot())
+    }
+
+    /// Every device (its index, name, MTU, state and MAC address) along with its protocol
+    /// addresses, from a single command.
+    pub fn interfaces(&self) -> Result<Vec<crate::Interface>, Error> {
+        self.block_on(self.inner.interfaces())
+    }
+
+    /// Network device.
+    pub fn link(&self) -> IpLinkCommand<'_> {
+        IpLinkCommand { ip_command: self }
+    }
+
+    /// Protocol (IP or IPv6) address on a device.
+    pub fn address(&self) -> IpAddressCommand<'_> {
+        IpAddressCommand { ip_command: self }
+    }
+
+    /// Routing table entry.
+    pub fn route(&self) -> IpRouteCommand<'_> {
+        IpRouteCommand { ip_command: self }
+    }
+
+    /// Rule in routing policy database.
+    pub fn rule(&self) -> IpRuleCommand<'_> {
+        IpRuleCommand { ip_command: self }
+    }
+
+    /// Manage ARP or NDISC cache entries.
+    pub fn neighbor(&self) -> IpNeighborCommand<'_> {
+        IpNeighborCommand { ip_command: self }
+    }
+
+    /// Bridge port VLAN membership (via bridge(8)).
+    pub fn bridge_vlan(&self) -> IpBridgeVlanCommand<'_> {
+        IpBridgeVlanCommand { ip_command: self }
+    }
+
+    /// Manage IPSec policies.
+    pub fn transform(&self) -> IpTransformCommand<'_> {
+        IpTransformCommand { ip_command: self }
+    }
+
+    /// Manage network namespaces.
+    pub fn netns(&self) -> IpNetNamespaceCommand<'_> {
+        IpNetNamespaceCommand { ip_command: self }
+    }
+
+    /// Tunnel over IP.
+    pub fn tunnel(&self) -> IpTunnelCommand<'_> {
+        IpTunnelCommand { ip_command: self }
+    }
+
+    /// Manage TUN/TAP devices.
+    pub fn tuntap(&self) -> IpTunTapCommand<'_> {
+        IpTunTapCommand { ip_command: self }
+    }
+
+    /// Foo over UDP receive ports.
+    pub fn fou(&self) -> IpFouCommand<'_> {
+        IpFouCommand { ip_command: self }
+    }
+
+    /// Run several commands from a single ip(8) process.
+    pub fn batch(&self) -> IpBatchCommand<'_> {
+        IpBatchCommand { ip_command: self }
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.lock().unwrap().block_on(future)
+    }
+
+    /// A blocking client of the (derived) async client, sharing the runtime.
+    fn derive(&self, inner: crate::IpCommand) -> Self {
+        Self {
+            inner,
+            runtime: self.runtime.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IpLinkCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpLinkCommand<'l> {
+    blocking_methods!(link {
+        /// Add virtual link.
+        fn add(configuration: LinkAddConfiguration) -> ();
+        /// Delete virtual link.
+        fn delete(configuration: LinkDeleteConfiguration) -> ();
+        /// Change device attributes.
+        fn set(configuration: LinkSetConfiguration) -> ();
+        /// Change the attributes of several devices, if any change fails the devices which were
+        /// already changed are reverted to their prior state.
+        fn set_all_with_rollback(configurations: Vec<LinkSetConfiguration>) -> ();
+        /// Change the attributes of the named device to the desired state, returns whether any
+        /// changed.
+        fn reconfigure(device: &str, desired: LinkDesiredState) -> bool;
+        /// Display device attributes.
+        fn show(configuration: Option<LinkShowConfiguration>) -> Vec<Link>;
+        /// Does the named device exist?
+        fn exists(name: &str) -> bool;
+        /// Delete the named device unless it doesn't exist, returns whether it was deleted.
+        fn ensure_absent(name: &str, link_type: &str) -> bool;
+        /// Set the secret from which the device derives its stable privacy addresses.
+        fn set_stable_secret(device: &str, secret: &str) -> ();
+    });
+}
+
+#[derive(Clone)]
+pub struct IpAddressCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpAddressCommand<'l> {
+    blocking_methods!(address {
+        /// Add new protocol address.
+        fn add(configuration: AddressAddConfiguration) -> ();
+        /// Modify the flags on an existing protocol address.
+        fn change(configuration: AddressAddConfiguration) -> ();
+        /// Add new or modify existing protocol address.
+        fn replace(configuration: AddressAddConfiguration) -> ();
+        /// Add new protocol address unless the device already has it.
+        fn ensure(configuration: AddressAddConfiguration) -> bool;
+        /// Delete protocol address.
+        fn delete(configuration: AddressDeleteConfiguration) -> ();
+        /// Look at protocol addresses.
+        fn show(configuration: Option<AddressShowConfiguration>) -> Vec<Address>;
+        /// Every device along with its protocol addresses, from a single command.
+        fn interfaces() -> Vec<Interface>;
+        /// Flush protocol addresses.
+        fn flush(configuration: Option<AddressFlushConfiguration>) -> ();
+        /// Flush every protocol address of every device (including loopback).
+        fn flush_all() -> ();
+        /// Save the protocol address raw configuration.
+        fn save(configuration: Option<AddressSaveConfiguration>) -> Vec<u8>;
+        /// Restore the protocol address from a raw configuration.
+        fn restore(netlink_configuration: Vec<u8>) -> ();
+    });
+}
+
+#[derive(Clone)]
+pub struct IpRouteCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpRouteCommand<'l> {
+    blocking_methods!(route {
+        /// List routes.
+        fn list(configuration: Option<RouteShowConfiguration>) -> Vec<Route>;
+        /// Get a single route.
+        fn get(configuration: RouteGetConfiguration) -> Route;
+        /// Add new route.
+        fn add(configuration: RouteAddConfiguration) -> ();
+        /// Delete route.
+        fn delete(configuration: RouteDeleteConfiguration) -> ();
+        /// Change route.
+        fn change(configuration: RouteChangeConfiguration) -> ();
+        /// Change or add new route.
+        fn replace(configuration: RouteReplaceConfiguration) -> ();
+        /// Append a new route.
+        fn append(configuration: RouteAddConfiguration) -> ();
+        /// Prepend a new route.
+        fn prepend(configuration: RouteAddConfiguration) -> ();
+    });
+}
+
+#[derive(Clone)]
+pub struct IpRuleCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpRuleCommand<'l> {
+    blocking_methods!(rule {
+        /// Insert a new rule.
+        fn add(configuration: RuleAddConfiguration) -> ();
+        /// Delete a rule.
+        fn delete(configuration: RuleDeleteConfiguration) -> ();
+        /// Flush rules table information.
+        fn flush() -> ();
+        /// List rules.
+        fn list() -> Vec<Rule>;
+    });
+}
+
+#[derive(Clone)]
+pub struct IpNeighborCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpNeighborCommand<'l> {
+    blocking_methods!(neighbor {
+        /// Add a new neighbour entry.
+        fn add(configuration: NeighborConfiguration) -> ();
+        /// Delete a neighbour entry.
+        fn delete(configuration: NeighborConfiguration) -> ();
+        /// Change an existing entry.
+        fn change(configuration: NeighborConfiguration) -> ();
+        /// Add a new entry or change an existing one.
+        fn replace(configuration: NeighborConfiguration) -> ();
+        /// Pin the link layer address of a neighbour with a permanent entry.
+        fn ensure_permanent(to: &str, device: &str, link_layer_address: &str) -> ();
+        /// List neighbour entries.
+        fn show(configuration: Option<NeighborShowConfiguration>) -> Vec<Neighbor>;
+    });
+}
+
+#[derive(Clone)]
+pub struct IpBridgeVlanCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpBridgeVlanCommand<'l> {
+    blocking_methods!(bridge_vlan {
+        /// Add a new VLAN filter entry.
+        fn add(configuration: BridgeVlanAddConfiguration) -> ();
+        /// Delete a VLAN filter entry.
+        fn delete(configuration: BridgeVlanDeleteConfiguration) -> ();
+        /// List VLAN configuration.
+        fn show(configuration: Option<BridgeVlanShowConfiguration>) -> Vec<BridgeVlanPort>;
+    });
+}
+
+#[derive(Clone)]
+pub struct IpTransformCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpTransformCommand<'l> {
+    blocking_methods!(transform {
+        /// Get existing state in xfrm.
+        fn state_get(source: &str, destination: &str, protocol: &str, spi: u32) -> TransformState;
+        /// Print out the list of existing state in xfrm.
+        fn state_list() -> Vec<TransformState>;
+        /// Flush all state in xfrm.
+        fn state_flush() -> ();
+        /// Count all existing state in xfrm.
+        fn state_count() -> u32;
+        /// Get an existing policy.
+        fn policy_get(selector: TransformSelector, direction: TransformDirection) -> TransformPolicy;
+        /// Flush policies.
+        fn policy_flush() -> ();
+        /// Count existing policies.
+        fn policy_count() -> TransformPolicyCount;
+    });
+}
+
+#[derive(Clone)]
+pub struct IpNetNamespaceCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpNetNamespaceCommand<'l> {
+    blocking_methods!(netns {
+        /// Show all of the named network namespaces.
+        fn list() -> Vec<Namespace>;
+        /// Create a new named network namespace.
+        fn add(network_namespace_name: &str) -> ();
+        /// Delete the name of a network.
+        fn delete(network_namespace_name: &str) -> ();
+        /// Assign an id to a peer network namespace.
+        fn set(network_namespace_name: &str, network_namespace_id: Option<u32>) -> ();
+        /// Report network namespaces names for process.
+        fn identify(process_id: u32) -> String;
+        /// Report processes in the named network namespace.
+        fn pids(network_namespace_name: &str) -> Vec<u32>;
+        /// Run command in the named network namespace to completion, capturing its output.
+        fn exec_capture(network_namespace_name: &str, command_and_args: &[String]) -> ExecOutput;
+    });
+}
+
+#[derive(Clone)]
+pub struct IpTunnelCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpTunnelCommand<'l> {
+    blocking_methods!(tunnel {
+        /// Add a new tunnel.
+        fn add(configuration: TunnelAddConfiguration) -> ();
+        /// Change an existing tunnel, attributes left unset keep their current values.
+        fn change(configuration: TunnelChangeConfiguration) -> ();
+        /// Destroy a tunnel.
+        fn delete(name: &str) -> ();
+        /// List tunnels, or only the named one.
+        fn show(name: Option<&str>) -> Vec<Tunnel>;
+        /// Configure ipv6 rapid deployment (6rd) of a SIT tunnel.
+        fn ipv6_rapid_development(configuration: SixRdConfiguration) -> ();
+    });
+}
+
+#[derive(Clone)]
+pub struct IpTunTapCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpTunTapCommand<'l> {
+    blocking_methods!(tuntap {
+        /// Add a new TAP/TUN device.
+        fn add(configuration: TunTapAddConfiguration) -> ();
+        /// Delete an existing TAP/TUN device.
+        fn delete(configuration: TunTapDeleteConfiguration) -> ();
+        /// Make an existing TAP/TUN device persistent, or not.
+        fn set_persist(name: &str, mode: TunTapMode, persist: bool) -> ();
+        /// List all TAP/TUN devices.
+        fn show() -> Vec<TunTap>;
+    });
+}
+
+#[derive(Clone)]
+pub struct IpFouCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpFouCommand<'l> {
+    blocking_methods!(fou {
+        /// Add a receive port.
+        fn add(configuration: FouAddConfiguration) -> ();
+        /// Delete a receive port.
+        fn delete(configuration: FouDeleteConfiguration) -> ();
+        /// List receive ports.
+        fn show() -> Vec<Fou>;
+    });
+}
+
+#[derive(Clone)]
+pub struct IpBatchCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpBatchCommand<'l> {
+    blocking_methods!(batch {
+        /// Run the commands of the batch, stopping at the first which fails.
+        fn run(batch: &Batch) -> ();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_info() {
+        let client = IpCommand::new().unwrap();
+        assert!(client.version_info().unwrap().iproute2.is_some());
+    }
+
+    #[test]
+    fn test_link_show() {
+        let client = IpCommand::new().unwrap();
+        let links = client.link().show(None).unwrap();
+        assert!(links.iter().any(|link| link.name == "lo"));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let client = IpCommand::new().unwrap();
+        let interfaces = client.snapshot().unwrap();
+        let loopback = interfaces
+            .iter()
+            .find(|interface| interface.link.name == "lo")
+            .unwrap();
+        assert!(loopback
+            .addresses
+            .iter()
+            .any(|address| address.local == Some("127.0.0.1".into())));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let client = IpCommand::new()
+            .unwrap()
+            .with_max_concurrency(NonZeroUsize::new(1).unwrap())
+            .with_env("LC_ALL", "C")
+            .dry_run();
+        client.rule().flush().unwrap();
+        client.tunnel().delete("tun0").unwrap();
+        client
+            .tuntap()
+            .add(TunTapAddConfiguration {
+                name: "tap0".into(),
+                mode: TunTapMode::Tap,
+                ..Default::default()
+            })
+            .unwrap();
+        let batch = Batch::new().command(vec!["link", "set", "lo", "up"]);
+        client.batch().run(&batch).unwrap();
+        assert_eq!(
+            client.recorded_commands(),
+            vec![
+                vec!["rule", "flush"],
+                vec!["tunnel", "del", "tun0"],
+                vec!["tuntap", "add", "dev", "tap0", "mode", "tap"],
+                vec!["-batch", "-"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interfaces() {
+        let client = IpCommand::new().unwrap();
+        let interfaces = client.interfaces().unwrap();
+        let loopback = interfaces
+            .iter()
+            .find(|interface| interface.name == "lo")
+            .unwrap();
+        assert!(loopback
+            .addresses
+            .iter()
+            .any(|address| address.local == Some("127.0.0.1".into())));
+    }
+
+    #[test]
+    fn test_route_get() {
+        let client = IpCommand::new().unwrap();
+        let route = client
+            .route()
+            .get(RouteGetConfiguration {
+                to: "127.0.0.1".into(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(route.device, Some("lo".into()));
+    }
+}
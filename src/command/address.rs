@@ -14,16 +14,21 @@
  * limitations under the License.
  */
 
-use crate::command::link::LinkStatus;
+use crate::command::link::{IpLinkCommand, Link, LinkFlags, LinkStatus};
 use crate::*;
+use serde::de::{self, Visitor};
 use serde::ser::SerializeSeq;
 use serde::{Deserialize, Serialize};
 use serde_command_opts::{BooleanType, Serializer};
-use snafu::ResultExt;
+use snafu::{ensure, OptionExt, ResultExt};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
 
 /// Add protocol address configuration flags.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum AddressAddConfigurationFlag {
+    #[default]
     None,
     /// Designates this address the "home address" as defined in RFC 6275 (IPv6 only).
     HomeAddress,
@@ -37,22 +42,16 @@ pub enum AddressAddConfigurationFlag {
     JoinMulticastGroups,
 }
 
-impl Default for AddressAddConfigurationFlag {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
-impl ToString for AddressAddConfigurationFlag {
-    fn to_string(&self) -> String {
-        match self {
-            Self::HomeAddress => "home".into(),
-            Self::KernelManagedTemporaryAddress => "mngtmpaddr".into(),
-            Self::NoDuplicateAddressDetection => "nodad".into(),
-            Self::NoPrefixRoute => "noprefixroute".into(),
-            Self::JoinMulticastGroups => "autojoin".into(),
+impl fmt::Display for AddressAddConfigurationFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::HomeAddress => "home",
+            Self::KernelManagedTemporaryAddress => "mngtmpaddr",
+            Self::NoDuplicateAddressDetection => "nodad",
+            Self::NoPrefixRoute => "noprefixroute",
+            Self::JoinMulticastGroups => "autojoin",
             _ => unimplemented!(),
-        }
+        })
     }
 }
 
@@ -85,8 +84,8 @@ pub struct AddressAddConfiguration {
     pub any_cast: Option<String>,
     /// Label for tagging the address.
     pub label: Option<String>,
-    /// The scope of the area where this address is valid.
-    pub scope: Option<String>,
+    /// The scope of the area where this address is valid (eg. `Scope::Link`).
+    pub scope: Option<Scope>,
     /// The name of the device to add the address to.
     #[serde(rename = "dev")]
     pub device: String,
@@ -96,13 +95,16 @@ pub struct AddressAddConfiguration {
     /// The preferred lifetime of this address (seconds or "forever").
     #[serde(rename = "preferred_lft")]
     pub preferred_lifetime: Option<String>,
+    /// Priority of the prefix route of this address (see `AddressFlushConfiguration::metric`).
+    pub metric: Option<u32>,
     /// Optional configuration flags.
     pub flags: Option<Vec<AddressAddConfigurationFlag>>,
 }
 
 /// List protocol addresses configuration flags.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum AddressConfigurationFlag {
+    #[default]
     None,
     /// Only list addresses installed due to stateless address configuration (IPv6 only).
     Dynamic,
@@ -128,28 +130,22 @@ pub enum AddressConfigurationFlag {
     Temporary,
 }
 
-impl Default for AddressConfigurationFlag {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
-impl ToString for AddressConfigurationFlag {
-    fn to_string(&self) -> String {
-        match self {
-            Self::Dynamic => "dynamic".into(),
-            Self::Permanent => "permanent".into(),
-            Self::Tentative => "tentative".into(),
-            Self::NotTentative => "-tentative".into(),
-            Self::Deprecated => "deprecated".into(),
-            Self::NotDeprecated => "-deprecated".into(),
-            Self::DuplicateAddressDetectionFailed => "dadfailed".into(),
-            Self::NotDuplicateAddressDetectionFailed => "-dadfailed".into(),
-            Self::Primary => "primary".into(),
-            Self::Secondary => "secondary".into(),
-            Self::Temporary => "temporary".into(),
+impl fmt::Display for AddressConfigurationFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Dynamic => "dynamic",
+            Self::Permanent => "permanent",
+            Self::Tentative => "tentative",
+            Self::NotTentative => "-tentative",
+            Self::Deprecated => "deprecated",
+            Self::NotDeprecated => "-deprecated",
+            Self::DuplicateAddressDetectionFailed => "dadfailed",
+            Self::NotDuplicateAddressDetectionFailed => "-dadfailed",
+            Self::Primary => "primary",
+            Self::Secondary => "secondary",
+            Self::Temporary => "temporary",
             _ => unimplemented!(),
-        }
+        })
     }
 }
 
@@ -171,11 +167,11 @@ impl Serialize for AddressConfigurationFlag {
 /// List protocol addresses configuration.
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct AddressShowConfiguration {
-    /// The name of the device.
+    /// The name of the device, addresses of all devices are listed when unset.
     #[serde(rename = "dev")]
-    pub device: String,
+    pub device: Option<String>,
     /// Only list addresses with this scope.
-    pub scope: Option<String>,
+    pub scope: Option<Scope>,
     /// Only list addresses matching this prefix.
     pub to: Option<String>,
     /// Only list addresses with labels matching the pattern.
@@ -209,7 +205,7 @@ pub struct AddressDeleteConfiguration {
     /// Label for tagging the address.
     pub label: Option<String>,
     /// The scope of the area where this address is valid.
-    pub scope: Option<String>,
+    pub scope: Option<Scope>,
     /// The name of the device.
     #[serde(rename = "dev")]
     pub device: String,
@@ -217,14 +213,14 @@ pub struct AddressDeleteConfiguration {
     pub flags: Option<Vec<AddressAddConfigurationFlag>>,
 }
 
-/// Flush/save protocol address configuration.
+/// Save protocol address configuration.
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct AddressFlushOrSaveConfiguration {
     /// The name of the device.
     #[serde(rename = "dev")]
     pub device: Option<String>,
     /// Only match addresses with this scope.
-    pub scope: Option<String>,
+    pub scope: Option<Scope>,
     /// Only match addresses with this prefix route priority.
     pub metric: Option<u32>,
     /// Only match addresses matching this prefix.
@@ -237,25 +233,164 @@ pub struct AddressFlushOrSaveConfiguration {
     pub state: Option<LinkStatus>,
 }
 
-pub type AddressFlushConfiguration = AddressFlushOrSaveConfiguration;
 pub type AddressSaveConfiguration = AddressFlushOrSaveConfiguration;
 
+/// Flush protocol address configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AddressFlushConfiguration {
+    /// The name of the device.
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+    /// Only match addresses with this scope.
+    pub scope: Option<Scope>,
+    /// Only match addresses with this prefix route priority.
+    pub metric: Option<u32>,
+    /// Only match addresses matching this prefix.
+    pub to: Option<String>,
+    /// Optional configuration flags.
+    pub flags: Option<Vec<AddressConfigurationFlag>>,
+    /// Only match addresses with labels matching the pattern.
+    pub label: Option<String>,
+    /// Only match running interfaces.
+    pub state: Option<LinkStatus>,
+    /// Allow flushing the matching addresses of every device when no device is set, which may
+    /// well include the addresses of a management interface.
+    #[serde(skip)]
+    pub confirm_flush_all: bool,
+}
+
 /// The returned address information structure.
 #[derive(Debug, Clone, Deserialize)]
 pub struct AddressInfo {
     pub family: Option<String>,
     pub local: Option<String>,
+    /// The address of the remote endpoint of a pointopoint interface (`local` is this end).
+    pub address: Option<String>,
     #[serde(rename = "prefixlen")]
     pub prefix_length: Option<u32>,
     pub broadcast: Option<String>,
     pub anycast: Option<String>,
-    pub scope: Option<String>,
+    pub scope: Option<Scope>,
     pub dynamic: Option<bool>,
     #[serde(rename = "noprefixroute")]
     pub no_prefix_route: Option<bool>,
     pub label: Option<String>,
-    pub valid_life_time: Option<u32>,
-    pub preferred_life_time: Option<u32>,
+    pub valid_life_time: Option<Lifetime>,
+    pub preferred_life_time: Option<Lifetime>,
+}
+
+/// The scope of the area where an address (or the destinations of a route) is valid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    /// Valid everywhere ("universe").
+    Global,
+    /// Valid within the site (IPv6 only).
+    Site,
+    /// Valid on this link (eg. IPv6 link local addresses).
+    Link,
+    /// Valid inside this host.
+    Host,
+    /// Any other scope, by number (eg. 255 for "nowhere").
+    Numeric(u8),
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Global => f.write_str("global"),
+            Self::Site => f.write_str("site"),
+            Self::Link => f.write_str("link"),
+            Self::Host => f.write_str("host"),
+            Self::Numeric(scope) => write!(f, "{}", scope),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = Error;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        Ok(match scope {
+            "global" | "universe" | "0" => Self::Global,
+            "site" | "200" => Self::Site,
+            "link" | "253" => Self::Link,
+            "host" | "254" => Self::Host,
+            "nowhere" => Self::Numeric(255),
+            scope => Self::Numeric(scope.parse().map_err(|_| Error::InvalidArgumentError {
+                field: "scope".into(),
+                value: scope.into(),
+            })?),
+        })
+    }
+}
+
+impl Serialize for Scope {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Scope {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// The remaining (valid or preferred) lifetime of a protocol address.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lifetime {
+    /// The address does not expire (eg. configured without a lifetime).
+    Forever,
+    /// The address expires after this many seconds.
+    Seconds(u32),
+}
+
+/// Reported as the number of seconds, where the largest is "forever" (some releases report
+/// a string instead).
+impl<'de> Deserialize<'de> for Lifetime {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct LifetimeVisitor;
+
+        impl<'de> Visitor<'de> for LifetimeVisitor {
+            type Value = Lifetime;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a number of seconds or \"forever\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Lifetime, E> {
+                if value >= u64::from(u32::MAX) {
+                    Ok(Lifetime::Forever)
+                } else {
+                    Ok(Lifetime::Seconds(value as u32))
+                }
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Lifetime, E> {
+                if value == "forever" {
+                    return Ok(Lifetime::Forever);
+                }
+                value
+                    .trim_end_matches("sec")
+                    .parse::<u64>()
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+                    .and_then(|seconds| self.visit_u64(seconds))
+            }
+        }
+
+        deserializer.deserialize_any(LifetimeVisitor)
+    }
 }
 
 /// The returned address structure.
@@ -282,6 +417,64 @@ pub struct Address {
     pub address_info: Option<Vec<AddressInfo>>,
 }
 
+impl Address {
+    /// The typed flags of the device (see `flags` for the names as reported).
+    pub fn link_flags(&self) -> LinkFlags {
+        LinkFlags::from_names(&self.flags)
+    }
+}
+
+/// A network device along with its protocol addresses (see `IpCommand::snapshot`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Interface {
+    /// The device attributes (eg. index, name, mtu, operational state and MAC address).
+    #[serde(flatten)]
+    pub link: Link,
+    #[serde(rename = "addr_info", default)]
+    pub addresses: Vec<AddressInfo>,
+}
+
+/// The outcome of a checked protocol address add (see `IpAddressCommand::add_checked`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum AddressAddOutcome {
+    /// The address was added.
+    Added,
+    /// The identical address (and prefix length) was already present on the device.
+    AlreadyPresent,
+    /// The address was added, although its subnet overlaps these existing addresses
+    /// (as "device address/prefix length").
+    AddedOverlapping(Vec<String>),
+}
+
+/// An address with its prefix length, the full length when it has none (eg. "192.0.2.1/24").
+fn parse_prefix(address: &str) -> Option<(IpAddr, u32)> {
+    let mut parts = address.splitn(2, '/');
+    let address: IpAddr = parts.next()?.parse().ok()?;
+    let full_length = if address.is_ipv4() { 32 } else { 128 };
+    let length = match parts.next() {
+        Some(length) => length
+            .parse()
+            .ok()
+            .filter(|length| *length <= full_length)?,
+        None => full_length,
+    };
+    Some((address, length))
+}
+
+/// Do the subnets share any address? ie. do the addresses match within the shorter prefix.
+fn overlaps((first, first_length): (IpAddr, u32), (second, second_length): (IpAddr, u32)) -> bool {
+    let (first, second, full_length) = match (first, second) {
+        (IpAddr::V4(first), IpAddr::V4(second)) => {
+            (u32::from(first) as u128, u32::from(second) as u128, 32)
+        }
+        (IpAddr::V6(first), IpAddr::V6(second)) => (u128::from(first), u128::from(second), 128),
+        _ => return false,
+    };
+    let length = first_length.min(second_length);
+    let host_bits = full_length - length;
+    length == 0 || (first >> host_bits) == (second >> host_bits)
+}
+
 #[derive(Clone)]
 pub struct IpAddressCommand<'l> {
     ip_command: &'l IpCommand,
@@ -300,10 +493,96 @@ impl<'l> IpAddressCommand<'l> {
                 .into_args(&configuration)
                 .context(CommandOptionsSerializationError {})?,
         );
-        self.ip_command
-            .command(&args, false, None)
-            .await
-            .map(|_| ())
+        self.ip_command.command_without_output(&args, None).await
+    }
+
+    /// Add new protocol address, unless the identical address is already present on the device.
+    ///
+    /// Addresses whose subnet overlaps the new one (on any device, link local addresses aside)
+    /// are reported, or with `reject_overlapping` fail the add with `OverlappingAddressError`.
+    pub async fn add_checked(
+        &self,
+        configuration: AddressAddConfiguration,
+        reject_overlapping: bool,
+    ) -> Result<AddressAddOutcome, Error> {
+        let prefix = parse_prefix(&configuration.local).context(InvalidArgumentError {
+            field: "local",
+            value: configuration.local.clone(),
+        })?;
+        let mut existing_addresses = vec![];
+        for address in self.show(None).await? {
+            for info in address.address_info.unwrap_or_default() {
+                if let (Some(local), Some(prefix_length)) = (info.local, info.prefix_length) {
+                    let existing = format!("{}/{}", local, prefix_length);
+                    if let Some(existing_prefix) = parse_prefix(&existing) {
+                        let link_local = info.scope == Some(Scope::Link);
+                        existing_addresses.push((
+                            address.name.clone(),
+                            existing,
+                            existing_prefix,
+                            link_local,
+                        ));
+                    }
+                }
+            }
+        }
+        if existing_addresses
+            .iter()
+            .any(|(device, _, existing_prefix, _)| {
+                *device == configuration.device && *existing_prefix == prefix
+            })
+        {
+            return Ok(AddressAddOutcome::AlreadyPresent);
+        }
+        let mut overlapping = vec![];
+        for (device, existing, existing_prefix, link_local) in existing_addresses {
+            if !link_local && overlaps(existing_prefix, prefix) {
+                ensure!(
+                    !reject_overlapping,
+                    OverlappingAddressError {
+                        address: configuration.local.clone(),
+                        existing,
+                        device,
+                    }
+                );
+                overlapping.push(format!("{} {}", device, existing));
+            }
+        }
+        self.add(configuration).await?;
+        Ok(if overlapping.is_empty() {
+            AddressAddOutcome::Added
+        } else {
+            AddressAddOutcome::AddedOverlapping(overlapping)
+        })
+    }
+
+    /// Add new protocol address unless the device already has it (the same local address and
+    /// prefix length), returns whether it was added.
+    pub async fn ensure(&self, configuration: AddressAddConfiguration) -> Result<bool, Error> {
+        let prefix = parse_prefix(&configuration.local).context(InvalidArgumentError {
+            field: "local",
+            value: configuration.local.clone(),
+        })?;
+        let addresses = self
+            .show(Some(AddressShowConfiguration {
+                device: Some(configuration.device.clone()),
+                ..Default::default()
+            }))
+            .await?;
+        let present = addresses
+            .into_iter()
+            .flat_map(|address| address.address_info.unwrap_or_default())
+            .any(|info| match (info.local, info.prefix_length) {
+                (Some(local), Some(prefix_length)) => {
+                    parse_prefix(&format!("{}/{}", local, prefix_length)) == Some(prefix)
+                }
+                _ => false,
+            });
+        if present {
+            return Ok(false);
+        }
+        self.add(configuration).await?;
+        Ok(true)
     }
 
     /// Modify the flags on an existing protocol address.
@@ -314,10 +593,7 @@ impl<'l> IpAddressCommand<'l> {
                 .into_args(&configuration)
                 .context(CommandOptionsSerializationError {})?,
         );
-        self.ip_command
-            .command(&args, false, None)
-            .await
-            .map(|_| ())
+        self.ip_command.command_without_output(&args, None).await
     }
 
     /// Add new or modify existing protocol address.
@@ -328,10 +604,7 @@ impl<'l> IpAddressCommand<'l> {
                 .into_args(&configuration)
                 .context(CommandOptionsSerializationError {})?,
         );
-        self.ip_command
-            .command(&args, false, None)
-            .await
-            .map(|_| ())
+        self.ip_command.command_without_output(&args, None).await
     }
 
     /// Delete protocol address.
@@ -342,10 +615,7 @@ impl<'l> IpAddressCommand<'l> {
                 .into_args(&configuration)
                 .context(CommandOptionsSerializationError {})?,
         );
-        self.ip_command
-            .command(&args, false, None)
-            .await
-            .map(|_| ())
+        self.ip_command.command_without_output(&args, None).await
     }
 
     /// Look at protocol addresses.
@@ -361,18 +631,183 @@ impl<'l> IpAddressCommand<'l> {
                     .context(CommandOptionsSerializationError {})?,
             );
         }
-        let mut output = self.ip_command.command(&args, false, None).await?;
+        if self.ip_command.is_oneline() {
+            return self.show_oneline(args).await;
+        }
+        let output = self.ip_command.command(&args, true, false, None).await?;
+        Self::parse_show_output(&output)
+    }
+
+    /// Every device along with its protocol addresses, from a single command.
+    pub async fn interfaces(&self) -> Result<Vec<Interface>, Error> {
+        if self.ip_command.is_oneline() {
+            return self
+                .show_oneline(vec!["address".into(), "show".into()])
+                .await;
+        }
+        let output = self
+            .ip_command
+            .command(&["address", "show"], true, false, None)
+            .await?;
+        Self::parse_show_output(&output)
+    }
+
+    fn parse_show_output<T: serde::de::DeserializeOwned>(output: &str) -> Result<T, Error> {
         // Strip out invalid junk the iproute2 json serializer produces.
-        output = output.replace("{},", "");
-        output = output.replace(",{}", "");
-        Ok(serde_json::from_str(&output).context(JsonDeserializationError {})?)
+        let output = output.replace("{},", "").replace(",{}", "");
+        serde_json::from_str(&output).context(JsonDeserializationError {})
+    }
+
+    /// List from `-oneline` output, which lacks the attributes of the devices, so these are
+    /// listed separately. Devices without (matching) addresses are only reported when no more
+    /// than the device is selected.
+    async fn show_oneline<T: serde::de::DeserializeOwned>(
+        &self,
+        args: Vec<String>,
+    ) -> Result<Vec<T>, Error> {
+        let unfiltered = args.len() == 2 || (args.len() == 4 && args[2] == "dev");
+        let output = self.ip_command.command(&args, false, false, None).await?;
+        let addresses = Self::parse_oneline_output(&output)?;
+        let mut link_args: Vec<String> = vec!["link".into(), "show".into()];
+        if args.len() == 4 && args[2] == "dev" {
+            link_args.push(args[3].clone());
+        }
+        let output = self
+            .ip_command
+            .command(&link_args, false, false, None)
+            .await?;
+        let mut interfaces = vec![];
+        for record in output.lines().filter(|record| !record.trim().is_empty()) {
+            let mut link = IpLinkCommand::parse_oneline_record(record)?;
+            let index = link["ifindex"].as_u64();
+            let address_info: Vec<serde_json::Value> = addresses
+                .iter()
+                .filter(|(address_index, _)| Some(*address_index as u64) == index)
+                .map(|(_, address_info)| address_info.clone())
+                .collect();
+            if address_info.is_empty() && !unfiltered {
+                continue;
+            }
+            link["addr_info"] = address_info.into();
+            interfaces.push(serde_json::from_value(link).context(JsonDeserializationError {})?);
+        }
+        Ok(interfaces)
+    }
+
+    /// Parse `-oneline` output, a record per address along with the index of its device, eg.
+    /// "2: eth0    inet 192.0.2.1/24 brd 192.0.2.255 scope global eth0\       valid_lft forever ...".
+    fn parse_oneline_output(output: &str) -> Result<Vec<(u32, serde_json::Value)>, Error> {
+        output
+            .lines()
+            .filter(|record| !record.trim().is_empty())
+            .map(|record| {
+                let unexpected = || Error::UnexpectedOutputError {
+                    output: record.into(),
+                };
+                let segments = IpCommand::oneline_segments(record);
+                let tokens: Vec<&str> = segments
+                    .first()
+                    .ok_or_else(unexpected)?
+                    .split_whitespace()
+                    .collect();
+                if tokens.len() < 4 {
+                    return Err(unexpected());
+                }
+                let index: u32 = tokens[0]
+                    .trim_end_matches(':')
+                    .parse()
+                    .map_err(|_| unexpected())?;
+                let mut fields = serde_json::Map::new();
+                fields.insert("family".into(), tokens[2].into());
+                Self::insert_oneline_prefix(&mut fields, "local", tokens[3]);
+                let mut position = 4;
+                while position < tokens.len() {
+                    let value = tokens.get(position + 1).copied();
+                    match (tokens[position], value) {
+                        ("peer", Some(value)) => {
+                            Self::insert_oneline_prefix(&mut fields, "address", value)
+                        }
+                        ("brd", Some(value)) => {
+                            fields.insert("broadcast".into(), value.into());
+                        }
+                        ("any", Some(value)) => {
+                            fields.insert("anycast".into(), value.into());
+                        }
+                        ("scope", Some(value)) => {
+                            fields.insert("scope".into(), value.into());
+                        }
+                        ("metric", Some(_)) => {}
+                        // IPv4 addresses end with their label.
+                        (label, None) if tokens[2] == "inet" => {
+                            fields.insert("label".into(), label.into());
+                            position -= 1;
+                        }
+                        (flag, _) => {
+                            fields.insert(flag.into(), true.into());
+                            position -= 1;
+                        }
+                    }
+                    position += 2;
+                }
+                for segment in &segments[1..] {
+                    IpCommand::insert_oneline_attributes(
+                        &mut fields,
+                        segment.split_whitespace(),
+                        &[
+                            ("valid_lft", "valid_life_time", false),
+                            ("preferred_lft", "preferred_life_time", false),
+                        ],
+                    );
+                }
+                for key in &["valid_life_time", "preferred_life_time"] {
+                    let lifetime = fields
+                        .get(*key)
+                        .and_then(|lifetime| lifetime.as_str())
+                        .and_then(Self::oneline_lifetime);
+                    if let Some(lifetime) = lifetime {
+                        fields.insert((*key).into(), lifetime.into());
+                    }
+                }
+                Ok((index, fields.into()))
+            })
+            .collect()
+    }
+
+    /// A lifetime as json output reports it, in seconds where the largest means forever (eg.
+    /// "forever", "100sec").
+    fn oneline_lifetime(lifetime: &str) -> Option<u32> {
+        match lifetime {
+            "forever" => Some(u32::MAX),
+            lifetime => lifetime.trim_end_matches("sec").parse().ok(),
+        }
     }
 
-    /// Flush protocol addresses.
+    /// An address with its optional prefix length (eg. "192.0.2.1/24").
+    fn insert_oneline_prefix(
+        fields: &mut serde_json::Map<String, serde_json::Value>,
+        key: &str,
+        prefix: &str,
+    ) {
+        let mut parts = prefix.splitn(2, '/');
+        fields.insert(key.into(), parts.next().unwrap().into());
+        if let Some(length) = parts.next().and_then(|length| length.parse::<u32>().ok()) {
+            fields.insert("prefixlen".into(), length.into());
+        }
+    }
+
+    /// Flush protocol addresses. Without a device this is refused with
+    /// `RefusedUnsafeOperationError`, unless `confirm_flush_all` is set.
     pub async fn flush(
         &self,
         configuration: Option<AddressFlushConfiguration>,
     ) -> Result<(), Error> {
+        ensure!(
+            matches!(&configuration, Some(configuration)
+                if configuration.device.is_some() || configuration.confirm_flush_all),
+            RefusedUnsafeOperationError {
+                operation: "flush of the addresses of every device",
+            }
+        );
         let mut args: Vec<String> = vec!["address".into(), "flush".into()];
         if let Some(configuration) = configuration {
             args.append(
@@ -381,10 +816,21 @@ impl<'l> IpAddressCommand<'l> {
                     .context(CommandOptionsSerializationError {})?,
             );
         }
-        self.ip_command
-            .command(&args, false, None)
-            .await
-            .map(|_| ())
+        self.ip_command.command_without_output(&args, None).await
+    }
+
+    /// Flush every protocol address of every device (including loopback).
+    pub async fn flush_all(&self) -> Result<(), Error> {
+        // ip(8) refuses to flush without any selector.
+        for prefix in &["0.0.0.0/0", "::/0"] {
+            self.flush(Some(AddressFlushConfiguration {
+                to: Some((*prefix).into()),
+                confirm_flush_all: true,
+                ..Default::default()
+            }))
+            .await?;
+        }
+        Ok(())
     }
 
     /// Save the protocol address raw configuration.
@@ -401,7 +847,7 @@ impl<'l> IpAddressCommand<'l> {
             );
         }
         self.ip_command
-            .command_with_raw_output(&args, false, None)
+            .command_with_raw_output(&args, false, false, None)
             .await
     }
 
@@ -409,9 +855,8 @@ impl<'l> IpAddressCommand<'l> {
     pub async fn restore(&self, netlink_configuration: Vec<u8>) -> Result<(), Error> {
         let args: Vec<String> = vec!["address".into(), "restore".into()];
         self.ip_command
-            .command(&args, false, Some(netlink_configuration))
+            .command_without_output(&args, Some(netlink_configuration))
             .await
-            .map(|_| ())
     }
 
     /// Convert the raw netlink configuration into a human readable form or json.
@@ -424,7 +869,567 @@ impl<'l> IpAddressCommand<'l> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::command::link::{LinkAddConfiguration, LinkDeleteConfiguration, LinkDeviceOrGroup};
+    use crate::command::link::{
+        LinkAddConfiguration, LinkDeleteConfiguration, LinkDeviceOrGroup, LinkKind,
+        LinkSetConfiguration, VethConfiguration,
+    };
+    use crate::command::route::RouteShowConfiguration;
+
+    #[tokio::test]
+    async fn test_show_loopback() {
+        let client = IpCommand::new().unwrap();
+
+        let addresses = client
+            .address()
+            .show(Some(AddressShowConfiguration {
+                device: Some("lo".into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        let address_info = addresses[0].address_info.as_ref().unwrap();
+        for local in &["127.0.0.1", "::1"] {
+            let address = address_info
+                .iter()
+                .find(|address| address.local == Some((*local).into()))
+                .unwrap();
+            assert_eq!(address.scope, Some(Scope::Host));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_show_to_prefix() {
+        let client = IpCommand::new().unwrap();
+
+        let addresses = client
+            .address()
+            .show(Some(AddressShowConfiguration {
+                to: Some("127.0.0.0/8".into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        assert!(addresses.iter().any(|address| address.name == "lo"));
+        for address in &addresses {
+            for info in address.address_info.as_deref().unwrap_or_default() {
+                assert!(info.local.as_deref().unwrap().starts_with("127."));
+            }
+        }
+
+        let client = client.dry_run();
+        client
+            .address()
+            .show(Some(AddressShowConfiguration {
+                scope: Some(Scope::Host),
+                ..Default::default()
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            client.recorded_commands(),
+            vec![vec!["-json", "address", "show", "scope", "host"]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_show_pointopoint() {
+        let client = IpCommand::new().unwrap().dry_run_with_output(
+            r#"[{"ifindex":5,"ifname":"tun0","flags":["POINTOPOINT","NOARP","UP","LOWER_UP"],"mtu":1500,"qdisc":"fq_codel","operstate":"UNKNOWN","group":"default","txqlen":500,"link_type":"none","addr_info":[{"family":"inet","local":"10.8.0.1","address":"10.8.0.2","prefixlen":32,"scope":"global","label":"tun0","valid_life_time":4294967295,"preferred_life_time":4294967295}]}]"#,
+        );
+        let addresses = client.address().show(None).await.unwrap();
+        let address_info = addresses[0].address_info.as_ref().unwrap();
+        assert_eq!(address_info[0].local, Some("10.8.0.1".into()));
+        assert_eq!(address_info[0].address, Some("10.8.0.2".into()));
+    }
+
+    #[tokio::test]
+    async fn test_show_link_scope() {
+        let test_namespace = "ip-command-test-scope-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let result = namespace_client
+            .address()
+            .add(AddressAddConfiguration {
+                device: "lo".into(),
+                local: "192.0.2.1/32".into(),
+                scope: Some(Scope::Link),
+                ..Default::default()
+            })
+            .await;
+        let addresses = namespace_client
+            .address()
+            .show(Some(AddressShowConfiguration {
+                device: Some("lo".into()),
+                ..Default::default()
+            }))
+            .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        result.unwrap();
+        let address_info = addresses.unwrap().pop().unwrap().address_info.unwrap();
+        let address = address_info
+            .iter()
+            .find(|address| address.local == Some("192.0.2.1".into()))
+            .unwrap();
+        assert_eq!(address.scope, Some(Scope::Link));
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        assert_eq!("universe".parse::<Scope>().unwrap(), Scope::Global);
+        assert_eq!("253".parse::<Scope>().unwrap(), Scope::Link);
+        assert_eq!("nowhere".parse::<Scope>().unwrap(), Scope::Numeric(255));
+        assert_eq!(Scope::Numeric(100).to_string(), "100");
+        assert!("galaxy".parse::<Scope>().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_lifetime() {
+        // Permanent (configured) address.
+        let info: AddressInfo = serde_json::from_str(
+            r#"{"family":"inet6","local":"fd00::1","prefixlen":64,"scope":"global","valid_life_time":4294967295,"preferred_life_time":4294967295}"#,
+        )
+        .unwrap();
+        assert_eq!(info.valid_life_time, Some(Lifetime::Forever));
+        assert_eq!(info.preferred_life_time, Some(Lifetime::Forever));
+
+        // Temporary (autoconfigured) address.
+        let info: AddressInfo = serde_json::from_str(
+            r#"{"family":"inet6","local":"2001:db8::5054:ff:fe12:3456","prefixlen":64,"scope":"global","dynamic":true,"mngtmpaddr":true,"valid_life_time":86337,"preferred_life_time":14337}"#,
+        )
+        .unwrap();
+        assert_eq!(info.valid_life_time, Some(Lifetime::Seconds(86337)));
+        assert_eq!(info.preferred_life_time, Some(Lifetime::Seconds(14337)));
+
+        let lifetimes: Vec<Lifetime> =
+            serde_json::from_str(r#"["forever", "300sec", "0"]"#).unwrap();
+        assert_eq!(
+            lifetimes,
+            vec![
+                Lifetime::Forever,
+                Lifetime::Seconds(300),
+                Lifetime::Seconds(0)
+            ]
+        );
+        assert!(serde_json::from_str::<Lifetime>(r#""never""#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_show_oneline() {
+        let test_namespace = "ip-command-test-oneline-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let oneline_client = namespace_client.with_oneline(true);
+        let result = async {
+            namespace_client
+                .address()
+                .add(AddressAddConfiguration {
+                    device: "lo".into(),
+                    local: "192.0.2.1/24".into(),
+                    label: Some("lo:test".into()),
+                    ..Default::default()
+                })
+                .await?;
+            namespace_client
+                .address()
+                .add(AddressAddConfiguration {
+                    device: "lo".into(),
+                    local: "2001:db8::1/64".into(),
+                    valid_lifetime: Some("100".into()),
+                    preferred_lifetime: Some("50".into()),
+                    ..Default::default()
+                })
+                .await
+        }
+        .await;
+        let addresses = oneline_client
+            .address()
+            .show(Some(AddressShowConfiguration {
+                device: Some("lo".into()),
+                ..Default::default()
+            }))
+            .await;
+        let interfaces = oneline_client.address().interfaces().await;
+        let links = oneline_client.link().show(None).await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        result.unwrap();
+        let address = addresses.unwrap().pop().unwrap();
+        assert_eq!(address.name, "lo");
+        assert_eq!(address.mtu, 65536);
+        let address_info = address.address_info.unwrap();
+        let ipv4 = address_info
+            .iter()
+            .find(|address| address.local == Some("192.0.2.1".into()))
+            .unwrap();
+        assert_eq!(ipv4.family, Some("inet".into()));
+        assert_eq!(ipv4.prefix_length, Some(24));
+        assert_eq!(ipv4.broadcast, None);
+        assert_eq!(ipv4.scope, Some(Scope::Global));
+        assert_eq!(ipv4.label, Some("lo:test".into()));
+        assert_eq!(ipv4.valid_life_time, Some(Lifetime::Forever));
+        let ipv6 = address_info
+            .iter()
+            .find(|address| address.local == Some("2001:db8::1".into()))
+            .unwrap();
+        assert_eq!(ipv6.prefix_length, Some(64));
+        assert!(matches!(ipv6.valid_life_time, Some(Lifetime::Seconds(seconds)) if seconds <= 100));
+        assert!(
+            matches!(ipv6.preferred_life_time, Some(Lifetime::Seconds(seconds)) if seconds <= 50)
+        );
+        let interfaces = interfaces.unwrap();
+        assert_eq!(interfaces.len(), 1);
+        assert_eq!(interfaces[0].link.name, "lo");
+        assert_eq!(interfaces[0].addresses.len(), address_info.len());
+        assert_eq!(links.unwrap()[0].link_type, Some("loopback".into()));
+    }
+
+    #[test]
+    fn test_parse_oneline_output() {
+        let addresses = IpAddressCommand::parse_oneline_output(concat!(
+            "5: ppp0    inet 10.0.0.1 peer 10.0.0.2/32 scope global ppp0\\       valid_lft forever preferred_lft forever\n",
+            "6: eth0    inet6 fe80::1/64 scope link noprefixroute \\       valid_lft forever preferred_lft 10sec\n",
+        ))
+        .unwrap();
+        let (index, address) = &addresses[0];
+        let address: AddressInfo = serde_json::from_value(address.clone()).unwrap();
+        assert_eq!(*index, 5);
+        assert_eq!(address.local, Some("10.0.0.1".into()));
+        assert_eq!(address.address, Some("10.0.0.2".into()));
+        assert_eq!(address.prefix_length, Some(32));
+        assert_eq!(address.label, Some("ppp0".into()));
+        let (index, address) = &addresses[1];
+        let address: AddressInfo = serde_json::from_value(address.clone()).unwrap();
+        assert_eq!(*index, 6);
+        assert_eq!(address.scope, Some(Scope::Link));
+        assert_eq!(address.no_prefix_route, Some(true));
+        assert_eq!(address.label, None);
+        assert_eq!(address.preferred_life_time, Some(Lifetime::Seconds(10)));
+    }
+
+    #[tokio::test]
+    async fn test_flush_refused_without_device() {
+        let client = IpCommand::new().unwrap().dry_run();
+        assert!(matches!(
+            client.address().flush(None).await,
+            Err(Error::RefusedUnsafeOperationError { .. })
+        ));
+        assert!(matches!(
+            client
+                .address()
+                .flush(Some(AddressFlushConfiguration {
+                    scope: Some(Scope::Global),
+                    ..Default::default()
+                }))
+                .await,
+            Err(Error::RefusedUnsafeOperationError { .. })
+        ));
+        client
+            .address()
+            .flush(Some(AddressFlushConfiguration {
+                scope: Some(Scope::Global),
+                confirm_flush_all: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        client.address().flush_all().await.unwrap();
+        assert_eq!(
+            client.recorded_commands(),
+            vec![
+                vec!["address", "flush", "scope", "global"],
+                vec!["address", "flush", "to", "0.0.0.0/0"],
+                vec!["address", "flush", "to", "::/0"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_prefix_route_metric() {
+        let test_namespace = "ip-command-test-address-metric-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let result = async {
+            // The prefix route of an address on loopback is a local one.
+            namespace_client
+                .link()
+                .add(LinkAddConfiguration {
+                    name: "veth0".into(),
+                    link_type: LinkKind::Veth(VethConfiguration {
+                        peer_name: Some("veth1".into()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .await?;
+            for device in &["veth0", "veth1"] {
+                namespace_client
+                    .link()
+                    .set(LinkSetConfiguration {
+                        device: LinkDeviceOrGroup::Device((*device).into()),
+                        state: Some(LinkStatus::Up),
+                        ..Default::default()
+                    })
+                    .await?;
+            }
+            namespace_client
+                .address()
+                .add(AddressAddConfiguration {
+                    device: "veth0".into(),
+                    local: "198.51.100.1/24".into(),
+                    metric: Some(300),
+                    ..Default::default()
+                })
+                .await?;
+            namespace_client
+                .route()
+                .list(Some(RouteShowConfiguration {
+                    to: Some("198.51.100.0/24".into()),
+                    ..Default::default()
+                }))
+                .await
+        }
+        .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        let routes = result.unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].destination, "198.51.100.0/24");
+        assert_eq!(routes[0].metric, Some(300));
+    }
+
+    #[tokio::test]
+    async fn test_flush_all() {
+        let test_namespace = "ip-command-test-flush-all-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let result = async {
+            for local in &["192.0.2.1/24", "2001:db8::1/64"] {
+                namespace_client
+                    .address()
+                    .add(AddressAddConfiguration {
+                        device: "lo".into(),
+                        local: (*local).into(),
+                        ..Default::default()
+                    })
+                    .await?;
+            }
+            namespace_client.address().flush_all().await?;
+            namespace_client.address().interfaces().await
+        }
+        .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        let interfaces = result.unwrap();
+        assert!(interfaces
+            .iter()
+            .all(|interface| interface.addresses.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_save_and_restore_large() {
+        let test_namespace = "ip-command-test-restore-large-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let result = async {
+            let script: String = (0..4_000)
+                .map(|i| format!("address add 10.{}.{}.1/32 dev lo\n", i / 250, i % 250))
+                .collect();
+            namespace_client
+                .raw_with_stdin(&["-batch", "-"], script.into_bytes())
+                .await?;
+            let count = || async {
+                let addresses = namespace_client
+                    .address()
+                    .show(Some(AddressShowConfiguration {
+                        device: Some("lo".into()),
+                        ..Default::default()
+                    }))
+                    .await?;
+                Ok::<_, Error>(addresses[0].address_info.as_ref().map_or(0, Vec::len))
+            };
+            let saved_count = count().await?;
+            let netlink_configuration = namespace_client
+                .address()
+                .save(Some(AddressSaveConfiguration {
+                    device: Some("lo".into()),
+                    ..Default::default()
+                }))
+                .await?;
+            namespace_client
+                .address()
+                .flush(Some(AddressFlushConfiguration {
+                    device: Some("lo".into()),
+                    ..Default::default()
+                }))
+                .await?;
+            let flushed_count = count().await?;
+            let saved_size = netlink_configuration.len();
+            namespace_client
+                .address()
+                .restore(netlink_configuration)
+                .await?;
+            Ok::<_, Error>((saved_size, saved_count, flushed_count, count().await?))
+        }
+        .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        let (saved_size, saved_count, flushed_count, restored_count) = result.unwrap();
+        // Larger than a pipe buffer.
+        assert!(saved_size > 64 * 1024);
+        assert!(saved_count >= 4_000);
+        assert_eq!(flushed_count, 0);
+        assert_eq!(restored_count, saved_count);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let client = IpCommand::new().unwrap();
+        let interfaces = client.snapshot().await.unwrap();
+        let loopback = interfaces
+            .iter()
+            .find(|interface| interface.link.name == "lo")
+            .unwrap();
+        assert!(loopback.link.link_flags().is_loopback());
+        assert!(loopback
+            .addresses
+            .iter()
+            .any(|address| address.local == Some("127.0.0.1".into())));
+
+        let client = client.dry_run_with_output(
+            r#"[{"ifindex":1,"ifname":"lo","flags":["LOOPBACK","UP"],"mtu":65536,"qdisc":"noqueue","operstate":"UNKNOWN","group":"default","txqlen":1000,"link_type":"loopback","address":"00:00:00:00:00:00","broadcast":"00:00:00:00:00:00","addr_info":[{"family":"inet","local":"127.0.0.1","prefixlen":8,"scope":"host","label":"lo","valid_life_time":4294967295,"preferred_life_time":4294967295},{}]}]"#,
+        );
+        let interfaces = client.snapshot().await.unwrap();
+        assert_eq!(client.recorded_commands().len(), 1);
+        assert_eq!(interfaces[0].link.mtu, 65536);
+        assert_eq!(interfaces[0].addresses.len(), 1);
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let prefix = |address| parse_prefix(address).unwrap();
+        assert!(overlaps(prefix("192.0.2.1/24"), prefix("192.0.2.129/25")));
+        assert!(overlaps(prefix("192.0.2.1/24"), prefix("192.0.2.7")));
+        assert!(!overlaps(prefix("192.0.2.1/25"), prefix("192.0.2.129/25")));
+        assert!(overlaps(prefix("2001:db8::1/64"), prefix("2001:db8::/48")));
+        assert!(!overlaps(
+            prefix("2001:db8::1/64"),
+            prefix("2001:db8:0:1::1/64")
+        ));
+        assert!(!overlaps(prefix("192.0.2.1/0"), prefix("::/0")));
+        assert_eq!(parse_prefix("192.0.2.1/33"), None);
+    }
+
+    #[tokio::test]
+    async fn test_add_checked() {
+        let link_name = "test_addr4";
+        let client = IpCommand::new().unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let address = client.address();
+        let add = |local: &str, reject_overlapping| {
+            address.add_checked(
+                AddressAddConfiguration {
+                    local: local.into(),
+                    device: link_name.into(),
+                    ..Default::default()
+                },
+                reject_overlapping,
+            )
+        };
+        let added = add("172.84.0.1/24", true).await;
+        let already_present = add("172.84.0.1/24", true).await;
+        let rejected = add("172.84.0.129/25", true).await;
+        let overlapping = add("172.84.0.129/25", false).await;
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(added.unwrap(), AddressAddOutcome::Added);
+        assert_eq!(already_present.unwrap(), AddressAddOutcome::AlreadyPresent);
+        assert!(matches!(
+            rejected,
+            Err(Error::OverlappingAddressError { existing, .. }) if existing == "172.84.0.1/24"
+        ));
+        assert_eq!(
+            overlapping.unwrap(),
+            AddressAddOutcome::AddedOverlapping(vec![format!("{} 172.84.0.1/24", link_name)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ensure() {
+        let link_name = "test_addr5";
+        let client = IpCommand::new().unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let address = client.address();
+        let ensure = |local: &str| {
+            address.ensure(AddressAddConfiguration {
+                local: local.into(),
+                device: link_name.into(),
+                ..Default::default()
+            })
+        };
+        let added = ensure("172.84.1.1/24").await;
+        let already_present = ensure("172.84.1.1/24").await;
+        let other_prefix_length = ensure("172.84.1.1/25").await;
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        assert!(added.unwrap());
+        assert!(!already_present.unwrap());
+        // The same local address with another prefix length is a distinct address.
+        assert!(other_prefix_length.unwrap());
+    }
 
     #[tokio::test]
     async fn test_add_and_show() {
@@ -457,7 +1462,7 @@ mod tests {
         let addresses = client
             .address()
             .show(Some(AddressShowConfiguration {
-                device: link_name.into(),
+                device: Some(link_name.into()),
                 ..Default::default()
             }))
             .await
@@ -523,7 +1528,7 @@ mod tests {
         let addresses = client
             .address()
             .show(Some(AddressShowConfiguration {
-                device: link_name.into(),
+                device: Some(link_name.into()),
                 ..Default::default()
             }))
             .await
@@ -580,7 +1585,7 @@ mod tests {
         let addresses = client
             .address()
             .show(Some(AddressShowConfiguration {
-                device: link_name.into(),
+                device: Some(link_name.into()),
                 ..Default::default()
             }))
             .await
@@ -652,7 +1657,7 @@ mod tests {
         let addresses = client
             .address()
             .show(Some(AddressShowConfiguration {
-                device: link_name.into(),
+                device: Some(link_name.into()),
                 ..Default::default()
             }))
             .await
@@ -0,0 +1,213 @@
+/*
+ * Copyright 2020 fsyncd, Berlin, Germany.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::*;
+
+/// Commands to run, in order, from a single ip(8) process (`ip -batch`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Batch {
+    lines: Vec<Vec<String>>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a command, given by its arguments (eg. `["link", "set", "dev", "eth0", "up"]`).
+    pub fn command<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.lines.push(args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// The number of commands in the batch.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// The batch file, arguments are quoted where ip(8) would otherwise split them.
+    fn script(&self) -> Result<String, Error> {
+        let mut script = String::new();
+        for line in &self.lines {
+            let args = line
+                .iter()
+                .map(|arg| Self::quote(arg))
+                .collect::<Result<Vec<_>, _>>()?;
+            script.push_str(&args.join(" "));
+            script.push('\n');
+        }
+        Ok(script)
+    }
+
+    fn quote(arg: &str) -> Result<String, Error> {
+        if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "#\"'".contains(c)) {
+            return Ok(arg.into());
+        }
+        // There is no escaping within quotes.
+        let both_quotes = arg.contains('"') && arg.contains('\'');
+        ensure!(
+            !arg.contains('\n') && !both_quotes,
+            InvalidArgumentError {
+                field: "batch",
+                value: arg
+            }
+        );
+        Ok(if arg.contains('"') {
+            format!("'{}'", arg)
+        } else {
+            format!("\"{}\"", arg)
+        })
+    }
+
+    /// The line reported by ip(8) as having failed (eg. "Command failed -:2").
+    fn failed_line(stderr: &str) -> Option<usize> {
+        stderr
+            .lines()
+            .filter_map(|line| line.strip_prefix("Command failed -:"))
+            .find_map(|line| line.trim().parse().ok())
+    }
+}
+
+#[derive(Clone)]
+pub struct IpBatchCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpBatchCommand<'l> {
+    pub fn new(ip_command: &'l IpCommand) -> Self {
+        Self { ip_command }
+    }
+
+    /// Run the commands of the batch, stopping at the first which fails.
+    ///
+    /// A batch is not atomic, when a command fails the commands before it have already been
+    /// applied. The failure is reported as a `BatchFailedError` with the (1-based) line of the
+    /// failed command and the number of commands applied, so the caller can roll them back.
+    /// Commands ip(8) cannot parse abort the batch without reporting the line, and are
+    /// reported as a `CommandFailedError`.
+    pub async fn run(&self, batch: &Batch) -> Result<(), Error> {
+        let script = batch.script()?;
+        let result = self
+            .ip_command
+            .command_without_output(&["-batch", "-"], Some(script.into_bytes()))
+            .await;
+        match result {
+            Err(Error::CommandFailedError { stdout, stderr }) => {
+                Err(match Batch::failed_line(&stderr) {
+                    Some(line) => Error::BatchFailedError {
+                        line,
+                        applied: line - 1,
+                        stderr,
+                    },
+                    None => Error::CommandFailedError { stdout, stderr },
+                })
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::link::*;
+
+    #[test]
+    fn test_script() {
+        let batch = Batch::new()
+            .command(vec!["link", "set", "dev", "eth0", "alias", "uplink port"])
+            .command(vec![
+                "link",
+                "set",
+                "dev",
+                "eth0",
+                "alias",
+                "\"quoted\" # port",
+            ])
+            .command(vec!["link", "set", "dev", "eth0", "alias", ""]);
+        assert_eq!(
+            batch.script().unwrap(),
+            "link set dev eth0 alias \"uplink port\"\n\
+             link set dev eth0 alias '\"quoted\" # port'\n\
+             link set dev eth0 alias \"\"\n"
+        );
+
+        let batch = Batch::new().command(vec!["link", "set", "dev", "eth0", "alias", "'\""]);
+        assert!(matches!(
+            batch.script(),
+            Err(Error::InvalidArgumentError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_failed_line() {
+        assert_eq!(
+            Batch::failed_line("RTNETLINK answers: File exists\nCommand failed -:2\n"),
+            Some(2)
+        );
+        assert_eq!(
+            Batch::failed_line("Command \"bogus\" is unknown, try \"ip link help\".\n"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_partially_applied() {
+        let link_name = "test_batch0";
+
+        let client = IpCommand::new().unwrap();
+        let batch = Batch::new()
+            .command(vec!["link", "add", "name", link_name, "type", "dummy"])
+            .command(vec!["link", "set", "dev", "test_batch_missing", "up"])
+            .command(vec!["link", "set", "dev", link_name, "up"]);
+        let result = client.batch().run(&batch).await;
+
+        let links = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        match result {
+            Err(Error::BatchFailedError { line, applied, .. }) => {
+                assert_eq!(line, 2);
+                assert_eq!(applied, 1);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        // The first command was applied, the last was not.
+        assert!(!links[0].link_flags().is_up());
+    }
+}
@@ -0,0 +1,255 @@
+/*
+ * Copyright 2020 fsyncd, Berlin, Germany.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::*;
+use serde::{Deserialize, Serialize};
+use serde_command_opts::{BooleanType, Serializer};
+use snafu::ResultExt;
+use std::fmt;
+
+/// Bridge port VLAN configuration flags.
+#[derive(Clone, Debug)]
+pub enum BridgeVlanFlag {
+    /// The VLAN specified is to be considered a PVID at ingress.
+    PortVlanId,
+    /// The VLAN specified is to be treated as untagged on egress.
+    Untagged,
+    /// The VLAN is configured on the software bridge (default).
+    Master,
+    /// The VLAN is configured on the specified physical device.
+    Itself,
+}
+
+impl fmt::Display for BridgeVlanFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::PortVlanId => "pvid",
+            Self::Untagged => "untagged",
+            Self::Master => "master",
+            Self::Itself => "self",
+        })
+    }
+}
+
+impl Serialize for BridgeVlanFlag {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Add/delete bridge port VLAN configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BridgeVlanAddOrDeleteConfiguration {
+    /// The interface with which this VLAN is associated.
+    #[serde(rename = "dev")]
+    pub device: String,
+    /// The VLAN id (or range of ids, eg. "10-20").
+    #[serde(rename = "vid")]
+    pub vlan_id: String,
+    /// Optional configuration flags.
+    pub flags: Option<Vec<BridgeVlanFlag>>,
+}
+
+pub type BridgeVlanAddConfiguration = BridgeVlanAddOrDeleteConfiguration;
+pub type BridgeVlanDeleteConfiguration = BridgeVlanAddOrDeleteConfiguration;
+
+/// List bridge port VLAN configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BridgeVlanShowConfiguration {
+    /// Only list VLANs of this interface.
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+}
+
+/// The returned VLAN (or range of VLANs) of a bridge port.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BridgeVlan {
+    #[serde(rename = "vlan")]
+    pub vlan_id: u16,
+    #[serde(rename = "vlanEnd")]
+    pub vlan_id_end: Option<u16>,
+    #[serde(default)]
+    pub flags: Vec<String>,
+}
+
+/// The returned bridge port structure.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BridgeVlanPort {
+    #[serde(rename = "ifname")]
+    pub name: String,
+    pub vlans: Vec<BridgeVlan>,
+}
+
+#[derive(Clone)]
+pub struct IpBridgeVlanCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpBridgeVlanCommand<'l> {
+    pub fn new(ip_command: &'l IpCommand) -> Self {
+        Self { ip_command }
+    }
+
+    /// Add a new VLAN filter entry.
+    pub async fn add(&self, configuration: BridgeVlanAddConfiguration) -> Result<(), Error> {
+        let mut args: Vec<String> = vec!["vlan".into(), "add".into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        self.ip_command
+            .bridge_command(&args, false)
+            .await
+            .map(|_| ())
+    }
+
+    /// Delete a VLAN filter entry.
+    pub async fn delete(&self, configuration: BridgeVlanDeleteConfiguration) -> Result<(), Error> {
+        let mut args: Vec<String> = vec!["vlan".into(), "del".into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        self.ip_command
+            .bridge_command(&args, false)
+            .await
+            .map(|_| ())
+    }
+
+    /// List VLAN configuration.
+    pub async fn show(
+        &self,
+        configuration: Option<BridgeVlanShowConfiguration>,
+    ) -> Result<Vec<BridgeVlanPort>, Error> {
+        let mut args: Vec<String> = vec!["vlan".into(), "show".into()];
+        if let Some(configuration) = configuration {
+            args.append(
+                &mut Serializer::new(BooleanType::OnOff)
+                    .into_args(&configuration)
+                    .context(CommandOptionsSerializationError {})?,
+            );
+        }
+        let output = self.ip_command.bridge_command(&args, true).await?;
+        serde_json::from_str(&output).context(JsonDeserializationError {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::link::*;
+
+    #[tokio::test]
+    async fn test_add_show_and_delete() {
+        let bridge_name = "test_br0";
+        let port_name = "test_brport0";
+        let client = IpCommand::new().unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: bridge_name.into(),
+                link_type: "bridge".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: port_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(port_name.into()),
+                master: Some(MasterSetConfiguration::Enslaved(bridge_name.into())),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .bridge_vlan()
+            .add(BridgeVlanAddConfiguration {
+                device: port_name.into(),
+                vlan_id: "10".into(),
+                flags: Some(vec![BridgeVlanFlag::PortVlanId, BridgeVlanFlag::Untagged]),
+            })
+            .await
+            .unwrap();
+
+        let ports = client
+            .bridge_vlan()
+            .show(Some(BridgeVlanShowConfiguration {
+                device: Some(port_name.into()),
+            }))
+            .await
+            .unwrap();
+
+        client
+            .bridge_vlan()
+            .delete(BridgeVlanDeleteConfiguration {
+                device: port_name.into(),
+                vlan_id: "10".into(),
+                flags: None,
+            })
+            .await
+            .unwrap();
+
+        for link_name in &[port_name, bridge_name] {
+            client
+                .link()
+                .delete(LinkDeleteConfiguration {
+                    device: LinkDeviceOrGroup::Device((*link_name).into()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        let vlan = ports[0]
+            .vlans
+            .iter()
+            .find(|vlan| vlan.vlan_id == 10)
+            .unwrap();
+        assert!(vlan.flags.contains(&"PVID".to_string()));
+        assert!(vlan.flags.contains(&"Egress Untagged".to_string()));
+    }
+
+    #[test]
+    fn test_serialize_add() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&BridgeVlanAddConfiguration {
+                device: "eth0".into(),
+                vlan_id: "10".into(),
+                flags: Some(vec![BridgeVlanFlag::PortVlanId, BridgeVlanFlag::Untagged]),
+            })
+            .unwrap();
+        assert_eq!(args, vec!["dev", "eth0", "vid", "10", "pvid", "untagged"]);
+    }
+}
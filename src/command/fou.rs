@@ -0,0 +1,237 @@
+/*
+ * Copyright 2020 fsyncd, Berlin, Germany.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::command::neighbor::deserialize_flag;
+use crate::*;
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Serialize};
+use serde_command_opts::{BooleanType, Serializer};
+use snafu::ResultExt;
+
+/// What a foo over UDP receive port decapsulates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FouEncapsulation {
+    /// Bare packets of the given IP protocol (eg. 4 for IPIP, 47 for GRE).
+    IpProtocol(u8),
+    /// Generic UDP encapsulation, whose header carries the protocol.
+    #[default]
+    Gue,
+}
+
+impl Serialize for FouEncapsulation {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::IpProtocol(protocol) => {
+                let mut seq = serializer.serialize_seq(Some(2))?;
+                seq.serialize_element("ipproto")?;
+                seq.serialize_element(&protocol.to_string())?;
+                seq.end()
+            }
+            Self::Gue => {
+                let mut seq = serializer.serialize_seq(Some(1))?;
+                seq.serialize_element("gue")?;
+                seq.end()
+            }
+        }
+    }
+}
+
+/// Add foo over UDP receive port configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FouAddConfiguration {
+    /// The UDP port to receive on.
+    pub port: u16,
+    /// What the received packets encapsulate.
+    pub encapsulation: FouEncapsulation,
+    /// Only receive packets sent to this local address.
+    pub local: Option<String>,
+    /// Only receive packets sent by this peer address.
+    pub peer: Option<String>,
+    /// Only receive packets sent from this peer port.
+    pub peer_port: Option<u16>,
+    /// Only receive packets arriving on this device.
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+}
+
+/// Delete foo over UDP receive port configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FouDeleteConfiguration {
+    /// The UDP port of the receive port.
+    pub port: u16,
+    /// The local address of the receive port.
+    pub local: Option<String>,
+    /// The peer address of the receive port.
+    pub peer: Option<String>,
+    /// The peer port of the receive port.
+    pub peer_port: Option<u16>,
+    /// The device of the receive port.
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+}
+
+/// The returned foo over UDP receive port structure.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fou {
+    pub port: u16,
+    #[serde(rename = "ipproto")]
+    pub ip_protocol: Option<u8>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub gue: bool,
+    /// Only reported for IPv6 receive ports.
+    pub family: Option<String>,
+    pub local: Option<String>,
+    pub peer: Option<String>,
+    pub peer_port: Option<u16>,
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+}
+
+/// Encapsulation of the packets of a tunnel device in UDP (see `IpCommand::fou`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TunnelEncap {
+    /// Generic UDP encapsulation (gue), rather than foo over UDP (fou).
+    pub gue: bool,
+    /// The UDP source port, chosen per flow when unset.
+    pub source_port: Option<u16>,
+    /// The UDP destination port (the receive port of the peer).
+    pub destination_port: u16,
+}
+
+impl Serialize for TunnelEncap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(6))?;
+        seq.serialize_element("encap")?;
+        seq.serialize_element(if self.gue { "gue" } else { "fou" })?;
+        seq.serialize_element("encap-sport")?;
+        seq.serialize_element(
+            &self
+                .source_port
+                .map_or_else(|| "auto".to_string(), |port| port.to_string()),
+        )?;
+        seq.serialize_element("encap-dport")?;
+        seq.serialize_element(&self.destination_port.to_string())?;
+        seq.end()
+    }
+}
+
+#[derive(Clone)]
+pub struct IpFouCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpFouCommand<'l> {
+    pub fn new(ip_command: &'l IpCommand) -> Self {
+        Self { ip_command }
+    }
+
+    /// Add a receive port.
+    pub async fn add(&self, configuration: FouAddConfiguration) -> Result<(), Error> {
+        let mut args: Vec<String> = vec!["fou".into(), "add".into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        self.ip_command.command_without_output(&args, None).await
+    }
+
+    /// Delete a receive port.
+    pub async fn delete(&self, configuration: FouDeleteConfiguration) -> Result<(), Error> {
+        let mut args: Vec<String> = vec!["fou".into(), "del".into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        self.ip_command.command_without_output(&args, None).await
+    }
+
+    /// List receive ports.
+    pub async fn show(&self) -> Result<Vec<Fou>, Error> {
+        let output = self
+            .ip_command
+            .command(&["fou", "show"], true, false, None)
+            .await?;
+        serde_json::from_str(&output).context(JsonDeserializationError {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_encapsulation() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&FouAddConfiguration {
+                port: 5555,
+                encapsulation: FouEncapsulation::IpProtocol(4),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(args, vec!["port", "5555", "ipproto", "4"]);
+
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&FouAddConfiguration {
+                port: 5555,
+                device: Some("eth0".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(args, vec!["port", "5555", "gue", "dev", "eth0"]);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_show() {
+        let port = 5555;
+
+        let client = IpCommand::new().unwrap();
+        client
+            .fou()
+            .add(FouAddConfiguration {
+                port,
+                encapsulation: FouEncapsulation::IpProtocol(4),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let receive_ports = client.fou().show().await.unwrap();
+
+        client
+            .fou()
+            .delete(FouDeleteConfiguration {
+                port,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let receive_port = receive_ports
+            .iter()
+            .find(|receive_port| receive_port.port == port)
+            .unwrap();
+        assert_eq!(receive_port.ip_protocol, Some(4));
+        assert!(!receive_port.gue);
+    }
+}
@@ -14,11 +14,18 @@
  * limitations under the License.
  */
 
+use crate::command::fou::TunnelEncap;
+#[cfg(feature = "streaming")]
+use crate::command::monitor::{MonitorEvent, MonitorObject};
 use crate::*;
-use serde::ser::SerializeSeq;
+#[cfg(feature = "streaming")]
+use futures::StreamExt;
+use serde::ser::{SerializeSeq, SerializeStruct};
 use serde::{Deserialize, Serialize};
 use serde_command_opts::{BooleanType, Serializer};
-use snafu::ResultExt;
+use snafu::{ensure, OptionExt, ResultExt};
+use std::fmt;
+use std::ops;
 
 /// Virtual link add device configuration.
 #[derive(Clone, Debug, Default, Serialize)]
@@ -51,24 +58,436 @@ pub struct LinkAddConfiguration {
     /// Maximum number of a Generic Segment Offload segments the device should accept.
     #[serde(rename = "gso_max_segs")]
     pub gso_maximum_segments: Option<u32>,
-    /// Type of the device.
+    /// The group the device belongs to.
+    pub group: Option<u32>,
+    /// Type of the device, along with any type specific arguments.
     #[serde(rename = "type")]
-    pub link_type: String,
+    pub link_type: LinkKind,
 }
 
+/// Type of a virtual link along with its type specific arguments.
 #[derive(Clone, Debug)]
-pub enum LinkDeviceOrGroup {
-    Device(String),
-    DeviceGroup(u32),
-    None,
+pub enum LinkKind {
+    /// Link type without any type specific arguments (eg. "dummy").
+    Generic(String),
+    /// Generic routing encapsulation tunnel over IPv4.
+    Gre(GreConfiguration),
+    /// Generic routing encapsulation tunnel over IPv6.
+    Ip6Gre(GreConfiguration),
+    /// Ethernet over generic routing encapsulation over IPv4 (can be bridged).
+    Gretap(GreConfiguration),
+    /// Ethernet over generic routing encapsulation over IPv6 (can be bridged).
+    Ip6Gretap(GreConfiguration),
+    /// Bonded (aggregated) device.
+    Bond(BondConfiguration),
+    /// MAC address based virtual LAN.
+    Macvlan(MacvlanConfiguration),
+    /// IP address based virtual LAN.
+    Ipvlan(IpvlanConfiguration),
+    /// WireGuard tunnel, keys and peers are configured with wg(8).
+    Wireguard,
+    /// Virtual routing and forwarding domain.
+    Vrf(VrfConfiguration),
+    /// 802.1Q virtual LAN.
+    Vlan(VlanConfiguration),
+    /// Virtual ethernet pair.
+    Veth(VethConfiguration),
+    /// Ethernet bridge.
+    Bridge(BridgeConfiguration),
 }
 
-impl Default for LinkDeviceOrGroup {
+impl Default for LinkKind {
     fn default() -> Self {
-        Self::None
+        Self::Generic(String::new())
+    }
+}
+
+impl From<&str> for LinkKind {
+    fn from(link_type: &str) -> Self {
+        Self::Generic(link_type.into())
+    }
+}
+
+impl Serialize for LinkKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::Generic(link_type) => serializer.serialize_str(link_type),
+            Self::Gre(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 1, "gre", configuration)
+            }
+            Self::Ip6Gre(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 2, "ip6gre", configuration)
+            }
+            Self::Bond(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 3, "bond", configuration)
+            }
+            Self::Macvlan(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 4, "macvlan", configuration)
+            }
+            Self::Ipvlan(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 5, "ipvlan", configuration)
+            }
+            Self::Wireguard => serializer.serialize_str("wireguard"),
+            Self::Vrf(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 7, "vrf", configuration)
+            }
+            Self::Vlan(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 8, "vlan", configuration)
+            }
+            Self::Gretap(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 9, "gretap", configuration)
+            }
+            Self::Ip6Gretap(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 10, "ip6gretap", configuration)
+            }
+            Self::Veth(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 11, "veth", configuration)
+            }
+            Self::Bridge(configuration) => {
+                serializer.serialize_newtype_variant("LinkKind", 12, "bridge", configuration)
+            }
+        }
+    }
+}
+
+/// Generic routing encapsulation tunnel configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct GreConfiguration {
+    /// Local address of the tunnel.
+    pub local: Option<String>,
+    /// Remote address of the tunnel.
+    pub remote: Option<String>,
+    /// Key to use for keyed GRE, in both directions.
+    pub key: Option<u32>,
+    /// Encapsulate the tunnel packets in UDP.
+    pub encapsulation: Option<TunnelEncap>,
+}
+
+/// Bonding policy of a bond device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum BondMode {
+    /// Transmit in sequential order from the first available slave through the last.
+    #[default]
+    BalanceRoundRobin,
+    /// Only one slave is active, another becomes active if the active slave fails.
+    ActiveBackup,
+    /// Transmit based on the selected transmit hash policy.
+    BalanceXor,
+    /// Transmit everything on all slaves.
+    Broadcast,
+    /// IEEE 802.3ad dynamic link aggregation (LACP).
+    Ieee8023ad,
+    /// Adaptive transmit load balancing.
+    BalanceTlb,
+    /// Adaptive load balancing.
+    BalanceAlb,
+}
+
+impl fmt::Display for BondMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::BalanceRoundRobin => "balance-rr",
+            Self::ActiveBackup => "active-backup",
+            Self::BalanceXor => "balance-xor",
+            Self::Broadcast => "broadcast",
+            Self::Ieee8023ad => "802.3ad",
+            Self::BalanceTlb => "balance-tlb",
+            Self::BalanceAlb => "balance-alb",
+        })
+    }
+}
+
+impl Serialize for BondMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Bond device configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BondConfiguration {
+    /// Bonding policy.
+    pub mode: BondMode,
+    /// MII link monitoring frequency in milliseconds.
+    #[serde(rename = "miimon")]
+    pub mii_monitor_interval: Option<u32>,
+    /// Rate at which LACPDU packets are requested from the link partner in 802.3ad mode
+    /// ("slow" or "fast").
+    pub lacp_rate: Option<String>,
+    /// Transmit hash policy used for slave selection in balance-xor and 802.3ad modes
+    /// (eg. "layer2", "layer3+4").
+    #[serde(rename = "xmit_hash_policy")]
+    pub transmit_hash_policy: Option<String>,
+    /// ARP link monitoring frequency in milliseconds, for slaves without MII carrier detection.
+    pub arp_interval: Option<u32>,
+    /// The addresses ARP monitoring probes (at most 16).
+    #[serde(serialize_with = "serialize_arp_ip_target")]
+    pub arp_ip_target: Vec<String>,
+    /// Which slaves validate the ARP replies and probes they receive
+    /// (eg. "none", "active", "backup", "all").
+    pub arp_validate: Option<String>,
+}
+
+/// The ARP targets are a single comma separated list, omitted entirely when empty.
+fn serialize_arp_ip_target<S>(targets: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let mut seq = serializer.serialize_seq(None)?;
+    if !targets.is_empty() {
+        seq.serialize_element("arp_ip_target")?;
+        seq.serialize_element(&targets.join(","))?;
+    }
+    seq.end()
+}
+
+/// Mode of a macvlan device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum MacvlanMode {
+    /// Do not allow communication between macvlan instances on the same physical interface.
+    Private,
+    /// Traffic between macvlan instances is sent through the physical interface (802.1Qbg).
+    #[default]
+    Vepa,
+    /// Allow direct communication between macvlan instances on the same physical interface.
+    Bridge,
+    /// Assign the physical interface to a single macvlan instance.
+    Passthrough,
+}
+
+impl fmt::Display for MacvlanMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Private => "private",
+            Self::Vepa => "vepa",
+            Self::Bridge => "bridge",
+            Self::Passthrough => "passthru",
+        })
+    }
+}
+
+impl Serialize for MacvlanMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Macvlan device configuration, the parent device is set with `LinkAddConfiguration::device`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MacvlanConfiguration {
+    /// Mode of the device.
+    pub mode: MacvlanMode,
+}
+
+/// Mode of an ipvlan device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum IpvlanMode {
+    /// Layer 2 mode, the parent device handles switching and neighbor discovery.
+    L2,
+    /// Layer 3 mode, the parent device acts as a router.
+    #[default]
+    L3,
+    /// Layer 3 mode with iptables (conntrack) support.
+    L3Symmetric,
+}
+
+impl fmt::Display for IpvlanMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::L2 => "l2",
+            Self::L3 => "l3",
+            Self::L3Symmetric => "l3s",
+        })
+    }
+}
+
+impl Serialize for IpvlanMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Communication between ipvlan slaves of the same parent device.
+#[derive(Clone, Debug)]
+pub enum IpvlanFlag {
+    /// Allow communication between slaves (default).
+    Bridge,
+    /// Do not allow communication between slaves.
+    Private,
+    /// Send traffic between slaves through the parent device.
+    Vepa,
+}
+
+impl fmt::Display for IpvlanFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bridge => "bridge",
+            Self::Private => "private",
+            Self::Vepa => "vepa",
+        })
+    }
+}
+
+impl Serialize for IpvlanFlag {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(1))?;
+        seq.serialize_element(&self.to_string())?;
+        seq.end()
+    }
+}
+
+/// Ipvlan device configuration, the parent device is set with `LinkAddConfiguration::device`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct IpvlanConfiguration {
+    /// Mode of the device.
+    pub mode: IpvlanMode,
+    /// Communication between slaves.
+    pub flags: Option<IpvlanFlag>,
+}
+
+/// Virtual routing and forwarding device configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VrfConfiguration {
+    /// The routing table associated with the device.
+    pub table: u32,
+}
+
+/// VLAN device configuration, the parent device is set with `LinkAddConfiguration::device`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VlanConfiguration {
+    /// The VLAN protocol ("802.1Q" by default, or "802.1ad").
+    pub protocol: Option<String>,
+    /// The VLAN identifier.
+    pub id: u16,
+    /// Mapping of VLAN header priority (PCP) to packet priority on incoming frames,
+    /// as (from, to) pairs.
+    #[serde(serialize_with = "serialize_ingress_qos_map")]
+    pub ingress_qos_map: Vec<(u32, u32)>,
+    /// Mapping of packet priority to VLAN header priority (PCP) on outgoing frames,
+    /// as (from, to) pairs.
+    #[serde(serialize_with = "serialize_egress_qos_map")]
+    pub egress_qos_map: Vec<(u32, u32)>,
+    /// Reorder the outgoing VLAN header (on by default).
+    #[serde(rename = "reorder_hdr")]
+    pub reorder_header: Option<bool>,
+    /// Register the VLAN with the switch using GARP VLAN Registration Protocol.
+    pub gvrp: Option<bool>,
+    /// Register the VLAN with the switch using Multiple VLAN Registration Protocol.
+    pub mvrp: Option<bool>,
+    /// Keep the operational state of the VLAN independent of that of its parent device.
+    pub loose_binding: Option<bool>,
+}
+
+fn serialize_ingress_qos_map<S>(map: &[(u32, u32)], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serialize_qos_map("ingress-qos-map", map, serializer)
+}
+
+fn serialize_egress_qos_map<S>(map: &[(u32, u32)], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serialize_qos_map("egress-qos-map", map, serializer)
+}
+
+/// A QoS map is a single keyword followed by "from:to" pairs, omitted entirely when empty.
+fn serialize_qos_map<S>(keyword: &str, map: &[(u32, u32)], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let mut seq = serializer.serialize_seq(None)?;
+    if !map.is_empty() {
+        seq.serialize_element(keyword)?;
+        for (from, to) in map {
+            seq.serialize_element(&format!("{}:{}", from, to))?;
+        }
+    }
+    seq.end()
+}
+
+/// Virtual ethernet pair configuration, the kernel names the peer when unset.
+#[derive(Clone, Debug, Default)]
+pub struct VethConfiguration {
+    /// Name of the peer device.
+    pub peer_name: Option<String>,
+    /// Network namespace (by name) to create the peer in, rather than that of the device.
+    pub peer_namespace: Option<String>,
+}
+
+impl Serialize for VethConfiguration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // The peer is a link configuration of its own, eg. "peer name veth1 netns test".
+        let mut peer: Vec<&str> = vec![];
+        if let Some(name) = &self.peer_name {
+            peer.extend_from_slice(&["name", name]);
+        }
+        if let Some(namespace) = &self.peer_namespace {
+            peer.extend_from_slice(&["netns", namespace]);
+        }
+        if !peer.is_empty() {
+            peer.insert(0, "peer");
+        }
+        let mut state = serializer.serialize_struct("VethConfiguration", 1)?;
+        state.serialize_field("peer", &peer)?;
+        state.end()
+    }
+}
+
+/// Bridge device configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BridgeConfiguration {
+    /// Enable the spanning tree protocol.
+    #[serde(serialize_with = "serialize_numeric_flag")]
+    pub stp_state: Option<bool>,
+    /// Filter the traffic of the ports by VLAN (see `IpCommand::bridge_vlan`).
+    #[serde(serialize_with = "serialize_numeric_flag")]
+    pub vlan_filtering: Option<bool>,
+    /// The VLAN assigned to untagged traffic of new ports (0 for none).
+    #[serde(rename = "vlan_default_pvid")]
+    pub vlan_default_port_vlan_id: Option<u16>,
+    /// Lifetime of learned MAC addresses, in hundredths of a second.
+    pub ageing_time: Option<u32>,
+}
+
+/// Bridge options are enabled by "1" rather than "on".
+fn serialize_numeric_flag<S>(flag: &Option<bool>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match flag {
+        Some(flag) => serializer.serialize_u8(*flag as u8),
+        None => serializer.serialize_none(),
     }
 }
 
+#[derive(Clone, Debug, Default)]
+pub enum LinkDeviceOrGroup {
+    Device(String),
+    DeviceGroup(u32),
+    #[default]
+    None,
+}
+
 impl Serialize for LinkDeviceOrGroup {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -154,9 +573,46 @@ impl Serialize for MasterSetConfiguration {
     }
 }
 
-#[derive(Clone, Debug)]
+/// How a device generates its IPv6 link-local (and SLAAC) addresses.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AddressGenerationMode {
+    /// Derive the interface identifier from the link layer address.
+    Eui64,
+    /// Do not generate a link-local address.
+    None,
+    /// Stable privacy addresses (RFC 7217), requires the device's stable secret.
+    StablePrivacy,
+    /// Stable privacy addresses derived from a random secret, when no secret has been set.
+    RandomFallback,
+    /// Any other mode supported by ip(8).
+    Raw(String),
+}
+
+impl fmt::Display for AddressGenerationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Eui64 => "eui64",
+            Self::None => "none",
+            Self::StablePrivacy => "stable_secret",
+            Self::RandomFallback => "random",
+            Self::Raw(mode) => mode,
+        })
+    }
+}
+
+impl Serialize for AddressGenerationMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub enum ExpressDataPathVariant {
     /// Kernel gets to choose the best available mode.
+    #[default]
     Default,
     /// Use the slow generic fallback mode.
     Generic,
@@ -166,25 +622,20 @@ pub enum ExpressDataPathVariant {
     Offload,
 }
 
-impl Default for ExpressDataPathVariant {
-    fn default() -> Self {
-        Self::Default
-    }
-}
-
-impl ToString for ExpressDataPathVariant {
-    fn to_string(&self) -> String {
-        match self {
-            ExpressDataPathVariant::Default => "xdp".into(),
-            ExpressDataPathVariant::Generic => "xdpgeneric".into(),
-            ExpressDataPathVariant::Driver => "xdpdrv".into(),
-            ExpressDataPathVariant::Offload => "xdpoffload".into(),
-        }
+impl fmt::Display for ExpressDataPathVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExpressDataPathVariant::Default => "xdp",
+            ExpressDataPathVariant::Generic => "xdpgeneric",
+            ExpressDataPathVariant::Driver => "xdpdrv",
+            ExpressDataPathVariant::Offload => "xdpoffload",
+        })
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum ExpressDataPathConfiguration {
+    #[default]
     Off,
     Pinned {
         variant: ExpressDataPathVariant,
@@ -199,12 +650,6 @@ pub enum ExpressDataPathConfiguration {
     },
 }
 
-impl Default for ExpressDataPathConfiguration {
-    fn default() -> Self {
-        Self::Off
-    }
-}
-
 impl Serialize for ExpressDataPathConfiguration {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -261,6 +706,90 @@ impl Serialize for ExpressDataPathConfiguration {
     }
 }
 
+/// Configuration of a virtual function of an SR-IOV capable device.
+///
+/// Attributes which are left unset keep their current value, which for a new virtual function
+/// is the driver default: for most drivers spoof checking is enabled (frames with a source MAC
+/// address other than the one assigned to the virtual function are dropped) and the virtual
+/// function is not trusted (it can neither change its MAC address nor enable promiscuous or
+/// all-multicast reception).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VfConfiguration {
+    /// Index of the virtual function.
+    pub vf: u32,
+    /// MAC address assigned to the virtual function.
+    pub mac: Option<String>,
+    /// VLAN tag inserted into, and stripped from, all traffic of the virtual function.
+    pub vlan: Option<u16>,
+    /// Priority bits of the VLAN tag (requires `vlan`).
+    pub qos: Option<u8>,
+    /// Maximum transmit bandwidth in Mbps (0 disables the limit).
+    pub max_tx_rate: Option<u32>,
+    /// Minimum guaranteed transmit bandwidth in Mbps (0 disables the guarantee).
+    pub min_tx_rate: Option<u32>,
+    /// Drop frames whose source MAC address is not the one assigned to the virtual function.
+    pub spoof_checking: Option<bool>,
+    /// Allow the virtual function to change its MAC address and to enable promiscuous or
+    /// all-multicast reception.
+    pub trust: Option<bool>,
+    /// Link state reported to the virtual function ("auto", "enable" or "disable").
+    pub state: Option<String>,
+}
+
+impl VfConfiguration {
+    /// Configuration which explicitly disables spoof checking and trusts the virtual function,
+    /// as required by network functions which bond, run VRRP or otherwise send from (or listen
+    /// on) addresses other than the assigned MAC address. Only use these for guests which are
+    /// trusted with the traffic of the physical network.
+    pub fn sane_defaults(vf: u32) -> Self {
+        Self {
+            vf,
+            spoof_checking: Some(false),
+            trust: Some(true),
+            ..Default::default()
+        }
+    }
+}
+
+impl Serialize for VfConfiguration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        let mut args = vec!["vf".to_string(), self.vf.to_string()];
+        if let Some(mac) = &self.mac {
+            args.extend(vec!["mac".into(), mac.clone()]);
+        }
+        if let Some(vlan) = self.vlan {
+            args.extend(vec!["vlan".into(), vlan.to_string()]);
+            if let Some(qos) = self.qos {
+                args.extend(vec!["qos".into(), qos.to_string()]);
+            }
+        }
+        if let Some(max_tx_rate) = self.max_tx_rate {
+            args.extend(vec!["max_tx_rate".into(), max_tx_rate.to_string()]);
+        }
+        if let Some(min_tx_rate) = self.min_tx_rate {
+            args.extend(vec!["min_tx_rate".into(), min_tx_rate.to_string()]);
+        }
+        if let Some(spoof_checking) = self.spoof_checking {
+            args.extend(vec!["spoofchk".into(), on_off(spoof_checking).into()]);
+        }
+        if let Some(trust) = self.trust {
+            args.extend(vec!["trust".into(), on_off(trust).into()]);
+        }
+        if let Some(state) = &self.state {
+            args.extend(vec!["state".into(), state.clone()]);
+        }
+        let mut seq = serializer.serialize_seq(Some(args.len()))?;
+        for arg in &args {
+            seq.serialize_element(arg)?;
+        }
+        seq.end()
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct LinkSetConfiguration {
     /// The device or group to operate on.
@@ -294,6 +823,8 @@ pub struct LinkSetConfiguration {
     pub broadcast: Option<String>,
     /// Maximum transmission unit for the device.
     pub mtu: Option<u32>,
+    /// Symbolic name of the device (an empty alias removes it).
+    pub alias: Option<String>,
     /// Move the device to the supplied network namespace or pid.
     #[serde(rename = "netns")]
     pub namespace: Option<String>,
@@ -307,13 +838,187 @@ pub struct LinkSetConfiguration {
     pub vrf_master: Option<String>,
     /// IPv6 address generation mode.
     #[serde(rename = "addrgenmode")]
-    pub address_generation_mode: Option<String>,
+    pub address_generation_mode: Option<AddressGenerationMode>,
     /// Set (or unset) a BPF program to run on every packet at driver level.
     pub express_data_path: Option<ExpressDataPathConfiguration>,
-    /// Type of the device.
+    /// Configure a virtual function of the (SR-IOV capable) device.
+    pub virtual_function: Option<VfConfiguration>,
+    /// Move the device into this group (only when addressing the device by name).
+    pub group: Option<u32>,
+    /// Type of the device, along with any type specific arguments to change
+    /// (eg. `LinkKind::Bridge`). Arguments left unset keep their current values.
     #[serde(rename = "type")]
-    pub link_type: Option<String>,
-    // Any type specific arguments: currently not supported.
+    pub link_type: Option<LinkKind>,
+}
+
+/// The largest IP packet (the IPv4 total length, and IPv6 payload length without jumbograms).
+#[cfg(feature = "tracing")]
+const MAXIMUM_IP_PACKET_SIZE: u32 = 65535;
+
+impl LinkSetConfiguration {
+    /// Reject attribute values which ip(8) (or the kernel) would refuse for any device type.
+    fn validate(&self) -> Result<(), Error> {
+        // Both are the master of the device.
+        ensure!(
+            self.master.is_none() || self.vrf_master.is_none(),
+            ConflictingConfigurationError {
+                field: "master",
+                conflicting_field: "vrf_master"
+            }
+        );
+        if let Some(mtu) = self.mtu {
+            ensure!(
+                mtu != 0,
+                InvalidArgumentError {
+                    field: "mtu",
+                    value: mtu.to_string(),
+                }
+            );
+            // The largest MTU depends on the device (and its driver), the kernel checks it.
+            #[cfg(feature = "tracing")]
+            {
+                if mtu > MAXIMUM_IP_PACKET_SIZE {
+                    tracing::warn!(mtu, "MTU larger than any IP packet");
+                }
+            }
+        }
+        if let Some(transmit_queue_length) = self.transmit_queue_length {
+            // Parsed as a signed integer by ip(8).
+            ensure!(
+                transmit_queue_length <= i32::MAX as u32,
+                InvalidArgumentError {
+                    field: "transmit_queue_length",
+                    value: transmit_queue_length.to_string(),
+                }
+            );
+        }
+        Ok(())
+    }
+
+    /// The configuration which restores the attributes changed by this configuration to
+    /// their values in the given (prior) link state.
+    fn rollback(&self, link: &Link) -> Self {
+        let flags = link.link_flags();
+        let device =
+            LinkDeviceOrGroup::Device(self.new_name.clone().unwrap_or_else(|| link.name.clone()));
+        let master = if self.master.is_some() || self.vrf_master.is_some() {
+            Some(match &link.master {
+                Some(master) => MasterSetConfiguration::Enslaved(master.clone()),
+                None => MasterSetConfiguration::Release,
+            })
+        } else {
+            None
+        };
+        Self {
+            device,
+            state: self.state.as_ref().map(|_| {
+                if flags.is_up() {
+                    LinkStatus::Up
+                } else {
+                    LinkStatus::Down
+                }
+            }),
+            arp: self.arp.map(|_| !flags.is_no_arp()),
+            multicast: self.multicast.map(|_| flags.is_multicast()),
+            all_multicast: self.all_multicast.map(|_| flags.is_all_multicast()),
+            promiscuous: self.promiscuous.map(|_| flags.is_promiscuous()),
+            trailers: self
+                .trailers
+                .map(|_| !flags.contains(LinkFlags::NO_TRAILERS)),
+            transmit_queue_length: self.transmit_queue_length.and(link.transmit_queue_length),
+            new_name: self.new_name.as_ref().map(|_| link.name.clone()),
+            address: self.address.as_ref().and(link.address.clone()),
+            broadcast: self.broadcast.as_ref().and(link.broadcast.clone()),
+            mtu: self.mtu.map(|_| link.mtu),
+            alias: self
+                .alias
+                .as_ref()
+                .map(|_| link.alias.clone().unwrap_or_default()),
+            master,
+            // Only the default group, or groups without a name, can be restored.
+            group: self
+                .group
+                .and(link.group.as_ref().and_then(|group| match group.as_str() {
+                    "default" => Some(0),
+                    group => group.parse().ok(),
+                })),
+            ..Default::default()
+        }
+    }
+}
+
+/// The attributes a device should have, any left unset are not changed.
+#[derive(Debug, Clone, Default)]
+pub struct LinkDesiredState {
+    /// State of the device.
+    pub state: Option<LinkStatus>,
+    /// Use of the Address Resolution Protocol.
+    pub arp: Option<bool>,
+    /// Support for multicast packets.
+    pub multicast: Option<bool>,
+    /// Reception of all hardware multicast packets.
+    pub all_multicast: Option<bool>,
+    /// Promiscuous listening mode.
+    pub promiscuous: Option<bool>,
+    /// Transmit queue length of the device.
+    pub transmit_queue_length: Option<u32>,
+    /// Station address of the device.
+    pub address: Option<String>,
+    /// Maximum transmission unit for the device.
+    pub mtu: Option<u32>,
+    /// Symbolic name of the device (an empty alias means none).
+    pub alias: Option<String>,
+}
+
+impl LinkDesiredState {
+    /// The configuration which changes only the attributes of the link which differ from
+    /// the desired state, none if it already has it.
+    fn changes(&self, link: &Link) -> Option<LinkSetConfiguration> {
+        let flags = link.link_flags();
+        let configuration = LinkSetConfiguration {
+            device: LinkDeviceOrGroup::Device(link.name.clone()),
+            state: self.state.clone().filter(|state| match state {
+                LinkStatus::Up => !flags.is_up(),
+                LinkStatus::Down => flags.is_up(),
+            }),
+            arp: self.arp.filter(|&arp| arp == flags.is_no_arp()),
+            multicast: self
+                .multicast
+                .filter(|&multicast| multicast != flags.is_multicast()),
+            all_multicast: self
+                .all_multicast
+                .filter(|&all_multicast| all_multicast != flags.is_all_multicast()),
+            promiscuous: self
+                .promiscuous
+                .filter(|&promiscuous| promiscuous != flags.is_promiscuous()),
+            transmit_queue_length: self
+                .transmit_queue_length
+                .filter(|&length| Some(length) != link.transmit_queue_length),
+            address: self.address.clone().filter(|address| {
+                !matches!(&link.address, Some(current) if current.eq_ignore_ascii_case(address))
+            }),
+            mtu: self.mtu.filter(|&mtu| mtu != link.mtu),
+            alias: self
+                .alias
+                .clone()
+                .filter(|alias| alias != link.alias.as_deref().unwrap_or_default()),
+            ..Default::default()
+        };
+        let changed = configuration.state.is_some()
+            || configuration.arp.is_some()
+            || configuration.multicast.is_some()
+            || configuration.all_multicast.is_some()
+            || configuration.promiscuous.is_some()
+            || configuration.transmit_queue_length.is_some()
+            || configuration.address.is_some()
+            || configuration.mtu.is_some()
+            || configuration.alias.is_some();
+        if changed {
+            Some(configuration)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Serialize)]
@@ -330,6 +1035,15 @@ pub struct LinkShowConfiguration {
     /// The type of devices to show.
     #[serde(rename = "type")]
     pub link_type: Option<String>,
+    /// Output more detailed information (eg. link type specific information).
+    #[serde(skip)]
+    pub details: bool,
+    /// Also report the traffic counters of each device.
+    #[serde(skip)]
+    pub statistics: bool,
+    /// Also report the detailed error counters of each device (`-s -s`), implies `statistics`.
+    #[serde(skip)]
+    pub detailed_statistics: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -345,39 +1059,307 @@ pub struct ExpressDataPath {
     pub program: Option<ExpressDataPathProgram>,
 }
 
-/// The returned link structure.
+/// The returned link type information (detailed output only).
 #[derive(Debug, Clone, Deserialize)]
-pub struct Link {
-    #[serde(rename = "ifindex")]
-    pub interface_index: u32,
-    #[serde(rename = "ifname")]
-    pub name: String,
-    #[serde(rename = "flags")]
-    pub flags: Vec<String>,
-    pub mtu: u32,
-    #[serde(rename = "qdisc")]
-    pub queueing_discipline: String,
-    #[serde(rename = "operstate")]
-    pub state: String,
-    #[serde(rename = "linkmode")]
-    pub link_mode: Option<String>,
-    #[serde(rename = "group")]
-    pub group: Option<String>,
-    #[serde(rename = "txqlen")]
-    pub transmit_queue_length: Option<u32>,
-    pub link_type: Option<String>,
-    pub address: Option<String>,
-    pub broadcast: Option<String>,
-    #[serde(rename = "xdp")]
-    pub express_data_path: Option<ExpressDataPath>,
+pub struct LinkInfo {
+    /// Type of the link.
+    #[serde(rename = "info_kind")]
+    pub kind: Option<String>,
+    /// Link type specific attributes.
+    #[serde(rename = "info_data")]
+    pub data: Option<serde_json::Value>,
 }
 
-#[derive(Clone)]
-pub struct IpLinkCommand<'l> {
-    ip_command: &'l IpCommand,
+/// The returned traffic counters of one direction of a link (statistics only).
+///
+/// The error details are each reported for one direction only, the less common ones (eg.
+/// `crc_errors`) with detailed statistics only.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LinkCounters {
+    pub bytes: u64,
+    pub packets: u64,
+    pub errors: u64,
+    pub dropped: u64,
+    /// Received packets dropped for lack of buffer space.
+    #[serde(rename = "over_errors")]
+    pub overrun_errors: Option<u64>,
+    /// Received multicast packets.
+    pub multicast: Option<u64>,
+    /// Received packets of an invalid length.
+    pub length_errors: Option<u64>,
+    /// Received packets failing their checksum.
+    pub crc_errors: Option<u64>,
+    /// Received packets misaligned.
+    pub frame_errors: Option<u64>,
+    /// Packets lost to a FIFO overrun (or underrun, transmitting).
+    pub fifo_errors: Option<u64>,
+    /// Received packets missed by the device.
+    pub missed_errors: Option<u64>,
+    /// Transmissions which lost the carrier.
+    pub carrier_errors: Option<u64>,
+    /// Transmissions which collided (half duplex only).
+    pub collisions: Option<u64>,
+    /// Transmissions aborted by the device.
+    pub aborted_errors: Option<u64>,
+    /// Transmissions which collided after the window (half duplex only).
+    pub window_errors: Option<u64>,
+    /// Transmissions missing the heartbeat of the transceiver.
+    pub heartbeat_errors: Option<u64>,
+    /// Changes of the carrier of the device, up or down.
+    pub carrier_changes: Option<u64>,
 }
 
-impl<'l> IpLinkCommand<'l> {
+/// The returned traffic counters of a link (statistics only).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LinkStats {
+    #[serde(rename = "rx")]
+    pub receive: LinkCounters,
+    #[serde(rename = "tx")]
+    pub transmit: LinkCounters,
+}
+
+/// The traffic rates of a link between two statistics snapshots.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkStatsDelta {
+    pub receive_bytes_per_second: f64,
+    pub receive_packets_per_second: f64,
+    pub transmit_bytes_per_second: f64,
+    pub transmit_packets_per_second: f64,
+}
+
+impl LinkStatsDelta {
+    /// The rates between the earlier and later snapshot, taken the interval apart.
+    ///
+    /// Counters which went backwards (eg. the device was recreated) count as no traffic, as
+    /// does an empty interval.
+    pub fn new(earlier: &LinkStats, later: &LinkStats, interval: Duration) -> Self {
+        let seconds = interval.as_secs_f64();
+        let rate = |earlier: u64, later: u64| {
+            if seconds > 0.0 {
+                later.saturating_sub(earlier) as f64 / seconds
+            } else {
+                0.0
+            }
+        };
+        Self {
+            receive_bytes_per_second: rate(earlier.receive.bytes, later.receive.bytes),
+            receive_packets_per_second: rate(earlier.receive.packets, later.receive.packets),
+            transmit_bytes_per_second: rate(earlier.transmit.bytes, later.transmit.bytes),
+            transmit_packets_per_second: rate(earlier.transmit.packets, later.transmit.packets),
+        }
+    }
+}
+
+/// Interface flags of a device (see netdevice(7)), as reported by ip(8) (eg. "UP", "LOWER_UP").
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LinkFlags(u32);
+
+impl LinkFlags {
+    pub const UP: Self = Self(0x1);
+    pub const BROADCAST: Self = Self(0x2);
+    pub const DEBUG: Self = Self(0x4);
+    pub const LOOPBACK: Self = Self(0x8);
+    pub const POINT_TO_POINT: Self = Self(0x10);
+    pub const NO_TRAILERS: Self = Self(0x20);
+    pub const NO_ARP: Self = Self(0x80);
+    pub const PROMISCUOUS: Self = Self(0x100);
+    pub const ALL_MULTICAST: Self = Self(0x200);
+    pub const MASTER: Self = Self(0x400);
+    pub const SLAVE: Self = Self(0x800);
+    pub const MULTICAST: Self = Self(0x1000);
+    pub const PORT_SELECT: Self = Self(0x2000);
+    pub const AUTO_MEDIA: Self = Self(0x4000);
+    pub const DYNAMIC: Self = Self(0x8000);
+    pub const LOWER_UP: Self = Self(0x10000);
+    pub const DORMANT: Self = Self(0x20000);
+    pub const ECHO: Self = Self(0x40000);
+    /// An up device without carrier, ip(8) reports this rather than the kernel's running flag.
+    pub const NO_CARRIER: Self = Self(0x100000);
+    /// The master device of an enslaved device is down.
+    pub const MASTER_DOWN: Self = Self(0x200000);
+
+    const NAMES: [(&'static str, Self); 20] = [
+        ("UP", Self::UP),
+        ("BROADCAST", Self::BROADCAST),
+        ("DEBUG", Self::DEBUG),
+        ("LOOPBACK", Self::LOOPBACK),
+        ("POINTOPOINT", Self::POINT_TO_POINT),
+        ("NOTRAILERS", Self::NO_TRAILERS),
+        ("NOARP", Self::NO_ARP),
+        ("PROMISC", Self::PROMISCUOUS),
+        ("ALLMULTI", Self::ALL_MULTICAST),
+        ("MASTER", Self::MASTER),
+        ("SLAVE", Self::SLAVE),
+        ("MULTICAST", Self::MULTICAST),
+        ("PORTSEL", Self::PORT_SELECT),
+        ("AUTOMEDIA", Self::AUTO_MEDIA),
+        ("DYNAMIC", Self::DYNAMIC),
+        ("LOWER_UP", Self::LOWER_UP),
+        ("DORMANT", Self::DORMANT),
+        ("ECHO", Self::ECHO),
+        ("NO-CARRIER", Self::NO_CARRIER),
+        ("M-DOWN", Self::MASTER_DOWN),
+    ];
+
+    /// The flags named as reported by ip(8), unknown names are ignored.
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> Self {
+        names
+            .iter()
+            .filter_map(|name| {
+                Self::NAMES
+                    .iter()
+                    .find(|(known, _)| *known == name.as_ref())
+                    .map(|(_, flag)| *flag)
+            })
+            .fold(Self::default(), |flags, flag| flags | flag)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Are all of the given flags set?
+    pub fn contains(self, flags: Self) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// Is the device administratively up?
+    pub fn is_up(self) -> bool {
+        self.contains(Self::UP)
+    }
+
+    /// Is the device up with carrier (the kernel's running flag)?
+    pub fn is_running(self) -> bool {
+        self.is_up() && !self.contains(Self::NO_CARRIER)
+    }
+
+    /// Does the device have carrier?
+    pub fn is_lower_up(self) -> bool {
+        self.contains(Self::LOWER_UP)
+    }
+
+    pub fn is_broadcast(self) -> bool {
+        self.contains(Self::BROADCAST)
+    }
+
+    pub fn is_loopback(self) -> bool {
+        self.contains(Self::LOOPBACK)
+    }
+
+    pub fn is_point_to_point(self) -> bool {
+        self.contains(Self::POINT_TO_POINT)
+    }
+
+    /// Is ARP (or neighbor discovery) disabled?
+    pub fn is_no_arp(self) -> bool {
+        self.contains(Self::NO_ARP)
+    }
+
+    pub fn is_promiscuous(self) -> bool {
+        self.contains(Self::PROMISCUOUS)
+    }
+
+    pub fn is_all_multicast(self) -> bool {
+        self.contains(Self::ALL_MULTICAST)
+    }
+
+    pub fn is_multicast(self) -> bool {
+        self.contains(Self::MULTICAST)
+    }
+
+    pub fn is_master(self) -> bool {
+        self.contains(Self::MASTER)
+    }
+
+    pub fn is_slave(self) -> bool {
+        self.contains(Self::SLAVE)
+    }
+
+    pub fn is_dormant(self) -> bool {
+        self.contains(Self::DORMANT)
+    }
+}
+
+impl ops::BitOr for LinkFlags {
+    type Output = Self;
+
+    fn bitor(self, flags: Self) -> Self {
+        Self(self.0 | flags.0)
+    }
+}
+
+/// The returned link structure.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Link {
+    #[serde(rename = "ifindex")]
+    pub interface_index: u32,
+    #[serde(rename = "ifname")]
+    pub name: String,
+    #[serde(rename = "flags")]
+    pub flags: Vec<String>,
+    pub mtu: u32,
+    #[serde(rename = "qdisc")]
+    pub queueing_discipline: String,
+    #[serde(rename = "operstate")]
+    pub state: String,
+    #[serde(rename = "linkmode")]
+    pub link_mode: Option<String>,
+    #[serde(rename = "group")]
+    pub group: Option<String>,
+    #[serde(rename = "txqlen")]
+    pub transmit_queue_length: Option<u32>,
+    pub link_type: Option<String>,
+    pub address: Option<String>,
+    pub broadcast: Option<String>,
+    #[serde(rename = "xdp")]
+    pub express_data_path: Option<ExpressDataPath>,
+    #[serde(rename = "linkinfo")]
+    pub link_info: Option<LinkInfo>,
+    /// Name of the master device (eg. the bridge or bond this device is enslaved to).
+    pub master: Option<String>,
+    /// Name of the parent device (eg. of a vlan / macvlan), or the peer of a veth device.
+    pub link: Option<String>,
+    /// Index of the parent device, reported instead of `link` when it is in another namespace.
+    pub link_index: Option<u32>,
+    /// Namespace id of the parent device, when it is in another namespace.
+    #[serde(rename = "link_netnsid")]
+    pub link_network_namespace_id: Option<i32>,
+    /// Traffic counters (statistics only).
+    #[serde(rename = "stats64")]
+    pub statistics: Option<LinkStats>,
+    /// Maximum size of a Generic Segment Offload packet the device accepts (detailed only).
+    #[serde(rename = "gso_max_size")]
+    pub gso_maximum_size: Option<u32>,
+    /// Maximum number of Generic Segment Offload segments the device accepts (detailed only).
+    #[serde(rename = "gso_max_segs")]
+    pub gso_maximum_segments: Option<u32>,
+    /// Maximum size of a Generic Receive Offload packet the device builds (detailed only).
+    #[serde(rename = "gro_max_size")]
+    pub gro_maximum_size: Option<u32>,
+    /// Number of holders of promiscuous mode, eg. a bridge or packet capture as well as the
+    /// `promisc` flag itself (detailed only).
+    pub promiscuity: Option<u32>,
+    /// Number of holders of all multicast mode (detailed only).
+    #[serde(rename = "allmulti")]
+    pub all_multicast: Option<u32>,
+    /// Symbolic name of the device.
+    #[serde(rename = "ifalias")]
+    pub alias: Option<String>,
+}
+
+impl Link {
+    /// The typed flags of the device (see `flags` for the names as reported).
+    pub fn link_flags(&self) -> LinkFlags {
+        LinkFlags::from_names(&self.flags)
+    }
+}
+
+#[derive(Clone)]
+pub struct IpLinkCommand<'l> {
+    ip_command: &'l IpCommand,
+}
+
+impl<'l> IpLinkCommand<'l> {
     pub fn new(ip_command: &'l IpCommand) -> Self {
         Self { ip_command }
     }
@@ -390,38 +1372,151 @@ impl<'l> IpLinkCommand<'l> {
                 .into_args(&configuration)
                 .context(CommandOptionsSerializationError {})?,
         );
-        self.ip_command
-            .command(&args, false, None)
-            .await
-            .map(|_| ())
+        self.ip_command.command_without_output(&args, None).await
     }
 
     /// Delete virtual link.
     pub async fn delete(&self, configuration: LinkDeleteConfiguration) -> Result<(), Error> {
+        // Every device belongs to the default group unless moved, deleting it is never intended.
+        if let LinkDeviceOrGroup::DeviceGroup(0) = configuration.device {
+            return Err(Error::InvalidArgumentError {
+                field: "device".into(),
+                value: "group 0".into(),
+            });
+        }
         let mut args: Vec<String> = vec!["link".into(), "delete".into()];
         args.append(
             &mut Serializer::new(BooleanType::OnOff)
                 .into_args(&configuration)
                 .context(CommandOptionsSerializationError {})?,
         );
-        self.ip_command
-            .command(&args, false, None)
-            .await
-            .map(|_| ())
+        self.ip_command.command_without_output(&args, None).await
     }
 
     /// Change device attributes.
+    ///
+    /// Rejected before running ip(8): an MTU of zero, a transmit queue length which does not
+    /// fit a signed 32-bit integer, or both a master and a vrf master.
     pub async fn set(&self, configuration: LinkSetConfiguration) -> Result<(), Error> {
+        configuration.validate()?;
         let mut args: Vec<String> = vec!["link".into(), "set".into()];
         args.append(
             &mut Serializer::new(BooleanType::OnOff)
                 .into_args(&configuration)
                 .context(CommandOptionsSerializationError {})?,
         );
+        self.ip_command.command_without_output(&args, None).await
+    }
+
+    /// Set the secret (formatted as an IPv6 address) from which the device derives its
+    /// stable privacy addresses, a prerequisite of the `stable_secret` address generation mode.
+    ///
+    /// Fails with `InvalidArgumentError` when the device is not a valid device name.
+    pub async fn set_stable_secret(&self, device: &str, secret: &str) -> Result<(), Error> {
+        IpCommand::ensure_device_name("device", device)?;
         self.ip_command
-            .command(&args, false, None)
+            .write_sysctl(&["net", "ipv6", "conf", device, "stable_secret"], secret)
             .await
-            .map(|_| ())
+    }
+
+    /// Change the attributes of several devices, if any change fails the devices which were
+    /// already changed are reverted to their prior state (as reported by `show`).
+    ///
+    /// Devices must be addressed by name, and only attributes which can be read back are
+    /// reverted (ie. not a move to another network namespace or an XDP program).
+    pub async fn set_all_with_rollback(
+        &self,
+        configurations: Vec<LinkSetConfiguration>,
+    ) -> Result<(), Error> {
+        for configuration in &configurations {
+            configuration.validate()?;
+        }
+        let mut rollbacks: Vec<LinkSetConfiguration> = vec![];
+        for configuration in configurations {
+            match self.set_with_rollback(configuration).await {
+                Ok(rollback) => rollbacks.push(rollback),
+                Err(error) => {
+                    for rollback in rollbacks.into_iter().rev() {
+                        if let Err(rollback_error) = self.set(rollback).await {
+                            return Err(Error::RollbackFailedError {
+                                error: Box::new(error),
+                                rollback_error: Box::new(rollback_error),
+                            });
+                        }
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Change the attributes of the named device to the desired state, returns whether any
+    /// changed.
+    ///
+    /// Only the attributes which differ from the current state (as reported by `show`) are
+    /// changed, by a single command. If it fails those which were applied are reverted.
+    pub async fn reconfigure(
+        &self,
+        device: &str,
+        desired: LinkDesiredState,
+    ) -> Result<bool, Error> {
+        let link = self
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(device.into()),
+                ..Default::default()
+            }))
+            .await?
+            .pop()
+            .context(NotFoundError {})?;
+        let configuration = match desired.changes(&link) {
+            Some(configuration) => configuration,
+            None => return Ok(false),
+        };
+        let rollback = configuration.rollback(&link);
+        if let Err(error) = self.set(configuration).await {
+            if let Err(rollback_error) = self.set(rollback).await {
+                return Err(Error::RollbackFailedError {
+                    error: Box::new(error),
+                    rollback_error: Box::new(rollback_error),
+                });
+            }
+            return Err(error);
+        }
+        Ok(true)
+    }
+
+    /// Change device attributes, returning the configuration which reverts the change.
+    async fn set_with_rollback(
+        &self,
+        configuration: LinkSetConfiguration,
+    ) -> Result<LinkSetConfiguration, Error> {
+        let device = match &configuration.device {
+            LinkDeviceOrGroup::Device(device) => device.clone(),
+            LinkDeviceOrGroup::DeviceGroup(group) => {
+                return Err(Error::InvalidArgumentError {
+                    field: "device".into(),
+                    value: format!("group {}", group),
+                })
+            }
+            LinkDeviceOrGroup::None => {
+                return Err(Error::InvalidArgumentError {
+                    field: "device".into(),
+                    value: "".into(),
+                })
+            }
+        };
+        let link = self
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(device),
+                ..Default::default()
+            }))
+            .await?
+            .pop()
+            .context(NotFoundError {})?;
+        let rollback = configuration.rollback(&link);
+        self.set(configuration).await?;
+        Ok(rollback)
     }
 
     /// Display device attributes.
@@ -429,7 +1524,24 @@ impl<'l> IpLinkCommand<'l> {
         &self,
         configuration: Option<LinkShowConfiguration>,
     ) -> Result<Vec<Link>, Error> {
-        let mut args: Vec<String> = vec!["link".into(), "show".into()];
+        let mut args: Vec<String> = vec![];
+        if let Some(LinkShowConfiguration { details: true, .. }) = configuration {
+            args.push("-details".into());
+        }
+        if let Some(LinkShowConfiguration {
+            statistics,
+            detailed_statistics,
+            ..
+        }) = configuration
+        {
+            if statistics || detailed_statistics {
+                args.push("-statistics".into());
+            }
+            if detailed_statistics {
+                args.push("-statistics".into());
+            }
+        }
+        args.append(&mut vec!["link".into(), "show".into()]);
         if let Some(configuration) = configuration {
             args.append(
                 &mut Serializer::new(BooleanType::OnOff)
@@ -437,8 +1549,217 @@ impl<'l> IpLinkCommand<'l> {
                     .context(CommandOptionsSerializationError {})?,
             );
         }
-        let output = self.ip_command.command(&args, false, None).await?;
-        Ok(serde_json::from_str(&output).context(JsonDeserializationError {})?)
+        if self.ip_command.is_oneline() {
+            let output = self.ip_command.command(&args, false, false, None).await?;
+            return Self::parse_oneline_output(&output);
+        }
+        let output = self.ip_command.command(&args, true, false, None).await?;
+        let links: Vec<serde_json::Value> =
+            serde_json::from_str(&output).context(JsonDeserializationError {})?;
+        // Devices filtered out (eg. by group) are reported as empty objects.
+        links
+            .into_iter()
+            .filter(|link| !matches!(link.as_object(), Some(link) if link.is_empty()))
+            .map(|link| serde_json::from_value(link).context(JsonDeserializationError {}))
+            .collect()
+    }
+
+    /// Parse `-oneline` output, only the basic attributes (up to the address, alias and peer
+    /// namespace id) of each device are reported.
+    fn parse_oneline_output(output: &str) -> Result<Vec<Link>, Error> {
+        output
+            .lines()
+            .filter(|record| !record.trim().is_empty())
+            .map(|record| {
+                serde_json::from_value(Self::parse_oneline_record(record)?)
+                    .context(JsonDeserializationError {})
+            })
+            .collect()
+    }
+
+    /// A record of `-oneline` output as json output would have reported it, eg.
+    /// "3: veth0@veth1: <BROADCAST,MULTICAST> mtu 1500 qdisc noop state DOWN ...\    link/ether ...".
+    pub(crate) fn parse_oneline_record(record: &str) -> Result<serde_json::Value, Error> {
+        let unexpected = || Error::UnexpectedOutputError {
+            output: record.into(),
+        };
+        let segments = IpCommand::oneline_segments(record);
+        let mut tokens = segments.first().ok_or_else(unexpected)?.split_whitespace();
+        let mut fields = serde_json::Map::new();
+        let index: u32 = tokens
+            .next()
+            .and_then(|index| index.trim_end_matches(':').parse().ok())
+            .ok_or_else(unexpected)?;
+        fields.insert("ifindex".into(), index.into());
+        let mut name = tokens
+            .next()
+            .ok_or_else(unexpected)?
+            .trim_end_matches(':')
+            .splitn(2, '@');
+        fields.insert("ifname".into(), name.next().unwrap().into());
+        // The parent (or peer) is reported by index when it is in another namespace.
+        match name.next() {
+            None | Some("NONE") => {}
+            Some(parent) => match parent
+                .strip_prefix("if")
+                .and_then(|index| index.parse::<u32>().ok())
+            {
+                Some(parent_index) => {
+                    fields.insert("link_index".into(), parent_index.into());
+                }
+                None => {
+                    fields.insert("link".into(), parent.into());
+                }
+            },
+        }
+        let flags = tokens
+            .next()
+            .and_then(|flags| flags.strip_prefix('<'))
+            .and_then(|flags| flags.strip_suffix('>'))
+            .ok_or_else(unexpected)?;
+        let flags: Vec<&str> = flags.split(',').filter(|flag| !flag.is_empty()).collect();
+        fields.insert("flags".into(), flags.into());
+        IpCommand::insert_oneline_attributes(
+            &mut fields,
+            tokens,
+            &[
+                ("mtu", "mtu", true),
+                ("qdisc", "qdisc", false),
+                ("master", "master", false),
+                ("state", "operstate", false),
+                ("mode", "linkmode", false),
+                ("group", "group", false),
+                ("qlen", "txqlen", true),
+            ],
+        );
+        for segment in &segments[1..] {
+            if let Some(alias) = segment.strip_prefix("alias ") {
+                fields.insert("ifalias".into(), alias.into());
+                continue;
+            }
+            let mut tokens = segment.split_whitespace().peekable();
+            if let Some(link_type) = tokens.peek().and_then(|token| token.strip_prefix("link/")) {
+                fields.insert("link_type".into(), link_type.into());
+                tokens.next();
+                if let Some(address) =
+                    tokens.next_if(|token| !matches!(*token, "brd" | "peer" | "link-netnsid"))
+                {
+                    fields.insert("address".into(), address.into());
+                }
+                IpCommand::insert_oneline_attributes(
+                    &mut fields,
+                    tokens,
+                    &[
+                        ("brd", "broadcast", false),
+                        ("link-netnsid", "link_netnsid", true),
+                    ],
+                );
+            }
+        }
+        Ok(fields.into())
+    }
+
+    /// Does the named device exist?
+    pub async fn exists(&self, name: &str) -> Result<bool, Error> {
+        let result = self
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(name.into()),
+                ..Default::default()
+            }))
+            .await;
+        match result {
+            Ok(links) => Ok(!links.is_empty()),
+            Err(Error::CommandFailedError { stderr, .. }) if Self::device_missing(&stderr) => {
+                Ok(false)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Delete the named device unless it doesn't exist, returns whether it was deleted.
+    pub async fn ensure_absent(&self, name: &str, link_type: &str) -> Result<bool, Error> {
+        let result = self
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(name.into()),
+                link_type: link_type.into(),
+            })
+            .await;
+        match result {
+            Ok(()) => Ok(true),
+            Err(Error::CommandFailedError { stderr, .. }) if Self::device_missing(&stderr) => {
+                Ok(false)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Wait until the operational state of the named device is up, resolves immediately if
+    /// it already is. Changes are followed via the monitor rather than by polling.
+    ///
+    /// Devices without carrier detection (eg. dummy) remain in the unknown state, never up.
+    #[cfg(feature = "streaming")]
+    pub async fn wait_up(&self, device: &str, wait_timeout: Duration) -> Result<(), Error> {
+        // Started before the initial check, so that no change in between is missed.
+        let mut events = self
+            .ip_command
+            .monitor()
+            .monitor(&[MonitorObject::Link])
+            .await?;
+        if self.is_up(device).await? {
+            return Ok(());
+        }
+        let monitored = async {
+            while let Some(event) = events.next().await {
+                if Self::reports_up(&event.context(CommandError {})?, device) {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        };
+        let result = tokio::time::timeout(wait_timeout, monitored).await;
+        match result {
+            Ok(Ok(true)) => return Ok(()),
+            Ok(Err(error)) => return Err(error),
+            _ => {}
+        }
+        // The monitor may have subscribed after the state changed.
+        if self.is_up(device).await? {
+            return Ok(());
+        }
+        match result {
+            Err(elapsed) => Err(Error::WaitTimeoutError { source: elapsed }),
+            _ => Err(Error::UnexpectedOutputError {
+                output: "monitor exited".into(),
+            }),
+        }
+    }
+
+    #[cfg(feature = "streaming")]
+    async fn is_up(&self, device: &str) -> Result<bool, Error> {
+        let links = self
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(device.into()),
+                ..Default::default()
+            }))
+            .await?;
+        Ok(links.iter().any(|link| link.state == "UP"))
+    }
+
+    /// Does the event report the device as up? eg. "5: eth0@if4: <...> mtu 1500 ... state UP ...".
+    #[cfg(feature = "streaming")]
+    fn reports_up(event: &MonitorEvent, device: &str) -> bool {
+        let tokens: Vec<&str> = event.message.split_whitespace().collect();
+        let name = tokens
+            .get(1)
+            .map(|name| name.trim_end_matches(':').split('@').next().unwrap());
+        !event.deleted
+            && name == Some(device)
+            && tokens.windows(2).any(|pair| pair == ["state", "UP"])
+    }
+
+    /// Did the command fail because the named device doesn't exist?
+    pub(crate) fn device_missing(stderr: &str) -> bool {
+        stderr.contains("Cannot find device") || stderr.contains("does not exist")
     }
 
     /// Display extended statistics.
@@ -459,6 +1780,7 @@ impl<'l> IpLinkCommand<'l> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::address::{AddressShowConfiguration, Scope};
 
     #[tokio::test]
     async fn test_add() {
@@ -477,6 +1799,7 @@ mod tests {
             number_receive_queues: Some(1u32),
             gso_maximum_size: Some(65536u32),
             gso_maximum_segments: Some(10u32),
+            group: None,
         };
 
         let client = IpCommand::new().unwrap();
@@ -584,12 +1907,15 @@ mod tests {
             address: Some("02:00:00:00:01:01".into()),
             broadcast: Some("FF:FF:FF:FF:FF:FF".into()),
             mtu: Some(1400),
+            alias: None,
             namespace: Some(test_namespace.into()),
             link_network_namespace_id: Some(101u32),
             express_data_path: None,
+            virtual_function: None,
             master: None,
             vrf_master: None,
-            address_generation_mode: Some("eui64".into()),
+            address_generation_mode: Some(AddressGenerationMode::Eui64),
+            group: None,
         };
 
         client.netns().add(test_namespace).await.unwrap();
@@ -620,17 +1946,1834 @@ mod tests {
 
         client.netns().delete(test_namespace).await.unwrap();
 
-        assert!(link[0].flags.contains(&"UP".to_string()));
-        assert!(link[0].flags.contains(&"NOARP".to_string()));
-        assert!(link[0].flags.contains(&"MULTICAST".to_string()));
-        assert!(link[0].flags.contains(&"ALLMULTI".to_string()));
-        assert!(link[0].flags.contains(&"NOTRAILERS".to_string()));
+        assert!(link[0].link_flags().is_up());
+        assert!(link[0].link_flags().is_no_arp());
+        assert!(link[0].link_flags().is_multicast());
+        assert!(link[0].link_flags().is_all_multicast());
+        assert!(link[0].link_flags().contains(LinkFlags::NO_TRAILERS));
         assert_eq!(link[0].transmit_queue_length, Some(1u32));
         assert_eq!(link[0].address, Some("02:00:00:00:01:01".into()));
         assert_eq!(link[0].broadcast, Some("ff:ff:ff:ff:ff:ff".into()));
         assert_eq!(link[0].mtu, 1400);
     }
 
+    #[tokio::test]
+    async fn test_add_gre() {
+        let link_name = "test_gre0";
+        let configuration = LinkAddConfiguration {
+            name: link_name.into(),
+            link_type: LinkKind::Gre(GreConfiguration {
+                local: Some("172.81.0.1".into()),
+                remote: Some("172.81.0.2".into()),
+                key: Some(42),
+                encapsulation: None,
+            }),
+            ..Default::default()
+        };
+
+        let client = IpCommand::new().unwrap();
+        let dry_run_client = client.dry_run();
+        dry_run_client
+            .link()
+            .add(configuration.clone())
+            .await
+            .unwrap();
+        assert!(dry_run_client.recorded_commands()[0]
+            .windows(2)
+            .any(|pair| pair == ["key", "42"]));
+
+        client.link().add(configuration).await.unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                details: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "gre".into(),
+            })
+            .await
+            .unwrap();
+
+        let link_info = link[0].link_info.as_ref().unwrap();
+        let data = link_info.data.as_ref().unwrap();
+        assert_eq!(link_info.kind, Some("gre".into()));
+        assert_eq!(data["local"], "172.81.0.1");
+        assert_eq!(data["remote"], "172.81.0.2");
+        // Keys are reported in dotted quad notation.
+        assert_eq!(data["ikey"], "0.0.0.42");
+        assert_eq!(data["okey"], "0.0.0.42");
+    }
+
+    #[test]
+    fn test_serialize_gre() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkAddConfiguration {
+                name: "gre1".into(),
+                link_type: LinkKind::Ip6Gre(GreConfiguration {
+                    local: Some("fd00::1".into()),
+                    remote: Some("fd00::2".into()),
+                    key: None,
+                    encapsulation: None,
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            args,
+            vec!["name", "gre1", "type", "ip6gre", "local", "fd00::1", "remote", "fd00::2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_gretap() {
+        let link_name = "test_gretap0";
+        let configuration = LinkAddConfiguration {
+            name: link_name.into(),
+            link_type: LinkKind::Gretap(GreConfiguration {
+                local: Some("127.0.0.1".into()),
+                remote: Some("127.0.0.2".into()),
+                key: Some(42),
+                encapsulation: None,
+            }),
+            ..Default::default()
+        };
+
+        let client = IpCommand::new().unwrap();
+        let dry_run_client = client.dry_run();
+        dry_run_client
+            .link()
+            .add(configuration.clone())
+            .await
+            .unwrap();
+        assert!(dry_run_client.recorded_commands()[0]
+            .windows(2)
+            .any(|pair| pair == ["key", "42"]));
+
+        client.link().add(configuration).await.unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                details: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "gretap".into(),
+            })
+            .await
+            .unwrap();
+
+        let link_info = link[0].link_info.as_ref().unwrap();
+        let data = link_info.data.as_ref().unwrap();
+        assert_eq!(link_info.kind, Some("gretap".into()));
+        // Carries ethernet frames, unlike a gre device.
+        assert_eq!(link[0].link_type, Some("ether".into()));
+        assert_eq!(data["local"], "127.0.0.1");
+        assert_eq!(data["remote"], "127.0.0.2");
+        // Keys are reported in dotted quad notation.
+        assert_eq!(data["ikey"], "0.0.0.42");
+        assert_eq!(data["okey"], "0.0.0.42");
+    }
+
+    #[test]
+    fn test_serialize_ip6gretap() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkAddConfiguration {
+                name: "gretap1".into(),
+                link_type: LinkKind::Ip6Gretap(GreConfiguration {
+                    local: Some("fd00::1".into()),
+                    remote: Some("fd00::2".into()),
+                    key: Some(7),
+                    encapsulation: None,
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "name",
+                "gretap1",
+                "type",
+                "ip6gretap",
+                "local",
+                "fd00::1",
+                "remote",
+                "fd00::2",
+                "key",
+                "7"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_bond() {
+        let link_name = "test_bond0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: LinkKind::Bond(BondConfiguration {
+                    mode: BondMode::ActiveBackup,
+                    mii_monitor_interval: Some(100),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                details: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "bond".into(),
+            })
+            .await
+            .unwrap();
+
+        let link_info = link[0].link_info.as_ref().unwrap();
+        let data = link_info.data.as_ref().unwrap();
+        assert_eq!(link_info.kind, Some("bond".into()));
+        assert_eq!(data["mode"], "active-backup");
+        assert_eq!(data["miimon"], 100);
+    }
+
+    #[tokio::test]
+    async fn test_add_bond_arp_monitor() {
+        let link_name = "test_bond1";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: LinkKind::Bond(BondConfiguration {
+                    mode: BondMode::ActiveBackup,
+                    arp_interval: Some(200),
+                    arp_ip_target: vec!["192.0.2.1".into(), "192.0.2.2".into()],
+                    arp_validate: Some("all".into()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                details: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "bond".into(),
+            })
+            .await
+            .unwrap();
+
+        let data = link[0].link_info.as_ref().unwrap().data.as_ref().unwrap();
+        assert_eq!(data["arp_interval"], 200);
+        assert_eq!(
+            data["arp_ip_target"],
+            serde_json::json!(["192.0.2.1", "192.0.2.2"])
+        );
+        assert_eq!(data["arp_validate"], "all");
+    }
+
+    #[test]
+    fn test_serialize_bond() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkKind::Bond(BondConfiguration {
+                mode: BondMode::Ieee8023ad,
+                mii_monitor_interval: Some(100),
+                lacp_rate: Some("fast".into()),
+                transmit_hash_policy: None,
+                arp_interval: None,
+                arp_ip_target: vec![],
+                arp_validate: None,
+            }))
+            .unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "bond",
+                "mode",
+                "802.3ad",
+                "miimon",
+                "100",
+                "lacp_rate",
+                "fast"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_bond_arp_monitor() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkKind::Bond(BondConfiguration {
+                mode: BondMode::ActiveBackup,
+                arp_interval: Some(200),
+                arp_ip_target: vec!["192.0.2.1".into(), "192.0.2.2".into()],
+                ..Default::default()
+            }))
+            .unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "bond",
+                "mode",
+                "active-backup",
+                "arp_interval",
+                "200",
+                "arp_ip_target",
+                "192.0.2.1,192.0.2.2"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_macvlan() {
+        let parent_link_name = "test_link4";
+        let link_name = "test_macvlan0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: parent_link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                device: Some(parent_link_name.into()),
+                link_type: LinkKind::Macvlan(MacvlanConfiguration {
+                    mode: MacvlanMode::Bridge,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                details: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        // Deleting the parent device also deletes the macvlan device.
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(parent_link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        let link_info = link[0].link_info.as_ref().unwrap();
+        assert_eq!(link_info.kind, Some("macvlan".into()));
+        assert_eq!(link_info.data.as_ref().unwrap()["mode"], "bridge");
+    }
+
+    #[tokio::test]
+    async fn test_show_parent_and_master() {
+        let parent_link_name = "test_link5";
+        let link_name = "test_macvlan1";
+        let bridge_name = "test_br1";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: parent_link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                device: Some(parent_link_name.into()),
+                link_type: LinkKind::Macvlan(MacvlanConfiguration::default()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: bridge_name.into(),
+                link_type: "bridge".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                master: Some(MasterSetConfiguration::Enslaved(bridge_name.into())),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        for link_name in &[parent_link_name, bridge_name] {
+            client
+                .link()
+                .delete(LinkDeleteConfiguration {
+                    device: LinkDeviceOrGroup::Device((*link_name).into()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(link[0].link, Some(parent_link_name.into()));
+        assert_eq!(link[0].master, Some(bridge_name.into()));
+    }
+
+    #[tokio::test]
+    async fn test_set_all_with_rollback() {
+        let link_names = ["test_link6", "test_link7"];
+        let client = IpCommand::new().unwrap();
+
+        for link_name in &link_names {
+            client
+                .link()
+                .add(LinkAddConfiguration {
+                    name: (*link_name).into(),
+                    link_type: "dummy".into(),
+                    mtu: Some(1500),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        let result = client
+            .link()
+            .set_all_with_rollback(vec![
+                LinkSetConfiguration {
+                    device: LinkDeviceOrGroup::Device(link_names[0].into()),
+                    state: Some(LinkStatus::Up),
+                    mtu: Some(1400),
+                    ..Default::default()
+                },
+                LinkSetConfiguration {
+                    device: LinkDeviceOrGroup::Device("test_missing0".into()),
+                    mtu: Some(1400),
+                    ..Default::default()
+                },
+                LinkSetConfiguration {
+                    device: LinkDeviceOrGroup::Device(link_names[1].into()),
+                    mtu: Some(1400),
+                    ..Default::default()
+                },
+            ])
+            .await;
+
+        let mut links = vec![];
+        for link_name in &link_names {
+            links.append(
+                &mut client
+                    .link()
+                    .show(Some(LinkShowConfiguration {
+                        device: LinkDeviceOrGroup::Device((*link_name).into()),
+                        ..Default::default()
+                    }))
+                    .await
+                    .unwrap(),
+            );
+            client
+                .link()
+                .delete(LinkDeleteConfiguration {
+                    device: LinkDeviceOrGroup::Device((*link_name).into()),
+                    link_type: "dummy".into(),
+                })
+                .await
+                .unwrap();
+        }
+
+        assert!(result.is_err());
+        assert_eq!(links[0].mtu, 1500);
+        assert!(!links[0].link_flags().is_up());
+        assert_eq!(links[1].mtu, 1500);
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure() {
+        let link_name = "test_link14";
+        let client = IpCommand::new().unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                mtu: Some(1500),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let desired = LinkDesiredState {
+            state: Some(LinkStatus::Up),
+            mtu: Some(1400),
+            alias: Some("uplink".into()),
+            ..Default::default()
+        };
+        let changed = client
+            .link()
+            .reconfigure(link_name, desired.clone())
+            .await
+            .unwrap();
+        let unchanged = client.link().reconfigure(link_name, desired).await.unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        assert!(changed);
+        assert!(!unchanged);
+        assert_eq!(link.mtu, 1400);
+        assert!(link.link_flags().is_up());
+        assert_eq!(link.alias, Some("uplink".into()));
+    }
+
+    #[tokio::test]
+    async fn test_set_stable_secret_invalid_device() {
+        let client = IpCommand::new().unwrap().dry_run();
+        for device in &[
+            "../../kernel/x",
+            "/etc/shadow",
+            "..",
+            "",
+            "sixteen_bytes_00",
+        ] {
+            match client.link().set_stable_secret(device, "::1").await {
+                Err(Error::InvalidArgumentError { field, value }) => {
+                    assert_eq!(field, "device");
+                    assert_eq!(value, *device);
+                }
+                result => panic!("{:?} for {:?}", result, device),
+            }
+        }
+        assert!(client.recorded_commands().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_stable_secret() {
+        let link_name = "test_secret0";
+        let secret = "2001:db8:1:2:3:4:5:6";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .set_stable_secret(link_name, secret)
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                state: Some(LinkStatus::Up),
+                address_generation_mode: Some(AddressGenerationMode::StablePrivacy),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let addresses = client
+            .address()
+            .show(Some(AddressShowConfiguration {
+                device: Some(link_name.into()),
+                scope: Some(Scope::Link),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        let address_info = addresses[0].address_info.clone().unwrap_or_default();
+        assert!(address_info
+            .iter()
+            .any(|info| info.family.as_deref() == Some("inet6")));
+
+        // Within a network namespace the parameter is written by a command.
+        let client = client.with_namespace("test").dry_run();
+        client
+            .link()
+            .set_stable_secret(link_name, secret)
+            .await
+            .unwrap();
+        assert_eq!(
+            client.recorded_commands(),
+            vec![vec![
+                "netns",
+                "exec",
+                "test",
+                "tee",
+                "/proc/sys/net/ipv6/conf/test_secret0/stable_secret"
+            ]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_address_generation_mode() {
+        let link_name = "test_addrgen0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // Without a stable secret some kernels reject the mode.
+        let result = client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                address_generation_mode: Some(AddressGenerationMode::StablePrivacy),
+                ..Default::default()
+            })
+            .await;
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        match result {
+            Ok(()) | Err(Error::CommandFailedError { .. }) => {}
+            Err(error) => panic!("unexpected error: {}", error),
+        }
+
+        let client = client.dry_run();
+        for mode in [
+            AddressGenerationMode::Eui64,
+            AddressGenerationMode::None,
+            AddressGenerationMode::StablePrivacy,
+            AddressGenerationMode::RandomFallback,
+            AddressGenerationMode::Raw("eui64".into()),
+        ] {
+            client
+                .link()
+                .set(LinkSetConfiguration {
+                    device: LinkDeviceOrGroup::Device(link_name.into()),
+                    address_generation_mode: Some(mode),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+        let modes: Vec<String> = client
+            .recorded_commands()
+            .into_iter()
+            .map(|command| command[5].clone())
+            .collect();
+        assert_eq!(
+            modes,
+            vec!["eui64", "none", "stable_secret", "random", "eui64"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_group() {
+        let link_names = ["test_group0", "test_group1"];
+        let group = 42;
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_names[0].into(),
+                group: Some(group),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_names[1].into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_names[1].into()),
+                group: Some(group),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        for state in &[LinkStatus::Up, LinkStatus::Down] {
+            client
+                .link()
+                .set(LinkSetConfiguration {
+                    device: LinkDeviceOrGroup::DeviceGroup(group),
+                    state: Some(state.clone()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        let links = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::DeviceGroup(group),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        for link_name in &link_names {
+            client
+                .link()
+                .delete(LinkDeleteConfiguration {
+                    device: LinkDeviceOrGroup::Device((*link_name).into()),
+                    link_type: "dummy".into(),
+                })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(links.len(), 2);
+        for link in &links {
+            assert!(link_names.contains(&link.name.as_str()));
+            assert!(!link.link_flags().is_up());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_group() {
+        let link_names = ["test_group2", "test_group3"];
+        let group = 43;
+
+        let client = IpCommand::new().unwrap();
+        for link_name in &link_names {
+            client
+                .link()
+                .add(LinkAddConfiguration {
+                    name: (*link_name).into(),
+                    group: Some(group),
+                    link_type: "dummy".into(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::DeviceGroup(group),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        let links = client.link().show(None).await.unwrap();
+        assert!(!links
+            .iter()
+            .any(|link| link_names.contains(&link.name.as_str())));
+
+        assert!(client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::DeviceGroup(0),
+                ..Default::default()
+            })
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_rollback_configuration() {
+        let link: Link = serde_json::from_str(
+            r#"{"ifindex":10,"ifname":"dummy0","flags":["BROADCAST","NOARP"],"mtu":1500,"qdisc":"noop","operstate":"DOWN","txqlen":1000,"address":"02:00:00:00:01:00"}"#,
+        )
+        .unwrap();
+        let rollback = LinkSetConfiguration {
+            device: LinkDeviceOrGroup::Device("dummy0".into()),
+            state: Some(LinkStatus::Up),
+            arp: Some(true),
+            new_name: Some("dummy1".into()),
+            mtu: Some(1400),
+            ..Default::default()
+        }
+        .rollback(&link);
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&rollback)
+            .unwrap();
+        assert_eq!(
+            args,
+            vec!["dev", "dummy1", "down", "arp", "off", "name", "dummy0", "mtu", "1500"]
+        );
+    }
+
+    #[test]
+    fn test_serialize_ipvlan() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkAddConfiguration {
+                name: "ipvlan0".into(),
+                device: Some("eth0".into()),
+                link_type: LinkKind::Ipvlan(IpvlanConfiguration {
+                    mode: IpvlanMode::L3Symmetric,
+                    flags: Some(IpvlanFlag::Private),
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            args,
+            vec!["name", "ipvlan0", "link", "eth0", "type", "ipvlan", "mode", "l3s", "private"]
+        );
+    }
+
+    #[test]
+    fn test_serialize_gre_encapsulation() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkAddConfiguration {
+                name: "gre0".into(),
+                link_type: LinkKind::Gre(GreConfiguration {
+                    remote: Some("192.0.2.2".into()),
+                    encapsulation: Some(TunnelEncap {
+                        destination_port: 5555,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "name",
+                "gre0",
+                "type",
+                "gre",
+                "remote",
+                "192.0.2.2",
+                "encap",
+                "fou",
+                "encap-sport",
+                "auto",
+                "encap-dport",
+                "5555"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_vlan() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkAddConfiguration {
+                name: "vlan0".into(),
+                device: Some("eth0".into()),
+                link_type: LinkKind::Vlan(VlanConfiguration {
+                    id: 100,
+                    egress_qos_map: vec![(1, 2), (3, 4)],
+                    mvrp: Some(true),
+                    loose_binding: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "name",
+                "vlan0",
+                "link",
+                "eth0",
+                "type",
+                "vlan",
+                "id",
+                "100",
+                "egress-qos-map",
+                "1:2",
+                "3:4",
+                "mvrp",
+                "on",
+                "loose_binding",
+                "off"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_vlan() {
+        let parent_link_name = "test_vlanlink0";
+        let link_name = "test_vlan0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: parent_link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                device: Some(parent_link_name.into()),
+                link_type: LinkKind::Vlan(VlanConfiguration {
+                    id: 100,
+                    egress_qos_map: vec![(1, 2)],
+                    gvrp: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                details: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        // Deleting the parent device also deletes the vlan device.
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(parent_link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        let link_info = link[0].link_info.as_ref().unwrap();
+        assert_eq!(link_info.kind, Some("vlan".into()));
+        let link_data = link_info.data.as_ref().unwrap();
+        assert_eq!(link_data["id"], 100);
+        assert_eq!(link_data["egress_qos"][0]["from"], 1);
+        assert_eq!(link_data["egress_qos"][0]["to"], 2);
+        let flags = link_data["flags"].as_array().unwrap();
+        assert!(flags.contains(&"GVRP".into()));
+        assert!(flags.contains(&"REORDER_HDR".into()));
+    }
+
+    #[tokio::test]
+    async fn test_add_wireguard() {
+        let link_name = "test_wg0";
+
+        let client = IpCommand::new().unwrap();
+        let result = client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: LinkKind::Wireguard,
+                ..Default::default()
+            })
+            .await;
+
+        // Skip on kernels without wireguard support.
+        if let Err(Error::CommandFailedError { stderr, .. }) = &result {
+            if stderr.contains("Unknown device type") || stderr.contains("not supported") {
+                return;
+            }
+        }
+        result.unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                details: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "wireguard".into(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            link[0].link_info.as_ref().unwrap().kind,
+            Some("wireguard".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_show_after_namespace_move() {
+        let link_name = "test_move0";
+        let test_namespace = "ip-command-test-move-namespace";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                mtu: Some(1400),
+                transmit_queue_length: Some(100),
+                link_type: "veth".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client.netns().add(test_namespace).await.unwrap();
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                namespace: Some(test_namespace.into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        client
+            .with_namespace(test_namespace)
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                state: Some(LinkStatus::Up),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let link = client
+            .with_namespace(test_namespace)
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        // Deleting the namespace deletes the veth pair.
+        client.netns().delete(test_namespace).await.unwrap();
+
+        assert_eq!(link[0].name, link_name);
+        assert_eq!(link[0].mtu, 1400);
+        assert_eq!(link[0].transmit_queue_length, Some(100));
+        assert_eq!(link[0].queueing_discipline, "noqueue");
+        assert!(link[0].link_flags().is_up());
+        // The peer remained in the host namespace, so is reported by index only.
+        assert_eq!(link[0].link, None);
+        assert!(link[0].link_index.is_some());
+        assert!(link[0].link_network_namespace_id.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_add_veth_peer_namespace() {
+        let test_namespace = "ip-command-test-veth-peer-namespace";
+        let link_name = "test_vethns0";
+        let peer_name = "test_vethns1";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let result = client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: LinkKind::Veth(VethConfiguration {
+                    peer_name: Some(peer_name.into()),
+                    peer_namespace: Some(test_namespace.into()),
+                }),
+                ..Default::default()
+            })
+            .await;
+        let peer_in_namespace = client
+            .with_namespace(test_namespace)
+            .link()
+            .exists(peer_name)
+            .await;
+        let peer_in_host = client.link().exists(peer_name).await;
+        let link_in_host = client.link().exists(link_name).await;
+
+        // Deleting the namespace deletes the veth pair.
+        client.netns().delete(test_namespace).await.unwrap();
+
+        result.unwrap();
+        assert!(peer_in_namespace.unwrap());
+        assert!(!peer_in_host.unwrap());
+        assert!(link_in_host.unwrap());
+    }
+
+    #[test]
+    fn test_serialize_veth() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkAddConfiguration {
+                name: "veth0".into(),
+                link_type: LinkKind::Veth(VethConfiguration {
+                    peer_name: Some("veth1".into()),
+                    peer_namespace: Some("test".into()),
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            args,
+            vec!["name", "veth0", "type", "veth", "peer", "name", "veth1", "netns", "test"]
+        );
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkAddConfiguration {
+                name: "veth0".into(),
+                link_type: LinkKind::Veth(VethConfiguration::default()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(args, vec!["name", "veth0", "type", "veth"]);
+    }
+
+    #[test]
+    fn test_serialize_set_type_arguments() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device("br0".into()),
+                arp: Some(true),
+                link_type: Some(LinkKind::Bridge(BridgeConfiguration {
+                    stp_state: Some(false),
+                    vlan_filtering: Some(true),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "dev",
+                "br0",
+                "arp",
+                "on",
+                "type",
+                "bridge",
+                "stp_state",
+                "0",
+                "vlan_filtering",
+                "1"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_bridge_vlan_filtering() {
+        let link_name = "test_brvf0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: LinkKind::Bridge(BridgeConfiguration::default()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let mut vlan_filtering = vec![];
+        for enabled in &[true, false] {
+            client
+                .link()
+                .set(LinkSetConfiguration {
+                    device: LinkDeviceOrGroup::Device(link_name.into()),
+                    link_type: Some(LinkKind::Bridge(BridgeConfiguration {
+                        vlan_filtering: Some(*enabled),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+
+            let link = client
+                .link()
+                .show(Some(LinkShowConfiguration {
+                    device: LinkDeviceOrGroup::Device(link_name.into()),
+                    details: true,
+                    ..Default::default()
+                }))
+                .await
+                .unwrap();
+            let link_info = link[0].link_info.as_ref().unwrap();
+            vlan_filtering.push(link_info.data.as_ref().unwrap()["vlan_filtering"].clone());
+        }
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(vlan_filtering, vec![1, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_operstate() {
+        let link_name = "test_wait0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "veth".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let peer = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()[0]
+            .link
+            .clone()
+            .unwrap();
+        for device in &[link_name.to_string(), peer] {
+            client
+                .link()
+                .set(LinkSetConfiguration {
+                    device: LinkDeviceOrGroup::Device(device.clone()),
+                    state: Some(LinkStatus::Up),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        let link = client
+            .wait_for(
+                Duration::from_millis(10),
+                Duration::from_secs(5),
+                |client| async move {
+                    let mut links = client
+                        .link()
+                        .show(Some(LinkShowConfiguration {
+                            device: LinkDeviceOrGroup::Device(link_name.into()),
+                            ..Default::default()
+                        }))
+                        .await?;
+                    Ok(links.pop().filter(|link| link.state == "UP"))
+                },
+            )
+            .await;
+
+        // Deleting either end deletes the veth pair.
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "veth".into(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(link.unwrap().name, link_name);
+    }
+
+    #[cfg(feature = "streaming")]
+    #[tokio::test]
+    async fn test_wait_up() {
+        let link_name = "test_wait1";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "veth".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let peer = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()[0]
+            .link
+            .clone()
+            .unwrap();
+
+        let timed_out = client
+            .link()
+            .wait_up(link_name, Duration::from_millis(100))
+            .await;
+        let set_up = async {
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+            for device in &[link_name.to_string(), peer] {
+                client
+                    .link()
+                    .set(LinkSetConfiguration {
+                        device: LinkDeviceOrGroup::Device(device.clone()),
+                        state: Some(LinkStatus::Up),
+                        ..Default::default()
+                    })
+                    .await?;
+            }
+            Ok::<_, Error>(())
+        };
+        let link = client.link();
+        let (waited, set_up) =
+            futures::join!(link.wait_up(link_name, Duration::from_secs(5)), set_up);
+        let already_up = client
+            .link()
+            .wait_up(link_name, Duration::from_millis(100))
+            .await;
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "veth".into(),
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(timed_out, Err(Error::WaitTimeoutError { .. })));
+        set_up.unwrap();
+        waited.unwrap();
+        already_up.unwrap();
+    }
+
+    #[cfg(feature = "streaming")]
+    #[test]
+    fn test_reports_up() {
+        let event = |line: &str| MonitorEvent {
+            object: Some(MonitorObject::Link),
+            deleted: false,
+            message: line.into(),
+        };
+        assert!(IpLinkCommand::reports_up(
+            &event(
+                "5: eth0@if4: <BROADCAST,UP,LOWER_UP> mtu 1500 qdisc noqueue state UP mode DEFAULT"
+            ),
+            "eth0"
+        ));
+        assert!(!IpLinkCommand::reports_up(
+            &event("5: eth0@if4: <BROADCAST,UP> mtu 1500 qdisc noqueue state LOWERLAYERDOWN"),
+            "eth0"
+        ));
+        assert!(!IpLinkCommand::reports_up(
+            &event("6: eth1: <BROADCAST,UP,LOWER_UP> mtu 1500 qdisc noqueue state UP"),
+            "eth0"
+        ));
+    }
+
+    #[test]
+    fn test_link_flags() {
+        let flags = LinkFlags::from_names(&["BROADCAST", "MULTICAST", "UP", "LOWER_UP", "UNKNOWN"]);
+        assert!(flags.is_up());
+        assert!(flags.is_running());
+        assert!(flags.is_lower_up());
+        assert!(flags.is_multicast());
+        assert!(!flags.is_no_arp());
+        assert!(flags.contains(LinkFlags::BROADCAST | LinkFlags::MULTICAST));
+        assert!(!flags.contains(LinkFlags::BROADCAST | LinkFlags::LOOPBACK));
+        assert_eq!(flags.bits(), 0x11003);
+        let flags = LinkFlags::from_names(&["NO-CARRIER", "BROADCAST", "MULTICAST", "UP"]);
+        assert!(flags.is_up());
+        assert!(!flags.is_running());
+        assert!(!LinkFlags::from_names(&["NOARP"]).is_running());
+        assert_eq!(LinkFlags::from_names::<&str>(&[]), LinkFlags::default());
+    }
+
+    #[test]
+    fn test_parse_oneline_output() {
+        let links = IpLinkCommand::parse_oneline_output(concat!(
+            "1: lo: <LOOPBACK,UP,LOWER_UP> mtu 65536 qdisc noqueue state UNKNOWN mode DEFAULT group default qlen 1000\\    link/loopback 00:00:00:00:00:00 brd 00:00:00:00:00:00\n",
+            "3: v0@v1: <BROADCAST,MULTICAST,M-DOWN> mtu 1500 qdisc noop master br0 state DOWN mode DEFAULT group default qlen 1000\\    link/ether ba:c7:e7:14:0f:8b brd ff:ff:ff:ff:ff:ff\\    alias my alias\n",
+            "4: v2@if5: <BROADCAST,MULTICAST> mtu 1400 qdisc noop state DOWN mode DEFAULT group default qlen 1000\\    link/ether ae:22:2b:80:80:86 brd ff:ff:ff:ff:ff:ff link-netnsid 0\n",
+        ))
+        .unwrap();
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].name, "lo");
+        assert_eq!(links[0].flags, vec!["LOOPBACK", "UP", "LOWER_UP"]);
+        assert_eq!(links[0].mtu, 65536);
+        assert_eq!(links[0].state, "UNKNOWN");
+        assert_eq!(links[0].link_type, Some("loopback".into()));
+        assert_eq!(links[1].interface_index, 3);
+        assert_eq!(links[1].name, "v0");
+        assert_eq!(links[1].link, Some("v1".into()));
+        assert_eq!(links[1].master, Some("br0".into()));
+        assert_eq!(links[1].queueing_discipline, "noop");
+        assert_eq!(links[1].transmit_queue_length, Some(1000));
+        assert_eq!(links[1].address, Some("ba:c7:e7:14:0f:8b".into()));
+        assert_eq!(links[1].broadcast, Some("ff:ff:ff:ff:ff:ff".into()));
+        assert_eq!(links[1].alias, Some("my alias".into()));
+        assert_eq!(links[2].link, None);
+        assert_eq!(links[2].link_index, Some(5));
+        assert_eq!(links[2].link_network_namespace_id, Some(0));
+        assert!(IpLinkCommand::parse_oneline_output("lo: mtu 1500").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_show_gso_parameters() {
+        let link_name = "test_gso0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                gso_maximum_size: Some(32_768),
+                gso_maximum_segments: Some(32),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                details: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(link[0].gso_maximum_size, Some(32_768));
+        assert_eq!(link[0].gso_maximum_segments, Some(32));
+        assert!(link[0].gro_maximum_size.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_exists_and_ensure_absent() {
+        let link_name = "test_absent0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let existed = client.link().exists(link_name).await.unwrap();
+        let removed = client
+            .link()
+            .ensure_absent(link_name, "dummy")
+            .await
+            .unwrap();
+        let exists = client.link().exists(link_name).await.unwrap();
+        let removed_again = client
+            .link()
+            .ensure_absent(link_name, "dummy")
+            .await
+            .unwrap();
+
+        assert!(existed);
+        assert!(removed);
+        assert!(!exists);
+        assert!(!removed_again);
+    }
+
+    #[tokio::test]
+    async fn test_show_promiscuity() {
+        let link_name = "test_promisc0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                promiscuous: Some(true),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let link = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                details: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        assert!(link[0].promiscuity.unwrap() >= 1);
+        assert!(link[0].all_multicast.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_show_statistics() {
+        let client = IpCommand::new().unwrap();
+        let links = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device("lo".into()),
+                statistics: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        assert!(links[0].statistics.is_some());
+
+        let links = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device("lo".into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        assert!(links[0].statistics.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_show_detailed_statistics() {
+        let client = IpCommand::new().unwrap();
+        let links = client
+            .link()
+            .show(Some(LinkShowConfiguration {
+                device: LinkDeviceOrGroup::Device("lo".into()),
+                detailed_statistics: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        let statistics = links[0].statistics.as_ref().unwrap();
+        assert_eq!(statistics.receive.crc_errors, Some(0));
+        assert_eq!(statistics.receive.missed_errors, Some(0));
+        assert_eq!(statistics.transmit.collisions, Some(0));
+        assert_eq!(statistics.transmit.carrier_errors, Some(0));
+        assert!(statistics.transmit.carrier_changes.is_some());
+        assert_eq!(statistics.transmit.crc_errors, None);
+    }
+
+    #[test]
+    fn test_deserialize_detailed_statistics() {
+        let statistics: LinkStats = serde_json::from_str(
+            r#"{"rx":{"bytes":1,"packets":2,"errors":3,"dropped":0,"over_errors":4,"multicast":5,
+            "length_errors":6,"crc_errors":7,"frame_errors":8,"fifo_errors":9,"missed_errors":10},
+            "tx":{"bytes":1,"packets":2,"errors":3,"dropped":0,"carrier_errors":11,"collisions":12,
+            "aborted_errors":13,"fifo_errors":14,"window_errors":15,"heartbeat_errors":16,
+            "carrier_changes":17}}"#,
+        )
+        .unwrap();
+        assert_eq!(statistics.receive.overrun_errors, Some(4));
+        assert_eq!(statistics.receive.multicast, Some(5));
+        assert_eq!(statistics.receive.missed_errors, Some(10));
+        assert_eq!(statistics.transmit.fifo_errors, Some(14));
+        assert_eq!(statistics.transmit.heartbeat_errors, Some(16));
+        assert_eq!(statistics.transmit.carrier_changes, Some(17));
+        assert_eq!(statistics.transmit.multicast, None);
+    }
+
+    #[test]
+    fn test_stats_delta() {
+        let counters = |bytes, packets| LinkCounters {
+            bytes,
+            packets,
+            ..Default::default()
+        };
+        let earlier = LinkStats {
+            receive: counters(1_000, 10),
+            transmit: counters(5_000, 20),
+        };
+        let later = LinkStats {
+            receive: counters(3_000, 30),
+            transmit: counters(4_000, 40),
+        };
+        assert_eq!(
+            LinkStatsDelta::new(&earlier, &later, Duration::from_millis(500)),
+            LinkStatsDelta {
+                receive_bytes_per_second: 4_000.0,
+                receive_packets_per_second: 40.0,
+                transmit_bytes_per_second: 0.0,
+                transmit_packets_per_second: 40.0,
+            }
+        );
+        assert_eq!(
+            LinkStatsDelta::new(&earlier, &later, Duration::from_secs(0)),
+            LinkStatsDelta::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_vf_defaults() {
+        let client = IpCommand::new().unwrap().dry_run();
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device("eth0".into()),
+                virtual_function: Some(VfConfiguration::sane_defaults(3)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            client.recorded_commands(),
+            vec![vec![
+                "link", "set", "dev", "eth0", "vf", "3", "spoofchk", "off", "trust", "on"
+            ]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_rejects_invalid_mtu() {
+        let client = IpCommand::new().unwrap().dry_run();
+        for (mtu, transmit_queue_length) in &[(Some(0), None), (None, Some(u32::MAX))] {
+            let result = client
+                .link()
+                .set(LinkSetConfiguration {
+                    device: LinkDeviceOrGroup::Device("eth0".into()),
+                    mtu: *mtu,
+                    transmit_queue_length: *transmit_queue_length,
+                    ..Default::default()
+                })
+                .await;
+            assert!(matches!(result, Err(Error::InvalidArgumentError { .. })));
+        }
+        assert!(client.recorded_commands().is_empty());
+        // Left to the kernel (only a warning with the tracing feature).
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device("lo".into()),
+                mtu: Some(65537),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(client.recorded_commands().len(), 1);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_validate_warns_of_large_mtu() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use tracing::span::{Attributes, Id, Record};
+
+        /// Counts warning events.
+        struct Warnings(Arc<AtomicUsize>);
+
+        impl tracing::Subscriber for Warnings {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &tracing::Event<'_>) {
+                if *event.metadata().level() == tracing::Level::WARN {
+                    self.0.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let validate = |mtu| {
+            LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device("lo".into()),
+                mtu: Some(mtu),
+                ..Default::default()
+            }
+            .validate()
+        };
+        tracing::subscriber::with_default(Warnings(warnings.clone()), || {
+            validate(65535).unwrap();
+            assert_eq!(warnings.load(Ordering::SeqCst), 0);
+            validate(65537).unwrap();
+            assert_eq!(warnings.load(Ordering::SeqCst), 1);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_set_rejects_master_and_vrf() {
+        let client = IpCommand::new().unwrap().dry_run();
+        let result = client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device("eth0".into()),
+                master: Some(MasterSetConfiguration::Enslaved("br0".into())),
+                vrf_master: Some("vrf0".into()),
+                ..Default::default()
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::ConflictingConfigurationError { .. })
+        ));
+        assert!(client.recorded_commands().is_empty());
+    }
+
     #[tokio::test]
     async fn test_set_xdp() {
         let link_name = "test_link3";
@@ -18,6 +18,8 @@ use crate::{Error, IpCommand};
 
 #[derive(Clone)]
 pub struct IpMACsecCommand<'l> {
+    // Unused until the subcommand is implemented.
+    #[allow(dead_code)]
     ip_command: &'l IpCommand,
 }
 
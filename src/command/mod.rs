@@ -16,6 +16,9 @@
 
 pub mod address;
 pub mod address_label;
+pub mod batch;
+pub mod bridge_vlan;
+pub mod fou;
 pub mod l2tp;
 pub mod link;
 pub mod macsec;
@@ -36,6 +39,9 @@ pub mod tuntap;
 
 pub use self::address::IpAddressCommand;
 pub use self::address_label::IpAddressLabelCommand;
+pub use self::batch::IpBatchCommand;
+pub use self::bridge_vlan::IpBridgeVlanCommand;
+pub use self::fou::IpFouCommand;
 pub use self::l2tp::IpL2tpCommand;
 pub use self::link::IpLinkCommand;
 pub use self::macsec::IpMACsecCommand;
@@ -14,10 +14,213 @@
  * limitations under the License.
  */
 
-use crate::{Error, IpCommand};
+use crate::IpCommand;
+#[cfg(feature = "streaming")]
+use crate::{CommandError, ConsoleStream, Error};
+#[cfg(feature = "streaming")]
+use futures::stream::{self, Stream, StreamExt};
+#[cfg(feature = "streaming")]
+use futures::task::{Context, Poll};
+#[cfg(feature = "streaming")]
+use snafu::ResultExt;
+#[cfg(feature = "streaming")]
+use std::pin::Pin;
+#[cfg(feature = "streaming")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "streaming")]
+use tokio::time::timeout;
+
+/// The kind of object a monitored event concerns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MonitorObject {
+    Link,
+    Address,
+    Route,
+    MulticastRoute,
+    Neighbor,
+    NetworkConfiguration,
+    Rule,
+    NetworkNamespaceId,
+    Prefix,
+    NextHop,
+}
+
+#[cfg(feature = "streaming")]
+impl MonitorObject {
+    fn keyword(self) -> &'static str {
+        match self {
+            MonitorObject::Link => "link",
+            MonitorObject::Address => "address",
+            MonitorObject::Route => "route",
+            MonitorObject::MulticastRoute => "mroute",
+            MonitorObject::Neighbor => "neigh",
+            MonitorObject::NetworkConfiguration => "netconf",
+            MonitorObject::Rule => "rule",
+            MonitorObject::NetworkNamespaceId => "nsid",
+            MonitorObject::Prefix => "prefix",
+            MonitorObject::NextHop => "nexthop",
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "LINK" => Some(MonitorObject::Link),
+            "ADDR" => Some(MonitorObject::Address),
+            "ROUTE" => Some(MonitorObject::Route),
+            "MROUTE" => Some(MonitorObject::MulticastRoute),
+            "NEIGH" => Some(MonitorObject::Neighbor),
+            "NETCONF" => Some(MonitorObject::NetworkConfiguration),
+            "RULE" => Some(MonitorObject::Rule),
+            "NSID" => Some(MonitorObject::NetworkNamespaceId),
+            "PREFIX" => Some(MonitorObject::Prefix),
+            "NEXTHOP" => Some(MonitorObject::NextHop),
+            _ => None,
+        }
+    }
+}
+
+/// A netlink event reported by the monitor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitorEvent {
+    /// The kind of object concerned, if recognized.
+    pub object: Option<MonitorObject>,
+    /// Was the object deleted.
+    pub deleted: bool,
+    /// The event as printed by ip(8) on a single line, without the label or "Deleted" prefix.
+    pub message: String,
+}
+
+impl MonitorEvent {
+    /// Parse a labelled, single line event (eg. "[LINK]Deleted 4: eth0: <...> ...").
+    #[cfg(feature = "streaming")]
+    fn parse(line: &str) -> Self {
+        let (object, message) = match line.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+            Some((label, message)) => (MonitorObject::from_label(label), message),
+            None => (None, line),
+        };
+        let (deleted, message) = match message.strip_prefix("Deleted ") {
+            Some(message) => (true, message),
+            None => (false, message),
+        };
+        Self {
+            object,
+            deleted,
+            message: message.trim().into(),
+        }
+    }
+
+    /// Identifies the object concerned, later events with the same key supersede earlier ones.
+    pub fn key(&self) -> (Option<MonitorObject>, String) {
+        let tokens: Vec<&str> = self.message.split_whitespace().collect();
+        let identity = match self.object {
+            // The interface index, eg. "4:".
+            Some(MonitorObject::Link) => tokens.iter().take(1).cloned().collect(),
+            // The interface, family and address, eg. "4: eth0 inet 192.0.2.1/24".
+            Some(MonitorObject::Address) => tokens.iter().take(4).cloned().collect(),
+            // The family and interface, eg. "inet eth0".
+            Some(MonitorObject::NetworkConfiguration) => tokens.iter().take(2).cloned().collect(),
+            // The address and interface, eg. "192.0.2.1 dev eth0".
+            Some(MonitorObject::Neighbor) => Self::tokens_until(&tokens, &["lladdr"]),
+            // The type, prefix, device and table, but not the attributes of the route.
+            Some(MonitorObject::Route) => {
+                let mut identity =
+                    Self::tokens_until(&tokens, &["proto", "scope", "src", "metric", "pref"]);
+                if let Some(position) = identity.iter().position(|token| *token == "via") {
+                    identity.drain(position..(position + 2).min(identity.len()));
+                }
+                identity
+            }
+            _ => tokens,
+        };
+        (self.object, identity.join(" "))
+    }
+
+    fn tokens_until<'a>(tokens: &[&'a str], terminators: &[&str]) -> Vec<&'a str> {
+        tokens
+            .iter()
+            .take_while(|token| !terminators.contains(token))
+            .cloned()
+            .collect()
+    }
+}
+
+/// A stream of monitored events.
+#[cfg(feature = "streaming")]
+pub struct MonitorStream {
+    console_stream: ConsoleStream,
+}
+
+#[cfg(feature = "streaming")]
+impl MonitorStream {
+    /// Collapse the events concerning the same object (see `MonitorEvent::key`), within a window
+    /// starting at the first event of each burst, into the latest. Coalesced events are yielded
+    /// at the end of each window, in the order of their latest occurrence.
+    pub fn coalesce(
+        self,
+        window: Duration,
+    ) -> Pin<Box<dyn Stream<Item = tokio::io::Result<MonitorEvent>> + Send>> {
+        Box::pin(stream::unfold(
+            (self, Vec::new(), false),
+            move |(mut inner, mut pending, mut finished)| async move {
+                if pending.is_empty() && !finished {
+                    let mut bursting = Self::merge(&mut pending, inner.next().await);
+                    let deadline = Instant::now() + window;
+                    while bursting {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        match timeout(remaining, inner.next()).await {
+                            Ok(next_event) => bursting = Self::merge(&mut pending, next_event),
+                            Err(_) => break,
+                        }
+                    }
+                    finished = pending.is_empty();
+                }
+                if pending.is_empty() {
+                    None
+                } else {
+                    let event = pending.remove(0);
+                    Some((event, (inner, pending, finished)))
+                }
+            },
+        ))
+    }
+
+    /// Merge the next event into the pending ones, is the burst of events ongoing?
+    fn merge(
+        pending: &mut Vec<tokio::io::Result<MonitorEvent>>,
+        next_event: Option<tokio::io::Result<MonitorEvent>>,
+    ) -> bool {
+        match next_event {
+            Some(Ok(event)) => {
+                let key = event.key();
+                pending.retain(|pending_event| {
+                    !matches!(pending_event, Ok(pending_event) if pending_event.key() == key)
+                });
+                pending.push(Ok(event));
+                true
+            }
+            Some(Err(error)) => {
+                pending.push(Err(error));
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(feature = "streaming")]
+impl Stream for MonitorStream {
+    type Item = tokio::io::Result<MonitorEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.console_stream)
+            .poll_next(cx)
+            .map(|line| line.map(|line| line.map(|line| MonitorEvent::parse(&line.line))))
+    }
+}
 
 #[derive(Clone)]
 pub struct IpMonitorCommand<'l> {
+    #[cfg_attr(not(feature = "streaming"), allow(dead_code))]
     ip_command: &'l IpCommand,
 }
 
@@ -26,8 +229,185 @@ impl<'l> IpMonitorCommand<'l> {
         Self { ip_command }
     }
 
-    /// Monitor the state of devices, addresses and routes.
-    pub async fn monitor(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Monitor the state of devices, addresses and routes (all objects if none are given).
+    #[cfg(feature = "streaming")]
+    pub async fn monitor(&self, objects: &[MonitorObject]) -> Result<MonitorStream, Error> {
+        let mut args: Vec<String> = vec!["-oneline".into(), "monitor".into(), "label".into()];
+        args.extend(objects.iter().map(|object| object.keyword().into()));
+        Ok(MonitorStream {
+            console_stream: self
+                .ip_command
+                .command_with_streaming_output(&args, false, false)
+                .await?,
+        })
+    }
+
+    /// Collect the next `count` events (of all objects if none are given), or fewer when
+    /// `collect_timeout` elapses first. The monitor is stopped once collected.
+    #[cfg(feature = "streaming")]
+    pub async fn collect(
+        &self,
+        objects: &[MonitorObject],
+        count: usize,
+        collect_timeout: Duration,
+    ) -> Result<Vec<MonitorEvent>, Error> {
+        let mut events = self.monitor(objects).await?;
+        let deadline = Instant::now() + collect_timeout;
+        let mut collected = vec![];
+        while collected.len() < count {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match timeout(remaining, events.next()).await {
+                Ok(Some(event)) => collected.push(event.context(CommandError {})?),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        Ok(collected)
+    }
+}
+
+#[cfg(all(test, feature = "streaming"))]
+mod tests {
+    use super::*;
+    use crate::command::link::*;
+    use tokio::time::delay_for;
+
+    #[test]
+    fn test_parse_and_key() {
+        let event = MonitorEvent::parse(
+            "[LINK]Deleted 27: test0: <BROADCAST,MULTICAST> mtu 1500 qdisc noop state DOWN",
+        );
+        assert_eq!(event.object, Some(MonitorObject::Link));
+        assert!(event.deleted);
+        assert_eq!(event.key(), (Some(MonitorObject::Link), "27:".into()));
+
+        let event = MonitorEvent::parse(
+            "[ADDR]27: test0    inet 10.98.0.1/24 scope global test0\\       valid_lft forever",
+        );
+        assert!(!event.deleted);
+        assert_eq!(
+            event.key(),
+            (
+                Some(MonitorObject::Address),
+                "27: test0 inet 10.98.0.1/24".into()
+            )
+        );
+
+        let event = MonitorEvent::parse(
+            "[ROUTE]default via 192.0.2.1 dev test0 table 10 proto static metric 20",
+        );
+        assert_eq!(
+            event.key(),
+            (
+                Some(MonitorObject::Route),
+                "default dev test0 table 10".into()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect() {
+        let test_namespace = "ip-command-test-monitor-collect-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let monitor = namespace_client.monitor();
+        let add_veth = async {
+            delay_for(Duration::from_millis(500)).await;
+            namespace_client
+                .link()
+                .add(LinkAddConfiguration {
+                    name: "veth0".into(),
+                    link_type: LinkKind::Veth(VethConfiguration {
+                        peer_name: Some("veth1".into()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .await
+        };
+        let (events, added) = futures::join!(
+            monitor.collect(&[MonitorObject::Link], 2, Duration::from_secs(5)),
+            add_veth
+        );
+        let timed_out = namespace_client
+            .monitor()
+            .collect(&[MonitorObject::Link], 1, Duration::from_millis(100))
+            .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        added.unwrap();
+        let events = events.unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|event| event.object == Some(MonitorObject::Link) && !event.deleted));
+        assert!(events.iter().any(|event| event.message.contains("veth0")));
+        assert!(timed_out.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_coalesce() {
+        let test_namespace = "ip-command-test-monitor-coalesce-namespace";
+        let link_name = "test_monitor0";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let result = async {
+            let mut events = namespace_client
+                .monitor()
+                .monitor(&[MonitorObject::Link])
+                .await?
+                .coalesce(Duration::from_secs(1));
+
+            delay_for(Duration::from_millis(500)).await;
+
+            namespace_client
+                .link()
+                .add(LinkAddConfiguration {
+                    name: link_name.into(),
+                    link_type: LinkKind::Veth(VethConfiguration {
+                        peer_name: Some("test_monitor1".into()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .await?;
+
+            for state in &[
+                LinkStatus::Up,
+                LinkStatus::Down,
+                LinkStatus::Up,
+                LinkStatus::Down,
+            ] {
+                namespace_client
+                    .link()
+                    .set(LinkSetConfiguration {
+                        device: LinkDeviceOrGroup::Device(link_name.into()),
+                        state: Some(state.clone()),
+                        ..Default::default()
+                    })
+                    .await?;
+            }
+
+            // Events of the device itself, rather than of its peer.
+            let mut link_events = vec![];
+            while let Ok(Some(Ok(event))) = timeout(Duration::from_secs(2), events.next()).await {
+                if event.message.contains(&format!(": {}@", link_name)) {
+                    link_events.push(event);
+                }
+            }
+            Ok::<_, Error>(link_events)
+        }
+        .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        let link_events = result.unwrap();
+        assert_eq!(link_events.len(), 1);
+        assert!(link_events[0].message.contains("state DOWN"));
+        assert!(!link_events[0].message.contains(",UP"));
     }
 }
@@ -14,7 +14,7 @@
  * limitations under the License.
  */
 
-use super::rule::IpRuleCommand;
+use super::rule::{IpRuleCommand, Rule, RuleAddConfiguration, RuleDeleteConfiguration};
 use crate::{Error, IpCommand};
 
 #[derive(Clone)]
@@ -25,18 +25,18 @@ pub struct IpMulticastRuleCommand<'l> {
 // TODO investigate multicast argument passing
 impl<'l> IpMulticastRuleCommand<'l> {
     pub fn new(ip_command: &'l IpCommand) -> Self {
-        let ip_rule_command = IpRuleCommand::new(ip_command);
+        let ip_rule_command = IpRuleCommand::multicast(ip_command);
         Self { ip_rule_command }
     }
 
     /// Insert a new multicast rule.
-    pub async fn add(&self) -> Result<(), Error> {
-        self.ip_rule_command.add().await
+    pub async fn add(&self, configuration: RuleAddConfiguration) -> Result<(), Error> {
+        self.ip_rule_command.add(configuration).await
     }
 
     /// Delete a multicast rule.
-    pub async fn delete(&self) -> Result<(), Error> {
-        self.ip_rule_command.delete().await
+    pub async fn delete(&self, configuration: RuleDeleteConfiguration) -> Result<(), Error> {
+        self.ip_rule_command.delete(configuration).await
     }
 
     /// Flush multicast rules table information.
@@ -55,7 +55,7 @@ impl<'l> IpMulticastRuleCommand<'l> {
     }
 
     // List multicast rules.
-    pub async fn list(&self) -> Result<(), Error> {
+    pub async fn list(&self) -> Result<Vec<Rule>, Error> {
         self.ip_rule_command.list().await
     }
 }
@@ -15,16 +15,44 @@
  */
 
 use crate::*;
-use serde::Deserialize;
-use snafu::ResultExt;
-
-#[derive(Debug, Clone, Deserialize)]
+#[cfg(feature = "streaming")]
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt, ResultExt};
+#[cfg(feature = "streaming")]
+use std::collections::{BTreeSet, VecDeque};
+use std::process::ExitStatus;
+#[cfg(feature = "streaming")]
+use tokio::time::delay_for;
+
+/// How often the named network namespaces are polled when monitoring without stdbuf.
+#[cfg(feature = "streaming")]
+const MONITOR_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The returned (named) network namespace structure.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Namespace {
     pub name: String,
-    #[serde(alias = "id", alias = "nsid")]
+    /// The id of the namespace, as seen from the current namespace (see `set`).
+    #[serde(alias = "id", alias = "nsid", skip_serializing_if = "Option::is_none")]
     pub id: Option<u32>,
 }
 
+/// The captured output of a command run in a network namespace (see `exec_capture`).
+#[derive(Debug, Clone)]
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: ExitStatus,
+}
+
+impl Namespace {
+    /// Has the namespace been assigned an id (see `set`)?
+    pub fn is_id_assigned(&self) -> bool {
+        self.id.is_some()
+    }
+}
+
 #[derive(Clone)]
 pub struct IpNetNamespaceCommand<'l> {
     ip_command: &'l IpCommand,
@@ -39,33 +67,39 @@ impl<'l> IpNetNamespaceCommand<'l> {
     pub async fn list(&self) -> Result<Vec<Namespace>, Error> {
         let output = self
             .ip_command
-            .command(&["netns".into(), "list".into()], false, None)
+            .command(&["netns", "list"], true, false, None)
             .await?;
-        Ok(serde_json::from_str(&output).context(JsonDeserializationError {})?)
+        serde_json::from_str(&output).context(JsonDeserializationError {})
     }
 
     /// Create a new named network namespace.
     pub async fn add(&self, network_namespace_name: &str) -> Result<(), Error> {
         self.ip_command
-            .command(
-                &["netns".into(), "add".into(), network_namespace_name.into()],
-                false,
-                None,
-            )
+            .command_without_output(&["netns", "add", network_namespace_name], None)
             .await
-            .map(|_| ())
     }
 
     /// Delete the name of a network.
     pub async fn delete(&self, network_namespace_name: &str) -> Result<(), Error> {
         self.ip_command
-            .command(
-                &["netns".into(), "del".into(), network_namespace_name.into()],
-                false,
+            .command_without_output(&["netns", "del", network_namespace_name], None)
+            .await
+    }
+
+    /// Name the network namespace of an existing process (eg. one created by a container
+    /// runtime), `delete` only removes the name again.
+    pub async fn attach(&self, network_namespace_name: &str, process_id: u32) -> Result<(), Error> {
+        self.ip_command
+            .command_without_output(
+                &[
+                    "netns".into(),
+                    "attach".into(),
+                    network_namespace_name.into(),
+                    format!("{}", process_id),
+                ],
                 None,
             )
             .await
-            .map(|_| ())
     }
 
     /// Assign an id to a peer network namespace.
@@ -78,70 +112,204 @@ impl<'l> IpNetNamespaceCommand<'l> {
             .map(|id| format!("{}", id))
             .unwrap_or_else(|| "auto".into());
         self.ip_command
-            .command(
+            .command_without_output(
                 &[
                     "netns".into(),
                     "set".into(),
                     network_namespace_name.into(),
                     network_namespace_id,
                 ],
-                false,
                 None,
             )
             .await
-            .map(|_| ())
     }
 
     /// Report network namespaces names for process.
+    ///
+    /// A process which doesn't exist is reported as `NotFoundError`.
     pub async fn identify(&self, process_id: u32) -> Result<String, Error> {
-        self.ip_command
+        match self
+            .ip_command
             .command(
                 &["netns".into(), "identify".into(), format!("{}", process_id)],
                 false,
+                false,
                 None,
             )
             .await
-            .map(|result| result.trim().into())
+        {
+            Ok(result) => Ok(result.trim().into()),
+            Err(Error::CommandFailedError { stderr, .. })
+                if IpCommand::namespace_missing(&stderr) =>
+            {
+                NotFoundError {}.fail()
+            }
+            Err(error) => Err(error),
+        }
     }
 
     /// Report processes in the named network namespace.
     pub async fn pids(&self, network_namespace_name: &str) -> Result<Vec<u32>, Error> {
-        let output = self
+        let output = match self
             .ip_command
             .command(
-                &["netns".into(), "pids".into(), network_namespace_name.into()],
+                &["netns", "pids", network_namespace_name],
+                false,
                 false,
                 None,
             )
+            .await
+        {
+            Ok(output) => output,
+            Err(Error::CommandFailedError { stderr, .. })
+                if IpCommand::namespace_missing(&stderr) =>
+            {
+                return NamespaceNotFoundError {
+                    name: network_namespace_name,
+                }
+                .fail()
+            }
+            Err(error) => return Err(error),
+        };
+        Self::parse_pids(&output)
+    }
+
+    /// One process id per line, any other token (eg. a warning) is unexpected.
+    fn parse_pids(output: &str) -> Result<Vec<u32>, Error> {
+        output
+            .split_whitespace()
+            .map(|id| id.parse().ok().context(UnexpectedOutputError { output }))
+            .collect()
+    }
+
+    /// Run command in the named network namespace to completion (within the timeout of the
+    /// client), capturing its output. A command exiting unsuccessfully is not an error, its exit
+    /// status is reported instead.
+    ///
+    /// Any namespace the client was created with (via `with_namespace`) is not applied on top.
+    pub async fn exec_capture(
+        &self,
+        network_namespace_name: &str,
+        command_and_args: &[String],
+    ) -> Result<ExecOutput, Error> {
+        // Failing to enter the namespace would be indistinguishable from the command failing.
+        if self.ip_command.dry_run.is_none()
+            && !self
+                .list()
+                .await?
+                .iter()
+                .any(|namespace| namespace.name == network_namespace_name)
+        {
+            return NamespaceNotFoundError {
+                name: network_namespace_name,
+            }
+            .fail();
+        }
+        let mut args: Vec<String> =
+            vec!["netns".into(), "exec".into(), network_namespace_name.into()];
+        args.append(&mut Vec::from(command_and_args));
+        let output = self
+            .ip_command
+            .execute_to_completion(&self.ip_command.command, &args, false, None)
             .await?;
-        Ok(Vec::from_iter(
-            output
-                .split_whitespace()
-                .map(|id| id.parse::<u32>().unwrap()),
-        ))
+        Ok(ExecOutput {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            status: output.status,
+        })
     }
 
     /// Run command in the named network namespace.
+    ///
+    /// Any namespace the client was created with (via `with_namespace`) is not applied on top.
+    #[cfg(feature = "streaming")]
     pub async fn exec(
         &self,
         network_namespace_name: &str,
         command_and_args: &[String],
     ) -> Result<ConsoleStream, Error> {
+        // Failing to enter the namespace would only be reported on the stream, so check first.
+        if self.ip_command.dry_run.is_none()
+            && !self
+                .list()
+                .await?
+                .iter()
+                .any(|namespace| namespace.name == network_namespace_name)
+        {
+            return NamespaceNotFoundError {
+                name: network_namespace_name,
+            }
+            .fail();
+        }
         let mut args: Vec<String> =
             vec!["netns".into(), "exec".into(), network_namespace_name.into()];
         args.append(&mut Vec::from(command_and_args));
         self.ip_command
-            .command_with_streaming_output(&args, false)
+            .command_with_streaming_output(&args, false, false)
             .await
     }
 
     /// Report as network namespace names are added and deleted.
+    ///
+    /// ip(8) buffers this report, so without the stdbuf tool the named network namespaces are
+    /// polled instead.
+    #[cfg(feature = "streaming")]
     pub async fn monitor(&self) -> Result<ConsoleStream, Error> {
+        if self.ip_command.dry_run.is_none() && IpCommand::path("stdbuf").is_none() {
+            return self.poll_monitor().await;
+        }
         self.ip_command
-            .command_with_streaming_output(&["netns".into(), "monitor".into()], false)
+            .command_with_streaming_output(&["netns", "monitor"], false, false)
             .await
     }
 
+    /// Report changes to the named network namespaces, in the same format as `netns monitor`.
+    #[cfg(feature = "streaming")]
+    async fn poll_monitor(&self) -> Result<ConsoleStream, Error> {
+        let names = Self::names(self.list().await?);
+        let lines = stream::unfold(
+            (self.ip_command.clone(), Some(names), VecDeque::new()),
+            |(ip_command, names, mut pending)| async move {
+                let mut names = names?;
+                while pending.is_empty() {
+                    delay_for(MONITOR_POLL_INTERVAL).await;
+                    let current_names = match ip_command.netns().list().await {
+                        Ok(namespaces) => Self::names(namespaces),
+                        Err(error) => {
+                            let error = io::Error::other(error.to_string());
+                            return Some((Err(error), (ip_command, None, pending)));
+                        }
+                    };
+                    pending.extend(
+                        current_names
+                            .difference(&names)
+                            .map(|name| format!("add {}", name)),
+                    );
+                    pending.extend(
+                        names
+                            .difference(&current_names)
+                            .map(|name| format!("delete {}", name)),
+                    );
+                    names = current_names;
+                }
+                let line = ConsoleLine {
+                    source: StreamSource::Stdout,
+                    line: pending.pop_front().unwrap(),
+                };
+                Some((Ok(line), (ip_command, Some(names), pending)))
+            },
+        );
+        Ok(ConsoleStream::from_stream(lines))
+    }
+
+    #[cfg(feature = "streaming")]
+    fn names(namespaces: Vec<Namespace>) -> BTreeSet<String> {
+        namespaces
+            .into_iter()
+            .map(|namespace| namespace.name)
+            .collect()
+    }
+
     /// List network namespace ids.
     pub async fn list_id(
         &self,
@@ -156,9 +324,10 @@ impl<'l> IpNetNamespaceCommand<'l> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "streaming")]
     use futures::channel::oneshot::channel;
+    #[cfg(feature = "streaming")]
     use futures::StreamExt;
-    use tokio::time::delay_for;
 
     #[tokio::test]
     async fn test_add_list_and_delete() {
@@ -200,6 +369,7 @@ mod tests {
         client.netns().delete(test_namespace).await.unwrap();
     }
 
+    #[cfg(feature = "streaming")]
     #[tokio::test]
     async fn test_exec_and_identify() {
         let test_namespace = "ip-command-test-exec-and-identify-namespace";
@@ -219,7 +389,7 @@ mod tests {
                 .await
                 .unwrap();
             if let Some(Ok(next_line)) = console_stream.next().await {
-                pid_sender.send(next_line.parse().unwrap()).unwrap();
+                pid_sender.send(next_line.line.parse().unwrap()).unwrap();
             }
         });
 
@@ -230,6 +400,54 @@ mod tests {
         client.netns().delete(test_namespace).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_exec_capture() {
+        let test_namespace = "ip-command-test-exec-capture-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let links = client
+            .netns()
+            .exec_capture(
+                test_namespace,
+                &["ip".into(), "-oneline".into(), "link".into(), "show".into()],
+            )
+            .await;
+        let failed = client
+            .netns()
+            .exec_capture(
+                test_namespace,
+                &[
+                    "sh".into(),
+                    "-c".into(),
+                    "echo out; echo err >&2; exit 3".into(),
+                ],
+            )
+            .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        let missing = client
+            .netns()
+            .exec_capture(test_namespace, &["true".into()])
+            .await;
+
+        let links = links.unwrap();
+        assert!(links.status.success());
+        // Only loopback exists in a new namespace.
+        assert_eq!(links.stdout.lines().count(), 1);
+        assert!(links.stdout.starts_with("1: lo:"));
+        let failed = failed.unwrap();
+        assert_eq!(failed.status.code(), Some(3));
+        assert_eq!(failed.stdout, "out\n");
+        assert_eq!(failed.stderr, "err\n");
+        assert!(matches!(
+            missing,
+            Err(Error::NamespaceNotFoundError { name }) if name == test_namespace
+        ));
+    }
+
+    #[cfg(feature = "streaming")]
     #[tokio::test]
     async fn test_exec_and_pids() {
         let test_namespace = "ip-command-test-exec-and-pids-namespace";
@@ -249,7 +467,7 @@ mod tests {
                 .await
                 .unwrap();
             if let Some(Ok(next_line)) = console_stream.next().await {
-                pid_sender.send(next_line.parse().unwrap()).unwrap();
+                pid_sender.send(next_line.line.parse().unwrap()).unwrap();
             }
         });
 
@@ -261,6 +479,7 @@ mod tests {
         client.netns().delete(test_namespace).await.unwrap();
     }
 
+    #[cfg(feature = "streaming")]
     #[tokio::test]
     async fn test_monitor() {
         let test_namespace = "ip-command-test-monitor-namespace";
@@ -272,8 +491,8 @@ mod tests {
         tokio::spawn(async move {
             let mut console_stream = monitor_client.netns().monitor().await.unwrap();
             while let Some(Ok(next_line)) = console_stream.next().await {
-                if next_line.contains("ip-command-test-monitor-namespace") {
-                    result_sender.send(next_line).unwrap();
+                if next_line.line.contains("ip-command-test-monitor-namespace") {
+                    result_sender.send(next_line.line).unwrap();
                     break;
                 }
             }
@@ -290,4 +509,89 @@ mod tests {
 
         client.netns().delete(test_namespace).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_namespace_not_found() {
+        let test_namespace = "ip-command-test-missing-namespace";
+        let client = IpCommand::new().unwrap();
+
+        let is_not_found = |result: Result<_, Error>| matches!(result, Err(Error::NamespaceNotFoundError { name }) if name == test_namespace);
+        assert!(is_not_found(
+            client.netns().pids(test_namespace).await.map(|_| ())
+        ));
+        assert!(is_not_found(
+            client
+                .with_namespace(test_namespace)
+                .link()
+                .show(None)
+                .await
+                .map(|_| ())
+        ));
+        #[cfg(feature = "streaming")]
+        assert!(is_not_found(
+            client
+                .netns()
+                .exec(test_namespace, &["true".into()])
+                .await
+                .map(|_| ())
+        ));
+        assert!(matches!(
+            client.netns().identify(u32::MAX).await,
+            Err(Error::NotFoundError {})
+        ));
+    }
+
+    #[test]
+    fn test_parse_pids() {
+        assert_eq!(
+            IpNetNamespaceCommand::parse_pids("1\n2\n\n").unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            IpNetNamespaceCommand::parse_pids("").unwrap(),
+            Vec::<u32>::new()
+        );
+        assert!(matches!(
+            IpNetNamespaceCommand::parse_pids("1\nWarning: stray output\n"),
+            Err(Error::UnexpectedOutputError { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_attach() {
+        let test_namespace = "ip-command-test-attach-namespace";
+
+        let mut child = tokio::process::Command::new("unshare")
+            .args(["--net", "sleep", "10"])
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        // Wait for the child to enter its own network namespace.
+        let namespace_of = |pid: &str| std::fs::read_link(format!("/proc/{}/ns/net", pid)).ok();
+        while namespace_of(&pid.to_string()) == namespace_of("self") {
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+        }
+
+        let client = IpCommand::new().unwrap();
+        client.netns().attach(test_namespace, pid).await.unwrap();
+        let namespaces = client.netns().list().await.unwrap();
+        let pids = client.netns().pids(test_namespace).await.unwrap();
+        client.netns().delete(test_namespace).await.unwrap();
+        child.kill().unwrap();
+
+        assert!(namespaces
+            .iter()
+            .any(|namespace| namespace.name == test_namespace));
+        assert_eq!(pids, vec![pid]);
+    }
+
+    #[test]
+    fn test_namespace_serialization() {
+        let output = r#"[{"name":"first","id":7},{"name":"second"}]"#;
+        let namespaces: Vec<Namespace> = serde_json::from_str(output).unwrap();
+        assert!(namespaces[0].is_id_assigned());
+        assert!(!namespaces[1].is_id_assigned());
+        assert_eq!(serde_json::to_string(&namespaces).unwrap(), output);
+    }
 }
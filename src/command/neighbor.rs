@@ -14,7 +14,171 @@
  * limitations under the License.
  */
 
-use crate::{Error, IpCommand};
+use crate::*;
+use serde::de::{self, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use serde_command_opts::{BooleanType, Serializer};
+use snafu::ResultExt;
+use std::fmt;
+use std::str::FromStr;
+
+/// Add, change, replace or delete neighbour entry configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NeighborConfiguration {
+    /// The protocol address of the neighbour (or the address to answer for, of a proxy entry).
+    pub to: String,
+    /// The link layer address of the neighbour.
+    #[serde(rename = "lladdr")]
+    pub link_layer_address: Option<String>,
+    /// The device the neighbour is attached to.
+    #[serde(rename = "dev")]
+    pub device: String,
+    /// The state of the entry (eg. `NeighborState::Permanent`).
+    #[serde(rename = "nud")]
+    pub state: Option<NeighborState>,
+    /// Answer (ARP or neighbour discovery) requests for the address on behalf of another host.
+    #[serde(skip)]
+    pub proxy: bool,
+}
+
+/// List neighbour entries configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NeighborShowConfiguration {
+    /// Only list neighbours matching this prefix.
+    pub to: Option<String>,
+    /// Only list neighbours attached to this device.
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+    /// Only list neighbours in this state (see `NeighborState`), or "all".
+    #[serde(rename = "nud")]
+    pub state: Option<String>,
+    /// Also report the timers (and probes) of each entry.
+    #[serde(skip)]
+    pub statistics: bool,
+    /// List proxy entries instead.
+    #[serde(skip)]
+    pub proxy: bool,
+}
+
+/// The neighbour unreachability detection (NUD) state of an entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NeighborState {
+    /// Configured, never verified nor expired.
+    Permanent,
+    /// Confirmed reachable recently.
+    Reachable,
+    /// Valid, but no longer confirmed reachable.
+    Stale,
+    /// Used while stale, waiting for confirmation before probing.
+    Delay,
+    /// Being probed for reachability.
+    Probe,
+    /// Probing found the neighbour unreachable.
+    Failed,
+    /// Being resolved, no link layer address yet.
+    Incomplete,
+    /// Valid without resolution (eg. on a device without ARP).
+    NoArp,
+    /// Not in any state (eg. just created).
+    None,
+}
+
+impl fmt::Display for NeighborState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Permanent => "permanent",
+            Self::Reachable => "reachable",
+            Self::Stale => "stale",
+            Self::Delay => "delay",
+            Self::Probe => "probe",
+            Self::Failed => "failed",
+            Self::Incomplete => "incomplete",
+            Self::NoArp => "noarp",
+            Self::None => "none",
+        })
+    }
+}
+
+/// Reported in upper case (eg. "REACHABLE"), given in lower case.
+impl FromStr for NeighborState {
+    type Err = Error;
+
+    fn from_str(state: &str) -> Result<Self, Self::Err> {
+        Ok(match state.to_ascii_lowercase().as_str() {
+            "permanent" => Self::Permanent,
+            "reachable" => Self::Reachable,
+            "stale" => Self::Stale,
+            "delay" => Self::Delay,
+            "probe" => Self::Probe,
+            "failed" => Self::Failed,
+            "incomplete" => Self::Incomplete,
+            "noarp" => Self::NoArp,
+            "none" => Self::None,
+            _ => {
+                return Err(Error::InvalidArgumentError {
+                    field: "state".into(),
+                    value: state.into(),
+                })
+            }
+        })
+    }
+}
+
+impl Serialize for NeighborState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for NeighborState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// The returned neighbour structure.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Neighbor {
+    #[serde(rename = "dst")]
+    pub destination: String,
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+    #[serde(rename = "lladdr")]
+    pub link_layer_address: Option<String>,
+    /// The states of the entry, usually just one.
+    #[serde(default)]
+    pub state: Vec<NeighborState>,
+    /// Seconds since the entry was last used (statistics only).
+    pub used: Option<u32>,
+    /// Seconds since the entry was last confirmed reachable (statistics only).
+    pub confirmed: Option<u32>,
+    /// Seconds since the entry was last updated (statistics only).
+    pub updated: Option<u32>,
+    /// Number of unanswered probes (statistics only).
+    pub probes: Option<u32>,
+    /// Is this a proxy entry?
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub proxy: bool,
+    /// Is the neighbour an IPv6 router (as advertised by neighbour discovery)?
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub router: bool,
+}
+
+/// Flags are reported as a null valued key, present only when the flag is set.
+pub(crate) fn deserialize_flag<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    IgnoredAny::deserialize(deserializer).map(|_| true)
+}
 
 #[derive(Clone)]
 pub struct IpNeighborCommand<'l> {
@@ -27,28 +191,95 @@ impl<'l> IpNeighborCommand<'l> {
     }
 
     /// Add a new neighbour entry.
-    pub async fn add(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn add(&self, configuration: NeighborConfiguration) -> Result<(), Error> {
+        self.modify("add", &configuration).await
     }
 
-    /// Delete a neighbour entry,
-    pub async fn delete(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Delete a neighbour entry.
+    pub async fn delete(&self, configuration: NeighborConfiguration) -> Result<(), Error> {
+        self.modify("del", &configuration).await
     }
 
     /// Change an existing entry.
-    pub async fn change(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn change(&self, configuration: NeighborConfiguration) -> Result<(), Error> {
+        self.modify("change", &configuration).await
     }
 
     /// Add a new entry or change an existing one.
-    pub async fn replace(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn replace(&self, configuration: NeighborConfiguration) -> Result<(), Error> {
+        self.modify("replace", &configuration).await
+    }
+
+    /// Pin the link layer address of a neighbour (eg. a gateway) with a permanent entry,
+    /// replacing any existing entry. Calling it again re-adds the entry if it was flushed.
+    pub async fn ensure_permanent(
+        &self,
+        to: &str,
+        device: &str,
+        link_layer_address: &str,
+    ) -> Result<(), Error> {
+        self.replace(NeighborConfiguration {
+            to: to.into(),
+            link_layer_address: Some(link_layer_address.into()),
+            device: device.into(),
+            state: Some(NeighborState::Permanent),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn modify(
+        &self,
+        command: &str,
+        configuration: &NeighborConfiguration,
+    ) -> Result<(), Error> {
+        let args = Self::modify_args(command, configuration)?;
+        self.ip_command.command_without_output(&args, None).await
+    }
+
+    /// Arguments of the add, delete, change and replace commands.
+    fn modify_args(
+        command: &str,
+        configuration: &NeighborConfiguration,
+    ) -> Result<Vec<String>, Error> {
+        let mut args: Vec<String> = vec!["neigh".into(), command.into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        // A proxy entry names its address with "proxy" instead of "to" (the first argument).
+        if configuration.proxy {
+            args[2] = "proxy".into();
+        }
+        Ok(args)
     }
 
     /// List neighbour entries.
-    pub async fn show(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn show(
+        &self,
+        configuration: Option<NeighborShowConfiguration>,
+    ) -> Result<Vec<Neighbor>, Error> {
+        let mut args: Vec<String> = vec![];
+        if let Some(NeighborShowConfiguration {
+            statistics: true, ..
+        }) = configuration
+        {
+            args.push("-statistics".into());
+        }
+        args.append(&mut vec!["neigh".into(), "show".into()]);
+        if let Some(configuration) = configuration {
+            if configuration.proxy {
+                args.push("proxy".into());
+            }
+            args.append(
+                &mut Serializer::new(BooleanType::OnOff)
+                    .into_args(&configuration)
+                    .context(CommandOptionsSerializationError {})?,
+            );
+        }
+        let output = self.ip_command.command(&args, true, false, None).await?;
+        serde_json::from_str(&output).context(JsonDeserializationError {})
     }
 
     /// Flush neighbour entries.
@@ -56,3 +287,239 @@ impl<'l> IpNeighborCommand<'l> {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::link::*;
+
+    const NEIGHBOR_SHOW_OUTPUT: &str = r#"[{"dst":"192.0.2.1","dev":"eth0","lladdr":"02:fc:00:00:00:05","used":726,"confirmed":726,"updated":692,"probes":1,"state":["STALE"]}]"#;
+
+    #[tokio::test]
+    async fn test_show_statistics() {
+        let client = IpCommand::new()
+            .unwrap()
+            .dry_run_with_output(NEIGHBOR_SHOW_OUTPUT);
+        let neighbors = client
+            .neighbor()
+            .show(Some(NeighborShowConfiguration {
+                device: Some("eth0".into()),
+                statistics: true,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.recorded_commands(),
+            vec![vec!["-json", "-statistics", "neigh", "show", "dev", "eth0"]]
+        );
+        assert_eq!(neighbors[0].destination, "192.0.2.1");
+        assert_eq!(neighbors[0].state, vec![NeighborState::Stale]);
+        assert!(!neighbors[0].router);
+        assert_eq!(neighbors[0].used, Some(726));
+        assert_eq!(neighbors[0].confirmed, Some(726));
+        assert_eq!(neighbors[0].updated, Some(692));
+        assert_eq!(neighbors[0].probes, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_ensure_permanent() {
+        let link_name = "test_neigh1";
+        let link_layer_address = "02:00:00:00:00:03";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let mut listed = vec![];
+        for _ in 0..2 {
+            client
+                .neighbor()
+                .ensure_permanent("192.0.2.7", link_name, link_layer_address)
+                .await
+                .unwrap();
+            listed.push(
+                client
+                    .neighbor()
+                    .show(Some(NeighborShowConfiguration {
+                        device: Some(link_name.into()),
+                        ..Default::default()
+                    }))
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        for neighbors in &listed {
+            assert_eq!(neighbors.len(), 1);
+            assert_eq!(neighbors[0].destination, "192.0.2.7");
+            assert_eq!(
+                neighbors[0].link_layer_address.as_deref(),
+                Some(link_layer_address)
+            );
+            assert_eq!(neighbors[0].state, vec![NeighborState::Permanent]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proxy_and_replace() {
+        let link_name = "test_neigh0";
+        let proxy = NeighborConfiguration {
+            to: "192.0.2.5".into(),
+            device: link_name.into(),
+            proxy: true,
+            ..Default::default()
+        };
+        let neighbor = NeighborConfiguration {
+            to: "192.0.2.6".into(),
+            link_layer_address: Some("02:00:00:00:00:01".into()),
+            device: link_name.into(),
+            state: Some(NeighborState::Permanent),
+            ..Default::default()
+        };
+
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client.neighbor().add(proxy.clone()).await.unwrap();
+        client.neighbor().replace(neighbor.clone()).await.unwrap();
+        client
+            .neighbor()
+            .change(NeighborConfiguration {
+                link_layer_address: Some("02:00:00:00:00:02".into()),
+                ..neighbor
+            })
+            .await
+            .unwrap();
+
+        let mut listed = vec![];
+        for proxy in &[true, false] {
+            listed.push(
+                client
+                    .neighbor()
+                    .show(Some(NeighborShowConfiguration {
+                        device: Some(link_name.into()),
+                        proxy: *proxy,
+                        ..Default::default()
+                    }))
+                    .await
+                    .unwrap(),
+            );
+        }
+        let (proxies, neighbors) = (&listed[0], &listed[1]);
+
+        client.neighbor().delete(proxy).await.unwrap();
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0].destination, "192.0.2.5");
+        assert!(proxies[0].proxy);
+        assert_eq!(neighbors.len(), 1);
+        assert_eq!(neighbors[0].destination, "192.0.2.6");
+        assert_eq!(
+            neighbors[0].link_layer_address.as_deref(),
+            Some("02:00:00:00:00:02")
+        );
+        assert!(!neighbors[0].proxy);
+    }
+
+    #[test]
+    fn test_neighbor_state() {
+        assert_eq!(
+            "NOARP".parse::<NeighborState>().unwrap(),
+            NeighborState::NoArp
+        );
+        assert_eq!(
+            "incomplete".parse::<NeighborState>().unwrap(),
+            NeighborState::Incomplete
+        );
+        assert_eq!(NeighborState::Permanent.to_string(), "permanent");
+        assert!("UNKNOWN".parse::<NeighborState>().is_err());
+
+        let neighbors: Vec<Neighbor> = serde_json::from_str(
+            r#"[{"dst":"2001:db8::1","dev":"eth0","lladdr":"02:00:00:00:00:01","router":null,"state":["REACHABLE"]}]"#,
+        )
+        .unwrap();
+        assert!(neighbors[0].router);
+        assert_eq!(neighbors[0].state, vec![NeighborState::Reachable]);
+    }
+
+    #[tokio::test]
+    async fn test_show_permanent_state() {
+        let test_namespace = "ip-command-test-neighbor-state-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let result = async {
+            namespace_client
+                .link()
+                .add(LinkAddConfiguration {
+                    name: "veth0".into(),
+                    link_type: LinkKind::Veth(VethConfiguration {
+                        peer_name: Some("veth1".into()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .await?;
+            namespace_client
+                .neighbor()
+                .add(NeighborConfiguration {
+                    to: "192.0.2.8".into(),
+                    link_layer_address: Some("02:00:00:00:00:04".into()),
+                    device: "veth0".into(),
+                    state: Some(NeighborState::Permanent),
+                    ..Default::default()
+                })
+                .await?;
+            namespace_client
+                .neighbor()
+                .show(Some(NeighborShowConfiguration {
+                    device: Some("veth0".into()),
+                    ..Default::default()
+                }))
+                .await
+        }
+        .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        let neighbors = result.unwrap();
+        assert_eq!(neighbors.len(), 1);
+        assert_eq!(neighbors[0].state, vec![NeighborState::Permanent]);
+        assert!(!neighbors[0].router);
+    }
+}
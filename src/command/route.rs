@@ -14,7 +14,677 @@
  * limitations under the License.
  */
 
-use crate::{Error, IpCommand};
+use crate::command::address::Scope;
+use crate::*;
+#[cfg(feature = "streaming")]
+use futures::{future, StreamExt};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Serialize};
+use serde_command_opts::{BooleanType, Serializer};
+use snafu::{OptionExt, ResultExt};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::Ipv6Addr;
+use std::time::Instant;
+
+/// Configuration files naming routing tables, by precedence.
+const ROUTING_TABLE_FILES: &[&str] = &["/etc/iproute2/rt_tables", "/usr/share/iproute2/rt_tables"];
+
+/// A routing table, by number or by name (eg. from `/etc/iproute2/rt_tables`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum RoutingTable {
+    /// The table of normal routes (254).
+    Main,
+    /// The table of local and broadcast addresses, maintained by the kernel (255).
+    Local,
+    /// The table looked up after all others by default (253).
+    Default,
+    /// Every table (only when listing routes).
+    All,
+    /// Any other table, by number.
+    Id(u32),
+    /// Any other table, by the name configured for its number.
+    Named(String),
+}
+
+impl RoutingTable {
+    /// The number of the table, names are resolved from the iproute2 configuration.
+    pub fn id(&self) -> Option<u32> {
+        match self {
+            Self::Main => Some(254),
+            Self::Local => Some(255),
+            Self::Default => Some(253),
+            Self::All => None,
+            Self::Id(id) => Some(*id),
+            Self::Named(name) => ROUTING_TABLE_FILES
+                .iter()
+                .filter_map(|path| std::fs::read_to_string(path).ok())
+                .find_map(|tables| Self::find_id(&tables, name)),
+        }
+    }
+
+    /// The number of the named table in the `rt_tables` formatted configuration.
+    fn find_id(tables: &str, name: &str) -> Option<u32> {
+        tables
+            .lines()
+            .map(|line| line.split('#').next().unwrap().split_whitespace())
+            .find_map(|mut fields| match (fields.next(), fields.next()) {
+                (Some(id), Some(table)) if table == name => id.parse().ok(),
+                _ => None,
+            })
+    }
+}
+
+impl From<&str> for RoutingTable {
+    fn from(table: &str) -> Self {
+        match table {
+            "main" | "254" => Self::Main,
+            "local" | "255" => Self::Local,
+            "default" | "253" => Self::Default,
+            "all" | "0" => Self::All,
+            table => table
+                .parse()
+                .map(Self::Id)
+                .unwrap_or_else(|_| Self::Named(table.into())),
+        }
+    }
+}
+
+impl From<u32> for RoutingTable {
+    fn from(table: u32) -> Self {
+        Self::from(table.to_string().as_str())
+    }
+}
+
+impl fmt::Display for RoutingTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Main => f.write_str("main"),
+            Self::Local => f.write_str("local"),
+            Self::Default => f.write_str("default"),
+            Self::All => f.write_str("all"),
+            Self::Id(id) => write!(f, "{}", id),
+            Self::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+impl Serialize for RoutingTable {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Reported by name when configured (eg. "local"), otherwise by number (as a string).
+impl<'de> Deserialize<'de> for RoutingTable {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::Number(id) => id
+                .as_u64()
+                .map(|id| Self::from(id as u32))
+                .ok_or_else(|| serde::de::Error::custom("invalid table number"))?,
+            serde_json::Value::String(table) => Self::from(table.as_str()),
+            _ => return Err(serde::de::Error::custom("expected a table name or number")),
+        })
+    }
+}
+
+/// The routing protocol which installed a route, by name or by number (eg. from
+/// `/etc/iproute2/rt_protos`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum RouteProtocol {
+    /// Installed by the kernel (eg. the prefix route of an address) (2).
+    Kernel,
+    /// Installed during boot, the default of added routes (3).
+    Boot,
+    /// Installed by the administrator (4).
+    Static,
+    /// Installed from an IPv6 router advertisement (9).
+    Ra,
+    /// Installed by a DHCP client (16).
+    Dhcp,
+    /// Installed by a BGP daemon (186).
+    Bgp,
+    /// Installed by an OSPF daemon (188).
+    Ospf,
+    /// Any other protocol, by number.
+    Numeric(u8),
+    /// Any other protocol, by the name configured for its number.
+    Named(String),
+}
+
+impl From<&str> for RouteProtocol {
+    fn from(protocol: &str) -> Self {
+        match protocol {
+            "kernel" | "2" => Self::Kernel,
+            "boot" | "3" => Self::Boot,
+            "static" | "4" => Self::Static,
+            "ra" | "9" => Self::Ra,
+            "dhcp" | "16" => Self::Dhcp,
+            "bgp" | "186" => Self::Bgp,
+            "ospf" | "188" => Self::Ospf,
+            protocol => protocol
+                .parse()
+                .map(Self::Numeric)
+                .unwrap_or_else(|_| Self::Named(protocol.into())),
+        }
+    }
+}
+
+impl From<u8> for RouteProtocol {
+    fn from(protocol: u8) -> Self {
+        Self::from(protocol.to_string().as_str())
+    }
+}
+
+impl fmt::Display for RouteProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Kernel => f.write_str("kernel"),
+            Self::Boot => f.write_str("boot"),
+            Self::Static => f.write_str("static"),
+            Self::Ra => f.write_str("ra"),
+            Self::Dhcp => f.write_str("dhcp"),
+            Self::Bgp => f.write_str("bgp"),
+            Self::Ospf => f.write_str("ospf"),
+            Self::Numeric(protocol) => write!(f, "{}", protocol),
+            Self::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+impl Serialize for RouteProtocol {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Reported by name when configured (eg. "static"), otherwise by number.
+impl<'de> Deserialize<'de> for RouteProtocol {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::Number(protocol) => protocol
+                .as_u64()
+                .filter(|protocol| *protocol <= u8::MAX as u64)
+                .map(|protocol| Self::from(protocol as u8))
+                .ok_or_else(|| serde::de::Error::custom("invalid protocol number"))?,
+            serde_json::Value::String(protocol) => Self::from(protocol.as_str()),
+            _ => {
+                return Err(serde::de::Error::custom(
+                    "expected a protocol name or number",
+                ))
+            }
+        })
+    }
+}
+
+/// Type of a route.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteType {
+    /// The route entry describes real paths to the destinations covered by the route prefix.
+    Unicast,
+    /// The destinations are assigned to this host.
+    Local,
+    /// The destinations are broadcast addresses.
+    Broadcast,
+    /// The destinations are anycast addresses assigned to this host.
+    Anycast,
+    /// A special type used for multicast routing.
+    Multicast,
+    /// These destinations are unreachable, packets are discarded silently.
+    Blackhole,
+    /// These destinations are unreachable, packets are discarded and the ICMP message host
+    /// unreachable is generated.
+    Unreachable,
+    /// These destinations are unreachable, packets are discarded and the ICMP message
+    /// communication administratively prohibited is generated.
+    Prohibit,
+    /// The lookup continues as if no route was found in this table (with policy rules).
+    Throw,
+    /// A special network address translation route.
+    Nat,
+    /// The destinations are resolved externally.
+    #[serde(rename = "xresolve")]
+    ExternalResolve,
+}
+
+impl fmt::Display for RouteType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Unicast => "unicast",
+            Self::Local => "local",
+            Self::Broadcast => "broadcast",
+            Self::Anycast => "anycast",
+            Self::Multicast => "multicast",
+            Self::Blackhole => "blackhole",
+            Self::Unreachable => "unreachable",
+            Self::Prohibit => "prohibit",
+            Self::Throw => "throw",
+            Self::Nat => "nat",
+            Self::ExternalResolve => "xresolve",
+        })
+    }
+}
+
+/// How a seg6 route applies its segments.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Seg6Mode {
+    /// Encapsulate the packet in an outer IPv6 header carrying the segments.
+    #[default]
+    Encap,
+    /// Insert the segments into the existing IPv6 header.
+    Inline,
+    /// Encapsulate the ethernet frame in an outer IPv6 header carrying the segments.
+    L2Encap,
+}
+
+impl fmt::Display for Seg6Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Encap => "encap",
+            Self::Inline => "inline",
+            Self::L2Encap => "l2encap",
+        })
+    }
+}
+
+/// Lightweight tunnel encapsulation of the packets matching a route.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RouteEncap {
+    /// IPv6 segment routing (SRv6), the packets are steered through the segments in order.
+    Seg6 {
+        mode: Seg6Mode,
+        segments: Vec<String>,
+    },
+    /// IPv6 segment routing endpoint behaviour of packets to the route destination.
+    Seg6Local {
+        /// The action (eg. "End", "End.DX6").
+        action: String,
+        /// The next hop of cross-connect actions (an IPv4 or IPv6 address).
+        next_hop: Option<String>,
+        /// The table looked up by table actions (eg. "End.DT6").
+        table: Option<String>,
+    },
+}
+
+impl Serialize for RouteEncap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut seq = serializer.serialize_seq(None)?;
+        seq.serialize_element("encap")?;
+        match self {
+            Self::Seg6 { mode, segments } => {
+                seq.serialize_element("seg6")?;
+                seq.serialize_element("mode")?;
+                seq.serialize_element(&mode.to_string())?;
+                seq.serialize_element("segs")?;
+                seq.serialize_element(&segments.join(","))?;
+            }
+            Self::Seg6Local {
+                action,
+                next_hop,
+                table,
+            } => {
+                seq.serialize_element("seg6local")?;
+                seq.serialize_element("action")?;
+                seq.serialize_element(action)?;
+                if let Some(next_hop) = next_hop {
+                    seq.serialize_element(if next_hop.contains(':') { "nh6" } else { "nh4" })?;
+                    seq.serialize_element(next_hop)?;
+                }
+                if let Some(table) = table {
+                    seq.serialize_element("table")?;
+                    seq.serialize_element(table)?;
+                }
+            }
+        }
+        seq.end()
+    }
+}
+
+/// Add/delete route configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RouteAddConfiguration {
+    /// The destination prefix of the route ("default" for the default route).
+    #[serde(rename = "to")]
+    pub destination: String,
+    /// Only route packets with this type of service (eg. "0x10", or a name from rt_dsfield).
+    #[serde(rename = "tos")]
+    pub type_of_service: Option<String>,
+    /// The type of the route (unicast by default).
+    #[serde(skip)]
+    pub route_type: Option<RouteType>,
+    /// Encapsulate the packets matching the route.
+    pub encapsulation: Option<RouteEncap>,
+    /// The address of the nexthop router.
+    #[serde(rename = "via")]
+    pub gateway: Option<String>,
+    /// The output device name.
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+    /// The source address to prefer when sending to the destination.
+    #[serde(rename = "src")]
+    pub preferred_source: Option<String>,
+    /// The preference value of the route.
+    pub metric: Option<u32>,
+    /// The scope of the destinations covered by the route prefix.
+    pub scope: Option<String>,
+    /// The routing protocol identifier of the route (`RouteProtocol::Boot` by default).
+    #[serde(rename = "proto")]
+    pub protocol: Option<RouteProtocol>,
+    /// The realm (or "from/to" realms) to which the route is assigned.
+    pub realm: Option<String>,
+    /// The table to add this route to (the main table by default).
+    pub table: Option<RoutingTable>,
+    /// Add this route to the table of the virtual routing and forwarding device.
+    pub vrf: Option<String>,
+    /// Per-route metrics (eg. mtu).
+    #[serde(flatten)]
+    pub metrics: Option<RouteMetrics>,
+}
+
+/// Per-route metrics, only the present metrics are set.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteMetrics {
+    /// The maximum transmission unit along the path to the destination.
+    pub mtu: Option<u32>,
+    /// The maximal segment size to advertise to these destinations when establishing TCP
+    /// connections.
+    #[serde(rename = "advmss")]
+    pub advertised_mss: Option<u32>,
+    /// The maximal window for TCP to advertise to these destinations.
+    pub window: Option<u32>,
+    /// The initial round trip time estimate.
+    pub rtt: Option<u32>,
+    /// The initial round trip time variance estimate.
+    #[serde(rename = "rttvar")]
+    pub rtt_variance: Option<u32>,
+    /// The clamp for the congestion window.
+    #[serde(rename = "cwnd")]
+    pub congestion_window: Option<u32>,
+    /// The initial congestion window size for connections to this destination.
+    #[serde(rename = "initcwnd")]
+    pub initial_congestion_window: Option<u32>,
+    /// The initial receive window size for connections to this destination.
+    #[serde(rename = "initrwnd")]
+    pub initial_receive_window: Option<u32>,
+    /// The hop limit to use for this destination.
+    #[serde(rename = "hoplimit")]
+    pub hop_limit: Option<u32>,
+}
+
+pub type RouteDeleteConfiguration = RouteAddConfiguration;
+pub type RouteReplaceConfiguration = RouteAddConfiguration;
+pub type RouteChangeConfiguration = RouteAddConfiguration;
+
+/// List routes configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RouteShowConfiguration {
+    /// Only list routes matching this prefix.
+    pub to: Option<String>,
+    /// Only list routes using this device.
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+    /// Only list routes from this table (the main table by default).
+    pub table: Option<RoutingTable>,
+    /// Only list routes from the table of the virtual routing and forwarding device.
+    pub vrf: Option<String>,
+    /// Only list routes installed by this protocol, ip(8) then leaves it out of each route.
+    #[serde(rename = "proto")]
+    pub protocol: Option<RouteProtocol>,
+}
+
+/// Get a single route configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RouteGetConfiguration {
+    /// The destination address.
+    pub to: String,
+    /// The source address.
+    pub from: Option<String>,
+    /// The device from which this packet is expected to arrive.
+    #[serde(rename = "iif")]
+    pub input_device: Option<String>,
+    /// Force the output device on which this packet will be routed.
+    #[serde(rename = "oif")]
+    pub output_device: Option<String>,
+    /// The firewall mark (fwmark).
+    pub mark: Option<u32>,
+    /// The type of service of the packet.
+    #[serde(rename = "tos")]
+    pub type_of_service: Option<String>,
+}
+
+impl RouteGetConfiguration {
+    /// Link local IPv6 addresses are only unique per link, so the destination must name the link,
+    /// either as the output device or as the zone of the address (eg. "fe80::1%eth0").
+    fn scoped(mut self) -> Result<Self, Error> {
+        if let Some(index) = self.to.find('%') {
+            let zone = self.to.split_off(index)[1..].to_string();
+            if self.output_device.is_none() {
+                self.output_device = Some(zone);
+            }
+        }
+        let link_local = match self.to.parse::<Ipv6Addr>() {
+            Ok(address) => address.segments()[0] & 0xffc0 == 0xfe80,
+            Err(_) => false,
+        };
+        if link_local && self.output_device.is_none() {
+            return Err(Error::InvalidArgumentError {
+                field: "output_device".into(),
+                value: self.to,
+            });
+        }
+        Ok(self)
+    }
+}
+
+/// The returned route structure.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Route {
+    /// The type of the route, only reported when other than unicast.
+    #[serde(rename = "type")]
+    pub route_type: Option<RouteType>,
+    #[serde(rename = "dst")]
+    pub destination: String,
+    /// The type of service the route is restricted to.
+    #[serde(rename = "tos")]
+    pub type_of_service: Option<String>,
+    pub gateway: Option<String>,
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+    pub protocol: Option<RouteProtocol>,
+    pub scope: Option<Scope>,
+    #[serde(rename = "prefsrc")]
+    pub preferred_source: Option<String>,
+    pub metric: Option<u32>,
+    pub table: Option<RoutingTable>,
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// The next hops of a multipath route, none for a single path route.
+    #[serde(rename = "nexthops")]
+    pub next_hops: Option<Vec<RouteNextHop>>,
+    /// Routing cache metadata of a cached result of `get` (eg. a redirected destination),
+    /// none for the empty metadata `get` reports for any other IPv4 destination.
+    #[serde(flatten, deserialize_with = "deserialize_cache_info")]
+    pub cache: Option<CacheInfo>,
+    /// Per-route metrics (eg. mtu).
+    #[serde(default, deserialize_with = "deserialize_metrics")]
+    pub metrics: Option<RouteMetrics>,
+    /// The realms to which the route is assigned.
+    #[serde(rename = "flow")]
+    pub realms: Option<RouteRealms>,
+    /// Lightweight tunnel encapsulation, only reported for encapsulating routes.
+    #[serde(flatten)]
+    pub encapsulation: Option<RouteEncapInfo>,
+}
+
+/// The returned next hop of a multipath route.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouteNextHop {
+    pub gateway: Option<String>,
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+    /// Relative share of the traffic routed via this next hop.
+    pub weight: Option<u32>,
+    /// Flags of the next hop (eg. "onlink", "linkdown").
+    #[serde(default)]
+    pub flags: Vec<String>,
+}
+
+/// The returned lightweight tunnel encapsulation of a route.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouteEncapInfo {
+    /// The type of encapsulation (eg. "seg6", "seg6local").
+    #[serde(rename = "encap")]
+    pub kind: String,
+    /// The seg6 mode (eg. "encap", "inline").
+    pub mode: Option<String>,
+    /// The seg6 segments.
+    #[serde(rename = "segs")]
+    pub segments: Option<Vec<String>>,
+    /// The seg6local action.
+    pub action: Option<String>,
+    /// The next hop of seg6local cross-connect actions.
+    #[serde(rename = "nh6", alias = "nh4")]
+    pub next_hop: Option<String>,
+}
+
+/// Routing cache metadata of a route (eg. learned path MTU or redirects).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CacheInfo {
+    /// The cache flags (eg. "redirected").
+    #[serde(rename = "cache")]
+    pub flags: Vec<String>,
+    /// Seconds until the cached route expires.
+    pub expires: Option<i32>,
+    /// The last error of the cached route.
+    pub error: Option<u32>,
+    /// Number of references to the cached route (statistics only).
+    pub users: Option<u32>,
+    /// Number of times the cached route was used (statistics only).
+    pub used: Option<u32>,
+    /// Seconds since the cached route was last used (statistics only).
+    pub age: Option<u32>,
+}
+
+/// The realms to which a route is assigned.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouteRealms {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Metrics are reported as an array holding a single object.
+fn deserialize_metrics<'de, D>(deserializer: D) -> Result<Option<RouteMetrics>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Vec::<RouteMetrics>::deserialize(deserializer)?.pop())
+}
+
+/// Empty cache metadata (no flags, nor any other attribute) is none.
+fn deserialize_cache_info<'de, D>(deserializer: D) -> Result<Option<CacheInfo>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(
+        Option::<CacheInfo>::deserialize(deserializer)?.filter(|cache| {
+            !cache.flags.is_empty()
+                || cache.expires.is_some()
+                || cache.error.is_some()
+                || cache.users.is_some()
+                || cache.used.is_some()
+                || cache.age.is_some()
+        }),
+    )
+}
+
+/// A least recently used cache of resolved routes.
+pub(crate) struct RouteCache {
+    capacity: usize,
+    time_to_live: Duration,
+    entries: HashMap<Vec<String>, (Instant, Route)>,
+    recently_used: VecDeque<Vec<String>>,
+}
+
+impl RouteCache {
+    pub(crate) fn new(capacity: usize, time_to_live: Duration) -> Self {
+        Self {
+            capacity,
+            time_to_live,
+            entries: HashMap::new(),
+            recently_used: VecDeque::new(),
+        }
+    }
+
+    fn get(&mut self, key: &[String]) -> Option<Route> {
+        let (expires_at, route) = self.entries.get(key)?.clone();
+        if expires_at <= Instant::now() {
+            self.remove(key);
+            return None;
+        }
+        self.touch(key);
+        Some(route)
+    }
+
+    fn insert(&mut self, key: Vec<String>, route: Route) {
+        // Routes from the routing cache may expire sooner than our own time to live.
+        let time_to_live = route
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.expires)
+            .map(|expires| Duration::from_secs(expires.max(0) as u64).min(self.time_to_live))
+            .unwrap_or(self.time_to_live);
+        if self.capacity == 0 || time_to_live == Duration::from_secs(0) {
+            return;
+        }
+        self.remove(&key);
+        while self.entries.len() >= self.capacity {
+            match self.recently_used.pop_back() {
+                Some(evicted) => {
+                    self.entries.remove(&evicted);
+                }
+                None => break,
+            }
+        }
+        self.recently_used.push_front(key.clone());
+        self.entries
+            .insert(key, (Instant::now() + time_to_live, route));
+    }
+
+    fn touch(&mut self, key: &[String]) {
+        if let Some(position) = self.recently_used.iter().position(|k| k[..] == *key) {
+            if let Some(key) = self.recently_used.remove(position) {
+                self.recently_used.push_front(key);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &[String]) {
+        self.entries.remove(key);
+        self.recently_used.retain(|k| k[..] != *key);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.recently_used.clear();
+    }
+}
 
 #[derive(Clone)]
 pub struct IpRouteCommand<'l> {
@@ -27,8 +697,55 @@ impl<'l> IpRouteCommand<'l> {
     }
 
     /// List routes.
-    pub async fn list(&self) -> Result<(), Error> {
-        unimplemented!()
+    ///
+    /// A table without any routes (ie. which the kernel has not created) lists no routes.
+    pub async fn list(
+        &self,
+        configuration: Option<RouteShowConfiguration>,
+    ) -> Result<Vec<Route>, Error> {
+        let mut args: Vec<String> = vec!["route".into(), "show".into()];
+        if let Some(configuration) = configuration {
+            args.append(
+                &mut Serializer::new(BooleanType::OnOff)
+                    .into_args(&configuration)
+                    .context(CommandOptionsSerializationError {})?,
+            );
+        }
+        let output = match self.ip_command.command(&args, true, false, None).await {
+            Ok(output) => output,
+            Err(Error::CommandFailedError { stderr, .. })
+                if stderr.contains("FIB table does not exist") =>
+            {
+                return Ok(vec![])
+            }
+            Err(error) => return Err(error),
+        };
+        serde_json::from_str(&output).context(JsonDeserializationError {})
+    }
+
+    /// List routes, each deserialized as it is read rather than after all of the output has
+    /// been buffered (eg. for tables of many thousands of routes).
+    #[cfg(feature = "streaming")]
+    pub async fn list_stream(
+        &self,
+        configuration: Option<RouteShowConfiguration>,
+    ) -> Result<JsonStream<Route>, Error> {
+        let mut args: Vec<String> = vec!["route".into(), "show".into()];
+        if let Some(configuration) = configuration {
+            args.append(
+                &mut Serializer::new(BooleanType::OnOff)
+                    .into_args(&configuration)
+                    .context(CommandOptionsSerializationError {})?,
+            );
+        }
+        let routes = self.ip_command.command_with_json_stream(&args).await?;
+        // As with `list`, a table without any routes lists no routes.
+        Ok(Box::pin(routes.filter(|route| {
+            future::ready(
+                !matches!(route, Err(Error::CommandFailedError { stderr, .. })
+                if stderr.contains("FIB table does not exist")),
+            )
+        })))
     }
 
     /// Flush routing tables.
@@ -52,37 +769,1167 @@ impl<'l> IpRouteCommand<'l> {
     }
 
     /// Get a single route.
-    pub async fn get(&self) -> Result<(), Error> {
-        unimplemented!()
+    ///
+    /// If the client has a route cache (see `IpCommand::with_route_cache`), results are
+    /// served from it until they expire.
+    ///
+    /// A link local IPv6 destination requires an output device (or a zone, eg. "fe80::1%eth0").
+    pub async fn get(&self, configuration: RouteGetConfiguration) -> Result<Route, Error> {
+        let configuration = configuration.scoped()?;
+        let mut args: Vec<String> = vec!["route".into(), "get".into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        // The cache is shared with clients of other network namespaces.
+        let key: Vec<String> = match &self.ip_command.namespace {
+            Some(namespace) => vec!["-netns".into(), namespace.clone()]
+                .into_iter()
+                .chain(args.iter().cloned())
+                .collect(),
+            None => args.clone(),
+        };
+        if let Some(route_cache) = &self.ip_command.route_cache {
+            if let Some(route) = route_cache.lock().unwrap().get(&key) {
+                return Ok(route);
+            }
+        }
+        let output = self.ip_command.command(&args, true, false, None).await?;
+        let mut routes: Vec<Route> =
+            serde_json::from_str(&output).context(JsonDeserializationError {})?;
+        let route = routes.pop().context(NotFoundError {})?;
+        if let Some(route_cache) = &self.ip_command.route_cache {
+            route_cache.lock().unwrap().insert(key, route.clone());
+        }
+        Ok(route)
     }
 
     /// Add new route.
-    pub async fn add(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn add(&self, configuration: RouteAddConfiguration) -> Result<(), Error> {
+        let args = Self::modify_args("add", &configuration)?;
+        self.modify(&args).await
     }
 
     /// Delete route.
-    pub async fn delete(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn delete(&self, configuration: RouteDeleteConfiguration) -> Result<(), Error> {
+        let args = Self::modify_args("del", &configuration)?;
+        self.modify(&args).await
     }
 
-    /// Change route.
-    pub async fn change(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Change route, which fails unless the route exists.
+    pub async fn change(&self, configuration: RouteChangeConfiguration) -> Result<(), Error> {
+        let args = Self::modify_args("change", &configuration)?;
+        self.modify(&args).await
     }
 
     /// Change or add new route.
-    pub async fn replace(&self) -> Result<(), Error> {
-        unimplemented!()
+    ///
+    /// Unless a protocol is given, the protocol of the existing route (if any) is preserved,
+    /// rather than being reset to "boot".
+    pub async fn replace(&self, configuration: RouteReplaceConfiguration) -> Result<(), Error> {
+        let mut configuration = configuration;
+        if configuration.protocol.is_none() {
+            configuration.protocol = self.existing_protocol(&configuration).await?;
+        }
+        let args = Self::modify_args("replace", &configuration)?;
+        self.modify(&args).await
     }
 
-    /// Append a new route.
-    pub async fn append(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Run a command which modifies routes, clearing the route cache (if any) as cached
+    /// routes may no longer resolve the same.
+    async fn modify(&self, args: &[String]) -> Result<(), Error> {
+        let result = self.ip_command.command_without_output(args, None).await;
+        if let Some(route_cache) = &self.ip_command.route_cache {
+            route_cache.lock().unwrap().clear();
+        }
+        result
     }
 
-    /// Prepend a new route.
-    pub async fn prepend(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Arguments of the add, delete, change, replace, append and prepend commands.
+    fn modify_args(
+        command: &str,
+        configuration: &RouteAddConfiguration,
+    ) -> Result<Vec<String>, Error> {
+        let mut args: Vec<String> = vec!["route".into(), command.into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        // The type must directly precede the prefix (after "to", the first argument).
+        if let Some(route_type) = &configuration.route_type {
+            args.insert(3, route_type.to_string());
+        }
+        Ok(args)
+    }
+
+    /// Is there a route equivalent to the configured one? ie. to the same destination prefix,
+    /// in the same table, via the same gateway and device (when configured).
+    pub async fn exists(&self, configuration: &RouteAddConfiguration) -> Result<bool, Error> {
+        let routes = self
+            .list(Some(RouteShowConfiguration {
+                to: Some(configuration.destination.clone()),
+                table: configuration.table.clone(),
+                vrf: configuration.vrf.clone(),
+                ..Default::default()
+            }))
+            .await?;
+        let matches = |configured: &Option<String>, listed: &Option<String>| {
+            configured.is_none() || configured == listed
+        };
+        Ok(routes.iter().any(|route| {
+            matches(&configuration.gateway, &route.gateway)
+                && matches(&configuration.device, &route.device)
+        }))
+    }
+
+    /// Add the route unless an equivalent route exists (see `exists`), returns whether it
+    /// was added.
+    pub async fn ensure(&self, configuration: RouteAddConfiguration) -> Result<bool, Error> {
+        if self.exists(&configuration).await? {
+            return Ok(false);
+        }
+        self.add(configuration).await?;
+        Ok(true)
+    }
+
+    /// The protocol of the route to the exact destination prefix, preferring one with the same
+    /// metric. `get` resolves addresses rather than routes, and does not report protocols.
+    async fn existing_protocol(
+        &self,
+        configuration: &RouteReplaceConfiguration,
+    ) -> Result<Option<RouteProtocol>, Error> {
+        let routes = self
+            .list(Some(RouteShowConfiguration {
+                to: Some(configuration.destination.clone()),
+                table: configuration.table.clone(),
+                vrf: configuration.vrf.clone(),
+                ..Default::default()
+            }))
+            .await?;
+        Ok(routes
+            .iter()
+            .find(|route| route.metric == configuration.metric)
+            .or_else(|| routes.first())
+            .and_then(|route| route.protocol.clone()))
+    }
+
+    /// Append a new route, after any existing routes to the same destination (eg. as a
+    /// fallback of another metric, or an additional IPv6 next hop).
+    pub async fn append(&self, configuration: RouteAddConfiguration) -> Result<(), Error> {
+        let args = Self::modify_args("append", &configuration)?;
+        self.modify(&args).await
+    }
+
+    /// Prepend a new route, before any existing routes to the same destination.
+    pub async fn prepend(&self, configuration: RouteAddConfiguration) -> Result<(), Error> {
+        let args = Self::modify_args("prepend", &configuration)?;
+        self.modify(&args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::link::*;
+
+    const ROUTE_GET_CACHED_OUTPUT: &str = r#"[{"dst":"172.82.0.2","gateway":"192.0.2.1","dev":"eth0","prefsrc":"192.0.2.2","flags":[],"cache":["redirected"],"expires":580,"users":1,"age":2,"metrics":[{"mtu":1400}]}]"#;
+    const ROUTE_LIST_OUTPUT: &str = r#"[{"dst":"172.82.0.0/24","gateway":"192.0.2.1","dev":"eth0","protocol":"static","metric":10,"flags":[]}]"#;
+    const ROUTE_LIST_MULTIPATH_OUTPUT: &str = r#"[{"dst":"198.51.100.0/24","dev":"eth0","protocol":"kernel","scope":"link","prefsrc":"198.51.100.1","flags":[]},{"dst":"203.0.113.0/24","flags":[],"nexthops":[{"gateway":"192.0.2.2","dev":"eth1","weight":1,"flags":[]},{"gateway":"198.51.100.2","dev":"eth0","weight":3,"flags":["onlink"]}]}]"#;
+    const ROUTE_GET_OUTPUT: &str = r#"[{"dst":"172.82.0.1","gateway":"192.0.2.1","dev":"eth0","prefsrc":"192.0.2.2","flags":[],"uid":0,"cache":[]}]"#;
+
+    #[tokio::test]
+    async fn test_get() {
+        let client = IpCommand::new().unwrap();
+        let route = client
+            .route()
+            .get(RouteGetConfiguration {
+                to: "127.0.0.1".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(route.destination, "127.0.0.1");
+        assert_eq!(route.device, Some("lo".into()));
+    }
+
+    #[tokio::test]
+    async fn test_get_link_local() {
+        let link_name = "test_route3";
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                state: Some(LinkStatus::Up),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let route = client
+            .route()
+            .get(RouteGetConfiguration {
+                to: "fe80::1".into(),
+                output_device: Some(link_name.into()),
+                ..Default::default()
+            })
+            .await;
+        let scoped_route = client
+            .route()
+            .get(RouteGetConfiguration {
+                to: format!("fe80::1%{}", link_name),
+                ..Default::default()
+            })
+            .await;
+        let unscoped_route = client
+            .route()
+            .get(RouteGetConfiguration {
+                to: "fe80::1".into(),
+                ..Default::default()
+            })
+            .await;
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        let route = route.unwrap();
+        assert_eq!(route.destination, "fe80::1");
+        assert_eq!(route.device, Some(link_name.into()));
+        assert_eq!(scoped_route.unwrap().device, Some(link_name.into()));
+        assert!(matches!(
+            unscoped_route,
+            Err(Error::InvalidArgumentError { field, .. }) if field == "output_device"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exists_by_table() {
+        let link_name = "test_route4";
+        let destination = "172.85.0.0/24";
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                state: Some(LinkStatus::Up),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let route = |table: &str| RouteAddConfiguration {
+            destination: destination.into(),
+            device: Some(link_name.into()),
+            table: Some(table.into()),
+            ..Default::default()
+        };
+        client.route().add(route("100")).await.unwrap();
+        let added = client.route().ensure(route("101")).await.unwrap();
+        let added_again = client.route().ensure(route("101")).await.unwrap();
+        let exists = client.route().exists(&route("100")).await.unwrap();
+        let exists_elsewhere = client.route().exists(&route("102")).await.unwrap();
+        let exists_via_other_device = client
+            .route()
+            .exists(&RouteAddConfiguration {
+                device: Some("lo".into()),
+                ..route("100")
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        assert!(added);
+        assert!(!added_again);
+        assert!(exists);
+        assert!(!exists_elsewhere);
+        assert!(!exists_via_other_device);
+    }
+
+    #[tokio::test]
+    async fn test_add_existing_and_replace() {
+        let link_name = "test_route5";
+        let client = IpCommand::new().unwrap();
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                state: Some(LinkStatus::Up),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let route = RouteAddConfiguration {
+            destination: "172.89.0.0/24".into(),
+            device: Some(link_name.into()),
+            ..Default::default()
+        };
+        client.route().add(route.clone()).await.unwrap();
+        let added_again = client.route().add(route.clone()).await;
+        let replaced = client.route().replace(route.clone()).await;
+        let changed = client
+            .route()
+            .change(RouteAddConfiguration {
+                protocol: Some("static".into()),
+                ..route.clone()
+            })
+            .await;
+        let changed_missing = client
+            .route()
+            .change(RouteAddConfiguration {
+                destination: "172.89.1.0/24".into(),
+                ..route.clone()
+            })
+            .await;
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                link_type: "dummy".into(),
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(added_again, Err(Error::CommandFailedError { .. })));
+        replaced.unwrap();
+        changed.unwrap();
+        assert!(matches!(
+            changed_missing,
+            Err(Error::CommandFailedError { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_modify_verbs() {
+        let client = IpCommand::new().unwrap().dry_run();
+        let route = RouteAddConfiguration {
+            destination: "172.86.0.0/24".into(),
+            device: Some("eth0".into()),
+            ..Default::default()
+        };
+        client.route().change(route.clone()).await.unwrap();
+        client.route().append(route.clone()).await.unwrap();
+        client.route().prepend(route).await.unwrap();
+        let verbs: Vec<String> = client
+            .recorded_commands()
+            .into_iter()
+            .map(|command| command[1].clone())
+            .collect();
+        assert_eq!(verbs, vec!["change", "append", "prepend"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_cached() {
+        let client = IpCommand::new()
+            .unwrap()
+            .with_route_cache(16, Duration::from_secs(60))
+            .dry_run_with_output(ROUTE_GET_OUTPUT);
+        let configuration = RouteGetConfiguration {
+            to: "172.82.0.1".into(),
+            ..Default::default()
+        };
+
+        let route = client.route().get(configuration.clone()).await.unwrap();
+        let cached_route = client.route().get(configuration).await.unwrap();
+
+        assert_eq!(client.recorded_commands().len(), 1);
+        assert_eq!(route.gateway, Some("192.0.2.1".into()));
+        assert_eq!(cached_route.gateway, Some("192.0.2.1".into()));
+    }
+
+    #[tokio::test]
+    async fn test_get_cached_per_namespace() {
+        let client = IpCommand::new()
+            .unwrap()
+            .with_route_cache(16, Duration::from_secs(60))
+            .dry_run_with_output(ROUTE_GET_OUTPUT);
+        let namespace_client = client.with_namespace("ip-command-test-route-cache-namespace");
+        let configuration = RouteGetConfiguration {
+            to: "172.82.0.1".into(),
+            ..Default::default()
+        };
+
+        client.route().get(configuration.clone()).await.unwrap();
+        namespace_client
+            .route()
+            .get(configuration.clone())
+            .await
+            .unwrap();
+        namespace_client.route().get(configuration).await.unwrap();
+
+        assert_eq!(client.recorded_commands().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_cached_cleared_by_modification() {
+        let client = IpCommand::new()
+            .unwrap()
+            .with_route_cache(16, Duration::from_secs(60))
+            .dry_run_with_output(ROUTE_GET_OUTPUT);
+        // Shares the route cache, modifications produce no output.
+        let modifying_client = client.dry_run_with_output("");
+        let configuration = RouteGetConfiguration {
+            to: "172.82.0.1".into(),
+            ..Default::default()
+        };
+        let route = RouteAddConfiguration {
+            destination: "172.82.0.0/24".into(),
+            gateway: Some("192.0.2.3".into()),
+            protocol: Some(RouteProtocol::Static),
+            ..Default::default()
+        };
+
+        client.route().get(configuration.clone()).await.unwrap();
+        modifying_client.route().add(route.clone()).await.unwrap();
+        client.route().get(configuration.clone()).await.unwrap();
+        modifying_client
+            .route()
+            .delete(route.clone())
+            .await
+            .unwrap();
+        client.route().get(configuration.clone()).await.unwrap();
+        modifying_client.route().replace(route).await.unwrap();
+        client.route().get(configuration).await.unwrap();
+
+        assert_eq!(client.recorded_commands().len(), 4);
+        let verbs: Vec<String> = modifying_client
+            .recorded_commands()
+            .into_iter()
+            .map(|command| command[1].clone())
+            .collect();
+        assert_eq!(verbs, vec!["add", "del", "replace"]);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_list_vrf() {
+        let vrf_name = "test_vrf0";
+        let link_name = "test_route0";
+        let destination = "172.83.0.0/24";
+        let client = IpCommand::new().unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: vrf_name.into(),
+                link_type: LinkKind::Vrf(VrfConfiguration { table: 1001 }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        for link_name in &[vrf_name, link_name] {
+            client
+                .link()
+                .set(LinkSetConfiguration {
+                    device: LinkDeviceOrGroup::Device((*link_name).into()),
+                    state: Some(LinkStatus::Up),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                vrf_master: Some(vrf_name.into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .route()
+            .add(RouteAddConfiguration {
+                destination: destination.into(),
+                device: Some(link_name.into()),
+                vrf: Some(vrf_name.into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let vrf_routes = client
+            .route()
+            .list(Some(RouteShowConfiguration {
+                vrf: Some(vrf_name.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        let main_routes = client.route().list(None).await.unwrap();
+
+        for link_name in &[link_name, vrf_name] {
+            client
+                .link()
+                .delete(LinkDeleteConfiguration {
+                    device: LinkDeviceOrGroup::Device((*link_name).into()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        assert!(vrf_routes
+            .iter()
+            .any(|route| route.destination == destination));
+        assert!(!main_routes
+            .iter()
+            .any(|route| route.destination == destination));
+    }
+
+    #[tokio::test]
+    async fn test_replace_preserves_protocol() {
+        let link_name = "test_route1";
+        let destination = "172.84.0.0/24";
+        let client = IpCommand::new().unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                state: Some(LinkStatus::Up),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .route()
+            .add(RouteAddConfiguration {
+                destination: destination.into(),
+                device: Some(link_name.into()),
+                protocol: Some("static".into()),
+                metric: Some(10),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .route()
+            .replace(RouteReplaceConfiguration {
+                destination: destination.into(),
+                device: Some(link_name.into()),
+                metric: Some(20),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let routes = client
+            .route()
+            .list(Some(RouteShowConfiguration {
+                to: Some(destination.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let replaced_route = routes
+            .iter()
+            .find(|route| route.metric == Some(20))
+            .unwrap();
+        assert_eq!(replaced_route.protocol, Some("static".into()));
+    }
+
+    #[test]
+    fn test_serialize_metrics() {
+        let args = Serializer::new(BooleanType::OnOff)
+            .into_args(&RouteAddConfiguration {
+                destination: "172.85.0.0/24".into(),
+                metrics: Some(RouteMetrics {
+                    mtu: Some(1400),
+                    advertised_mss: Some(1360),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            args,
+            vec!["to", "172.85.0.0/24", "mtu", "1400", "advmss", "1360"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_metrics() {
+        let link_name = "test_route2";
+        let destination = "172.85.0.0/24";
+        let client = IpCommand::new().unwrap();
+
+        client
+            .link()
+            .add(LinkAddConfiguration {
+                name: link_name.into(),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                state: Some(LinkStatus::Up),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        client
+            .route()
+            .add(RouteAddConfiguration {
+                destination: destination.into(),
+                device: Some(link_name.into()),
+                metrics: Some(RouteMetrics {
+                    mtu: Some(1400),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let routes = client
+            .route()
+            .list(Some(RouteShowConfiguration {
+                to: Some(destination.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client
+            .link()
+            .delete(LinkDeleteConfiguration {
+                device: LinkDeviceOrGroup::Device(link_name.into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(routes[0].metrics.as_ref().unwrap().mtu, Some(1400));
+    }
+
+    #[test]
+    fn test_modify_args() {
+        let args = IpRouteCommand::modify_args(
+            "add",
+            &RouteAddConfiguration {
+                destination: "172.86.0.0/24".into(),
+                route_type: Some(RouteType::Blackhole),
+                realm: Some("5".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "route",
+                "add",
+                "to",
+                "blackhole",
+                "172.86.0.0/24",
+                "realm",
+                "5"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_blackhole() {
+        let destination = "172.86.0.0/24";
+        let client = IpCommand::new().unwrap();
+        let configuration = RouteAddConfiguration {
+            destination: destination.into(),
+            route_type: Some(RouteType::Blackhole),
+            ..Default::default()
+        };
+
+        client.route().add(configuration.clone()).await.unwrap();
+
+        let routes = client
+            .route()
+            .list(Some(RouteShowConfiguration {
+                to: Some(destination.into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        client.route().delete(configuration).await.unwrap();
+
+        assert_eq!(routes[0].route_type, Some(RouteType::Blackhole));
+    }
+
+    #[cfg(feature = "streaming")]
+    #[tokio::test]
+    async fn test_list_stream() {
+        let client = IpCommand::new().unwrap();
+        let table = Some(RouteShowConfiguration {
+            table: Some("local".into()),
+            ..Default::default()
+        });
+        let routes = client.route().list(table.clone()).await.unwrap();
+        let streamed: Vec<Route> = client
+            .route()
+            .list_stream(table)
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(streamed.len(), routes.len());
+        assert_eq!(streamed[0].destination, routes[0].destination);
+
+        // Dropping the stream early stops the command.
+        let mut stream = client.route().list_stream(None).await.unwrap();
+        assert!(stream.next().await.unwrap().is_ok());
+        drop(stream);
+
+        let empty = client
+            .route()
+            .list_stream(Some(RouteShowConfiguration {
+                table: Some("4242".into()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+        assert!(empty.is_empty());
+
+        let client = client.dry_run_with_output("[{\"dst\":\"172.82.0.0/24\"},{}");
+        let mut stream = client.route().list_stream(None).await.unwrap();
+        assert_eq!(
+            stream.next().await.unwrap().unwrap().destination,
+            "172.82.0.0/24"
+        );
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(Error::JsonDeserializationError { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_type_of_service() {
+        let test_namespace = "ip-command-test-tos-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+        let namespace_client = client.with_namespace(test_namespace);
+        namespace_client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device("lo".into()),
+                state: Some(LinkStatus::Up),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        namespace_client
+            .route()
+            .add(RouteAddConfiguration {
+                destination: "172.87.0.0/24".into(),
+                type_of_service: Some("0x10".into()),
+                device: Some("lo".into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let get = |type_of_service: Option<&str>| RouteGetConfiguration {
+            to: "172.87.0.1".into(),
+            type_of_service: type_of_service.map(String::from),
+            ..Default::default()
+        };
+        let matching = namespace_client.route().get(get(Some("0x10"))).await;
+        let other = namespace_client.route().get(get(None)).await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        assert_eq!(matching.unwrap().type_of_service, Some("0x10".into()));
+        assert!(other.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_table() {
+        let test_namespace = "ip-command-test-table-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+        let namespace_client = client.with_namespace(test_namespace);
+        namespace_client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device("lo".into()),
+                state: Some(LinkStatus::Up),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        for (destination, table) in &[
+            ("172.88.0.0/24", Some(RoutingTable::Id(100))),
+            ("172.88.1.0/24", None),
+        ] {
+            namespace_client
+                .route()
+                .add(RouteAddConfiguration {
+                    destination: (*destination).into(),
+                    device: Some("lo".into()),
+                    table: table.clone(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+        let routes = namespace_client
+            .route()
+            .list(Some(RouteShowConfiguration {
+                table: Some(RoutingTable::Id(100)),
+                ..Default::default()
+            }))
+            .await;
+        let all_routes = namespace_client
+            .route()
+            .list(Some(RouteShowConfiguration {
+                table: Some(RoutingTable::All),
+                ..Default::default()
+            }))
+            .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        let routes = routes.unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].destination, "172.88.0.0/24");
+        let all_routes = all_routes.unwrap();
+        let table_of = |destination: &str| {
+            all_routes
+                .iter()
+                .find(|route| route.destination == destination)
+                .map(|route| route.table.clone())
+                .unwrap()
+        };
+        assert_eq!(table_of("172.88.0.0/24"), Some(RoutingTable::Id(100)));
+        // Routes of the main table are listed without it.
+        assert!(matches!(
+            table_of("172.88.1.0/24"),
+            None | Some(RoutingTable::Main)
+        ));
+        assert!(all_routes
+            .iter()
+            .any(|route| route.table == Some(RoutingTable::Local)));
+    }
+
+    #[test]
+    fn test_routing_table() {
+        assert_eq!(RoutingTable::from("254"), RoutingTable::Main);
+        assert_eq!(RoutingTable::from(100), RoutingTable::Id(100));
+        assert_eq!(RoutingTable::from("vpn"), RoutingTable::Named("vpn".into()));
+        assert_eq!(RoutingTable::Named("vpn".into()).to_string(), "vpn");
+        assert_eq!(RoutingTable::Local.id(), Some(255));
+        assert_eq!(
+            RoutingTable::find_id("# reserved values\n255\tlocal\n100 vpn # tunnel\n", "vpn"),
+            Some(100)
+        );
+        assert_eq!(RoutingTable::find_id("100 vpn\n", "other"), None);
+        let tables: Vec<RoutingTable> = serde_json::from_str(r#"["local", "100", 101]"#).unwrap();
+        assert_eq!(
+            tables,
+            vec![
+                RoutingTable::Local,
+                RoutingTable::Id(100),
+                RoutingTable::Id(101)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_route_protocol() {
+        assert_eq!(RouteProtocol::from("static"), RouteProtocol::Static);
+        assert_eq!(RouteProtocol::from("186"), RouteProtocol::Bgp);
+        assert_eq!(RouteProtocol::from(42), RouteProtocol::Numeric(42));
+        assert_eq!(
+            RouteProtocol::from("bird"),
+            RouteProtocol::Named("bird".into())
+        );
+        assert_eq!(RouteProtocol::Ra.to_string(), "ra");
+        let protocols: Vec<RouteProtocol> =
+            serde_json::from_str(r#"["kernel", "dhcp", "42", 188]"#).unwrap();
+        assert_eq!(
+            protocols,
+            vec![
+                RouteProtocol::Kernel,
+                RouteProtocol::Dhcp,
+                RouteProtocol::Numeric(42),
+                RouteProtocol::Ospf
+            ]
+        );
+        assert!(serde_json::from_str::<RouteProtocol>("256").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_by_protocol() {
+        let test_namespace = "ip-command-test-route-protocol-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let result = async {
+            for (destination, protocol) in &[
+                ("198.51.100.0/24", Some(RouteProtocol::Static)),
+                ("203.0.113.0/24", None),
+            ] {
+                namespace_client
+                    .route()
+                    .add(RouteAddConfiguration {
+                        destination: (*destination).into(),
+                        route_type: Some(RouteType::Blackhole),
+                        protocol: protocol.clone(),
+                        ..Default::default()
+                    })
+                    .await?;
+            }
+            namespace_client
+                .route()
+                .list(Some(RouteShowConfiguration {
+                    protocol: Some(RouteProtocol::Static),
+                    ..Default::default()
+                }))
+                .await
+        }
+        .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        // The protocol filtered by is left out of the listed routes.
+        let routes = result.unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].destination, "198.51.100.0/24");
+    }
+
+    #[tokio::test]
+    async fn test_add_seg6() {
+        let test_namespace = "ip-command-test-seg6-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+        let namespace_client = client.with_namespace(test_namespace);
+        namespace_client
+            .link()
+            .set(LinkSetConfiguration {
+                device: LinkDeviceOrGroup::Device("lo".into()),
+                state: Some(LinkStatus::Up),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let added = namespace_client
+            .route()
+            .add(RouteAddConfiguration {
+                destination: "2001:db8:1::/64".into(),
+                encapsulation: Some(RouteEncap::Seg6 {
+                    mode: Seg6Mode::Encap,
+                    segments: vec!["2001:db8::1".into(), "2001:db8::2".into()],
+                }),
+                device: Some("lo".into()),
+                ..Default::default()
+            })
+            .await;
+        let routes = namespace_client
+            .route()
+            .list(Some(RouteShowConfiguration {
+                to: Some("2001:db8:1::/64".into()),
+                // The main table is only listed for IPv4.
+                table: Some("all".into()),
+                ..Default::default()
+            }))
+            .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        // Without kernel support for seg6 there is nothing to read back.
+        if let Err(Error::CommandFailedError { .. }) = added {
+            return;
+        }
+        added.unwrap();
+        let encapsulation = routes.unwrap()[0].encapsulation.clone().unwrap();
+        assert_eq!(encapsulation.kind, "seg6");
+        assert_eq!(encapsulation.mode, Some("encap".into()));
+        assert_eq!(
+            encapsulation.segments,
+            Some(vec!["2001:db8::1".into(), "2001:db8::2".into()])
+        );
+    }
+
+    #[test]
+    fn test_serialize_seg6_local() {
+        let args = IpRouteCommand::modify_args(
+            "add",
+            &RouteAddConfiguration {
+                destination: "2001:db8:2::1/128".into(),
+                encapsulation: Some(RouteEncap::Seg6Local {
+                    action: "End.DX6".into(),
+                    next_hop: Some("2001:db8::3".into()),
+                    table: None,
+                }),
+                device: Some("eth0".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "route",
+                "add",
+                "to",
+                "2001:db8:2::1/128",
+                "encap",
+                "seg6local",
+                "action",
+                "End.DX6",
+                "nh6",
+                "2001:db8::3",
+                "dev",
+                "eth0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_next_hops() {
+        let routes: Vec<Route> = serde_json::from_str(ROUTE_LIST_MULTIPATH_OUTPUT).unwrap();
+        assert!(routes[0].next_hops.is_none());
+        assert_eq!(routes[1].device, None);
+        let next_hops = routes[1].next_hops.as_ref().unwrap();
+        assert_eq!(next_hops.len(), 2);
+        assert_eq!(next_hops[0].gateway, Some("192.0.2.2".into()));
+        assert_eq!(next_hops[0].device, Some("eth1".into()));
+        assert_eq!(next_hops[0].weight, Some(1));
+        assert!(next_hops[0].flags.is_empty());
+        assert_eq!(next_hops[1].weight, Some(3));
+        assert_eq!(next_hops[1].flags, vec!["onlink"]);
+    }
+
+    #[test]
+    fn test_deserialize_cache_info() {
+        let routes: Vec<Route> = serde_json::from_str(ROUTE_GET_CACHED_OUTPUT).unwrap();
+        let cache = routes[0].cache.as_ref().unwrap();
+        assert_eq!(cache.flags, vec!["redirected"]);
+        assert_eq!(cache.expires, Some(580));
+        assert_eq!(cache.users, Some(1));
+        assert_eq!(cache.age, Some(2));
+        assert_eq!(routes[0].metrics.as_ref().unwrap().mtu, Some(1400));
+
+        let routes: Vec<Route> = serde_json::from_str(ROUTE_LIST_OUTPUT).unwrap();
+        assert!(routes[0].cache.is_none());
+        assert_eq!(routes[0].protocol, Some("static".into()));
+
+        // IPv4 results of get always report the (empty) cache flags.
+        let routes: Vec<Route> = serde_json::from_str(ROUTE_GET_OUTPUT).unwrap();
+        assert!(routes[0].cache.is_none());
+    }
+
+    #[test]
+    fn test_route_cache_eviction_and_expiry() {
+        let route: Route = serde_json::from_str::<Vec<Route>>(ROUTE_GET_OUTPUT)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let mut route_cache = RouteCache::new(1, Duration::from_secs(60));
+        route_cache.insert(vec!["a".into()], route.clone());
+        route_cache.insert(vec!["b".into()], route.clone());
+        assert!(route_cache.get(&["a".into()]).is_none());
+        assert!(route_cache.get(&["b".into()]).is_some());
+
+        let expired_route = Route {
+            cache: Some(CacheInfo {
+                expires: Some(0),
+                ..Default::default()
+            }),
+            ..route
+        };
+        route_cache.insert(vec!["c".into()], expired_route);
+        assert!(route_cache.get(&["c".into()]).is_none());
     }
 }
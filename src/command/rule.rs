@@ -14,31 +14,128 @@
  * limitations under the License.
  */
 
-use crate::{Error, IpCommand};
+use crate::*;
+use serde::{Deserialize, Serialize};
+use serde_command_opts::{BooleanType, Serializer};
+use snafu::ResultExt;
+
+/// Add rule configuration, the selectors (and action) of the rule.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RuleAddConfiguration {
+    /// Select the source prefix to match.
+    pub from: Option<String>,
+    /// Select the destination prefix to match.
+    pub to: Option<String>,
+    /// Select the incoming device to match.
+    #[serde(rename = "iif")]
+    pub input_interface: Option<String>,
+    /// Select the outgoing device to match.
+    #[serde(rename = "oif")]
+    pub output_interface: Option<String>,
+    /// Select the firewall mark (and optional mask, eg. "0x10/0xff") to match.
+    #[serde(rename = "fwmark")]
+    pub firewall_mark: Option<String>,
+    /// The priority of the rule, rules are evaluated in order of increasing priority.
+    pub priority: Option<u32>,
+    /// The routing table to look up when the rule matches.
+    pub table: Option<String>,
+    /// Reject the result of the table lookup if its prefix is not longer than this length
+    /// (eg. 0 to ignore default routes).
+    #[serde(rename = "suppress_prefixlength")]
+    pub suppress_prefix_length: Option<u8>,
+    /// Reject the result of the table lookup if its device is in this group.
+    #[serde(rename = "suppress_ifgroup")]
+    pub suppress_interface_group: Option<u32>,
+}
+
+/// Delete rule configuration.
+///
+/// The first rule matching all of the given selectors is deleted, so to delete a specific rule
+/// (rather than whichever equivalent rule has the lowest priority) set its `priority`.
+pub type RuleDeleteConfiguration = RuleAddConfiguration;
+
+/// The returned rule structure.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    pub priority: u32,
+    #[serde(rename = "src")]
+    pub source: String,
+    #[serde(rename = "srclen")]
+    pub source_length: Option<u32>,
+    #[serde(rename = "dst")]
+    pub destination: Option<String>,
+    #[serde(rename = "dstlen")]
+    pub destination_length: Option<u32>,
+    #[serde(rename = "iif")]
+    pub input_interface: Option<String>,
+    #[serde(rename = "oif")]
+    pub output_interface: Option<String>,
+    #[serde(rename = "fwmark")]
+    pub firewall_mark: Option<String>,
+    #[serde(rename = "fwmask")]
+    pub firewall_mask: Option<String>,
+    pub table: Option<String>,
+    #[serde(rename = "suppress_prefixlen")]
+    pub suppress_prefix_length: Option<u8>,
+    /// The group name, or number when it has none (eg. "default" for group 0).
+    #[serde(rename = "suppress_ifgroup")]
+    pub suppress_interface_group: Option<String>,
+}
 
 #[derive(Clone)]
 pub struct IpRuleCommand<'l> {
     ip_command: &'l IpCommand,
+    object: &'static str,
 }
 
 impl<'l> IpRuleCommand<'l> {
     pub fn new(ip_command: &'l IpCommand) -> Self {
-        Self { ip_command }
+        Self {
+            ip_command,
+            object: "rule",
+        }
+    }
+
+    /// Rule command operating on the multicast routing rules instead.
+    pub(crate) fn multicast(ip_command: &'l IpCommand) -> Self {
+        Self {
+            ip_command,
+            object: "mrule",
+        }
     }
 
     /// Insert a new rule.
-    pub async fn add(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn add(&self, configuration: RuleAddConfiguration) -> Result<(), Error> {
+        self.modify("add", &configuration).await
     }
 
     /// Delete a rule.
-    pub async fn delete(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn delete(&self, configuration: RuleDeleteConfiguration) -> Result<(), Error> {
+        self.modify("del", &configuration).await
+    }
+
+    async fn modify(
+        &self,
+        command: &str,
+        configuration: &RuleAddConfiguration,
+    ) -> Result<(), Error> {
+        let mut args: Vec<String> = vec![self.object.into(), command.into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        self.ip_command.command_without_output(&args, None).await
     }
 
     /// Flush rules table information.
+    ///
+    /// Every rule except the lookup of the local table is deleted, including the default
+    /// lookups of the main and default tables.
     pub async fn flush(&self) -> Result<(), Error> {
-        unimplemented!()
+        self.ip_command
+            .command_without_output(&[self.object, "flush"], None)
+            .await
     }
 
     /// Save rules table information as raw netlink configuration.
@@ -51,8 +148,92 @@ impl<'l> IpRuleCommand<'l> {
         unimplemented!()
     }
 
-    // List rules.
-    pub async fn list(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// List rules.
+    pub async fn list(&self) -> Result<Vec<Rule>, Error> {
+        let output = self
+            .ip_command
+            .command(&[self.object, "list"], true, false, None)
+            .await?;
+        serde_json::from_str(&output).context(JsonDeserializationError {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_delete_by_priority() {
+        let test_namespace = "ip-command-test-rule-namespace";
+        let rule = |priority| RuleAddConfiguration {
+            from: Some("192.0.2.0/24".into()),
+            priority: Some(priority),
+            table: Some("100".into()),
+            ..Default::default()
+        };
+
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+        let namespace_client = client.with_namespace(test_namespace);
+
+        namespace_client.rule().add(rule(1000)).await.unwrap();
+        namespace_client.rule().add(rule(2000)).await.unwrap();
+        namespace_client.rule().delete(rule(2000)).await.unwrap();
+        let rules = namespace_client.rule().list().await.unwrap();
+
+        namespace_client.rule().flush().await.unwrap();
+        let flushed = namespace_client.rule().list().await.unwrap();
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        let priorities: Vec<u32> = rules
+            .iter()
+            .filter(|rule| rule.table.as_deref() == Some("100"))
+            .map(|rule| rule.priority)
+            .collect();
+        assert_eq!(priorities, vec![1000]);
+        assert_eq!(rules[1].source, "192.0.2.0");
+        assert_eq!(rules[1].source_length, Some(24));
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].table.as_deref(), Some("local"));
+    }
+
+    #[tokio::test]
+    async fn test_add_suppress() {
+        let test_namespace = "ip-command-test-suppress-namespace";
+
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+        let namespace_client = client.with_namespace(test_namespace);
+
+        namespace_client
+            .rule()
+            .add(RuleAddConfiguration {
+                priority: Some(1000),
+                table: Some("main".into()),
+                suppress_prefix_length: Some(0),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        namespace_client
+            .rule()
+            .add(RuleAddConfiguration {
+                priority: Some(1001),
+                table: Some("main".into()),
+                suppress_interface_group: Some(5),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let rules = namespace_client.rule().list().await.unwrap();
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        let rule = |priority| rules.iter().find(|rule| rule.priority == priority).unwrap();
+        assert_eq!(rule(1000).suppress_prefix_length, Some(0));
+        assert_eq!(rule(1000).suppress_interface_group, None);
+        assert_eq!(rule(1001).suppress_interface_group.as_deref(), Some("5"));
+        assert_eq!(rule(32766).suppress_prefix_length, None);
     }
 }
@@ -14,7 +14,91 @@
  * limitations under the License.
  */
 
-use crate::{Error, IpCommand};
+#[cfg(feature = "streaming")]
+use crate::ConsoleStream;
+use crate::{CommandOptionsSerializationError, Error, IpCommand, UnexpectedOutputError};
+use serde::Serialize;
+use serde_command_opts::{BooleanType, Serializer};
+use snafu::{OptionExt, ResultExt};
+use std::fmt;
+
+/// The number of xfrm policies in each direction.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransformPolicyCount {
+    pub input: u32,
+    pub output: u32,
+    pub forward: u32,
+}
+
+/// The returned xfrm state (security association).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformState {
+    pub source: String,
+    pub destination: String,
+    /// The IPsec protocol (eg. "esp", "ah").
+    pub protocol: String,
+    /// The security parameter index.
+    pub spi: u32,
+    pub request_id: Option<u32>,
+    /// Eg. "transport" or "tunnel".
+    pub mode: Option<String>,
+}
+
+/// Direction of the traffic an xfrm policy applies to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransformDirection {
+    Input,
+    Output,
+    Forward,
+}
+
+impl TransformDirection {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "in" => Some(Self::Input),
+            "out" => Some(Self::Output),
+            "fwd" => Some(Self::Forward),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TransformDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Input => "in",
+            Self::Output => "out",
+            Self::Forward => "fwd",
+        })
+    }
+}
+
+/// Selector of the traffic an xfrm policy applies to.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct TransformSelector {
+    /// The source prefix (eg. "10.0.0.0/24").
+    #[serde(rename = "src")]
+    pub source: String,
+    /// The destination prefix.
+    #[serde(rename = "dst")]
+    pub destination: String,
+    /// The upper layer protocol (eg. "tcp").
+    #[serde(rename = "proto")]
+    pub protocol: Option<String>,
+    /// The device the traffic is routed via.
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+}
+
+/// The returned xfrm policy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformPolicy {
+    pub selector: TransformSelector,
+    pub direction: TransformDirection,
+    pub priority: Option<u32>,
+    /// Eg. "main" or "sub".
+    pub policy_type: Option<String>,
+}
 
 #[derive(Clone)]
 pub struct IpTransformCommand<'l> {
@@ -46,9 +130,36 @@ impl<'l> IpTransformCommand<'l> {
         unimplemented!()
     }
 
-    /// Get existing state in xfrm.
-    pub async fn state_get(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Get existing state in xfrm, fails with `NotFoundError` when there is no such state.
+    pub async fn state_get(
+        &self,
+        source: &str,
+        destination: &str,
+        protocol: &str,
+        spi: u32,
+    ) -> Result<TransformState, Error> {
+        let args: Vec<String> = vec![
+            "xfrm".into(),
+            "state".into(),
+            "get".into(),
+            "src".into(),
+            source.into(),
+            "dst".into(),
+            destination.into(),
+            "proto".into(),
+            protocol.into(),
+            "spi".into(),
+            format!("{:#x}", spi),
+        ];
+        let output = match self.ip_command.command(&args, false, false, None).await {
+            Err(Error::CommandFailedError { stderr, .. }) if stderr.contains("No such process") => {
+                return Err(Error::NotFoundError {})
+            }
+            result => result?,
+        };
+        Self::parse_states(&output)?
+            .pop()
+            .context(UnexpectedOutputError { output })
     }
 
     /// Delete all existing state in xfrm.
@@ -57,18 +168,28 @@ impl<'l> IpTransformCommand<'l> {
     }
 
     /// Print out the list of existing state in xfrm.
-    pub async fn state_list(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn state_list(&self) -> Result<Vec<TransformState>, Error> {
+        let output = self
+            .ip_command
+            .command(&["xfrm", "state"], false, false, None)
+            .await?;
+        Self::parse_states(&output)
     }
 
     /// Flush all state in xfrm.
     pub async fn state_flush(&self) -> Result<(), Error> {
-        unimplemented!()
+        self.ip_command
+            .command_without_output(&["xfrm", "state", "flush"], None)
+            .await
     }
 
     /// Count all existing state in xfrm.
-    pub async fn state_count(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn state_count(&self) -> Result<u32, Error> {
+        let output = self
+            .ip_command
+            .command(&["xfrm", "state", "count"], false, false, None)
+            .await?;
+        Self::parse_state_count(&output)
     }
 
     /// Add a new policy.
@@ -86,9 +207,31 @@ impl<'l> IpTransformCommand<'l> {
         unimplemented!()
     }
 
-    /// Get an existing policy.
-    pub async fn policy_get(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Get an existing policy, fails with `NotFoundError` when there is no such policy.
+    pub async fn policy_get(
+        &self,
+        selector: TransformSelector,
+        direction: TransformDirection,
+    ) -> Result<TransformPolicy, Error> {
+        let mut args: Vec<String> = vec!["xfrm".into(), "policy".into(), "get".into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&selector)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        args.push("dir".into());
+        args.push(direction.to_string());
+        let output = match self.ip_command.command(&args, false, false, None).await {
+            Err(Error::CommandFailedError { stderr, .. })
+                if stderr.contains("No such file or directory") =>
+            {
+                return Err(Error::NotFoundError {})
+            }
+            result => result?,
+        };
+        Self::parse_policies(&output)?
+            .pop()
+            .context(UnexpectedOutputError { output })
     }
 
     /// Delete all existing xfrm policies.
@@ -103,12 +246,18 @@ impl<'l> IpTransformCommand<'l> {
 
     /// Flush policies.
     pub async fn policy_flush(&self) -> Result<(), Error> {
-        unimplemented!()
+        self.ip_command
+            .command_without_output(&["xfrm", "policy", "flush"], None)
+            .await
     }
 
     /// Count existing policies.
-    pub async fn policy_count(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn policy_count(&self) -> Result<TransformPolicyCount, Error> {
+        let output = self
+            .ip_command
+            .command(&["xfrm", "policy", "count"], false, false, None)
+            .await?;
+        Self::parse_policy_count(&output)
     }
 
     /// Configure the policy hash table.
@@ -116,8 +265,446 @@ impl<'l> IpTransformCommand<'l> {
         unimplemented!()
     }
 
-    /// State monitoring for xfrm objects.
-    pub async fn monitor(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Parse the state count (eg. "SAD count 42").
+    fn parse_state_count(output: &str) -> Result<u32, Error> {
+        output
+            .split_whitespace()
+            .skip_while(|token| *token != "count")
+            .nth(1)
+            .and_then(|count| count.parse().ok())
+            .context(UnexpectedOutputError { output })
+    }
+
+    /// Parse the policy count (eg. "SPD IN  0 OUT 1 FWD 0"), any subsequent counts of socket
+    /// policies and hash table details are ignored.
+    fn parse_policy_count(output: &str) -> Result<TransformPolicyCount, Error> {
+        let tokens: Vec<&str> = output.split_whitespace().collect();
+        let count = |direction: &str| {
+            tokens
+                .iter()
+                .position(|token| *token == direction)
+                .and_then(|position| tokens.get(position + 1))
+                .and_then(|count| count.parse().ok())
+                .context(UnexpectedOutputError { output })
+        };
+        Ok(TransformPolicyCount {
+            input: count("IN")?,
+            output: count("OUT")?,
+            forward: count("FWD")?,
+        })
+    }
+
+    /// Split text output into records, each starting with an unindented line (eg. "src ... dst ...").
+    fn records(output: &str) -> Vec<Vec<&str>> {
+        let mut records: Vec<Vec<&str>> = vec![];
+        for line in output.lines().filter(|line| !line.trim().is_empty()) {
+            match records.last_mut() {
+                Some(record) if line.starts_with(char::is_whitespace) => record.push(line),
+                _ => records.push(vec![line]),
+            }
+        }
+        records
+    }
+
+    /// The value following the first occurrence of the key.
+    fn value_of<'o>(tokens: &[&'o str], key: &str) -> Option<&'o str> {
+        tokens
+            .iter()
+            .position(|token| *token == key)
+            .and_then(|position| tokens.get(position + 1))
+            .copied()
+    }
+
+    /// Parse the states (a "src ... dst ..." line followed by indented details), only the
+    /// protocol, SPI, request id and mode of the details are parsed.
+    fn parse_states(output: &str) -> Result<Vec<TransformState>, Error> {
+        Self::records(output)
+            .into_iter()
+            .map(|record| {
+                let unexpected = || Error::UnexpectedOutputError {
+                    output: record.join("\n"),
+                };
+                let addresses: Vec<&str> = record[0].split_whitespace().collect();
+                // The selector of later lines has addresses of its own.
+                let details: Vec<&str> = record[1..]
+                    .iter()
+                    .flat_map(|line| line.split_whitespace())
+                    .collect();
+                let spi = Self::value_of(&details, "spi")
+                    .and_then(|spi| u32::from_str_radix(spi.trim_start_matches("0x"), 16).ok())
+                    .ok_or_else(unexpected)?;
+                Ok(TransformState {
+                    source: Self::value_of(&addresses, "src")
+                        .ok_or_else(unexpected)?
+                        .into(),
+                    destination: Self::value_of(&addresses, "dst")
+                        .ok_or_else(unexpected)?
+                        .into(),
+                    protocol: Self::value_of(&details, "proto")
+                        .ok_or_else(unexpected)?
+                        .into(),
+                    spi,
+                    request_id: Self::value_of(&details, "reqid").and_then(|id| id.parse().ok()),
+                    mode: Self::value_of(&details, "mode").map(String::from),
+                })
+            })
+            .collect()
+    }
+
+    /// Parse the policies (a selector line followed by indented details), the templates are
+    /// ignored.
+    fn parse_policies(output: &str) -> Result<Vec<TransformPolicy>, Error> {
+        Self::records(output)
+            .into_iter()
+            .map(|record| {
+                let unexpected = || Error::UnexpectedOutputError {
+                    output: record.join("\n"),
+                };
+                let selector: Vec<&str> = record[0].split_whitespace().collect();
+                let details: Vec<&str> = record
+                    .get(1)
+                    .map(|line| line.split_whitespace().collect())
+                    .unwrap_or_default();
+                Ok(TransformPolicy {
+                    selector: TransformSelector {
+                        source: Self::value_of(&selector, "src")
+                            .ok_or_else(unexpected)?
+                            .into(),
+                        destination: Self::value_of(&selector, "dst")
+                            .ok_or_else(unexpected)?
+                            .into(),
+                        protocol: Self::value_of(&selector, "proto").map(String::from),
+                        device: Self::value_of(&selector, "dev").map(String::from),
+                    },
+                    direction: Self::value_of(&details, "dir")
+                        .and_then(TransformDirection::from_keyword)
+                        .ok_or_else(unexpected)?,
+                    priority: Self::value_of(&details, "priority")
+                        .and_then(|priority| priority.parse().ok()),
+                    policy_type: Self::value_of(&details, "ptype").map(String::from),
+                })
+            })
+            .collect()
+    }
+
+    /// State monitoring for xfrm objects, each line of an event is streamed separately.
+    #[cfg(feature = "streaming")]
+    pub async fn monitor(&self) -> Result<ConsoleStream, Error> {
+        self.ip_command
+            .command_with_streaming_output(&["xfrm", "monitor"], false, false)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "streaming")]
+    use futures::StreamExt;
+    #[cfg(feature = "streaming")]
+    use std::time::Duration;
+    #[cfg(feature = "streaming")]
+    use tokio::time::{delay_for, timeout};
+
+    #[test]
+    fn test_parse_counts() {
+        assert_eq!(
+            IpTransformCommand::parse_state_count("\t SAD count 42\n").unwrap(),
+            42
+        );
+        // With statistics (-s) the hash table details follow.
+        assert_eq!(
+            IpTransformCommand::parse_state_count("\t SAD count 0 (buckets count 8 Max 1048576)\n")
+                .unwrap(),
+            0
+        );
+        assert!(IpTransformCommand::parse_state_count("").is_err());
+        assert!(IpTransformCommand::parse_state_count("\t SAD count\n").is_err());
+        assert_eq!(
+            IpTransformCommand::parse_policy_count(
+                "\t SPD IN  1 OUT 2 FWD 3 (Sock: IN 0 OUT 0 FWD 0)\n"
+            )
+            .unwrap(),
+            TransformPolicyCount {
+                input: 1,
+                output: 2,
+                forward: 3,
+            }
+        );
+        assert_eq!(
+            IpTransformCommand::parse_policy_count("\t SPD IN  0 OUT 0 FWD 0\n").unwrap(),
+            TransformPolicyCount::default()
+        );
+        assert!(IpTransformCommand::parse_policy_count("\t SPD IN  0 OUT 0\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_states() {
+        let states = IpTransformCommand::parse_states(concat!(
+            "src 192.0.2.10 dst 192.0.2.20\n",
+            "\tproto esp spi 0x00001000 reqid 1 mode transport\n",
+            "\treplay-window 0 \n",
+            "\tenc cbc(aes) 0x000102030405060708090a0b0c0d0e0f\n",
+            "\tsel src 0.0.0.0/0 dst 0.0.0.0/0 \n",
+            "src 2001:db8::1 dst 2001:db8::2\n",
+            "\tproto ah spi 0x00000201 mode tunnel\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            states,
+            vec![
+                TransformState {
+                    source: "192.0.2.10".into(),
+                    destination: "192.0.2.20".into(),
+                    protocol: "esp".into(),
+                    spi: 0x1000,
+                    request_id: Some(1),
+                    mode: Some("transport".into()),
+                },
+                TransformState {
+                    source: "2001:db8::1".into(),
+                    destination: "2001:db8::2".into(),
+                    protocol: "ah".into(),
+                    spi: 0x201,
+                    request_id: None,
+                    mode: Some("tunnel".into()),
+                },
+            ]
+        );
+        assert!(IpTransformCommand::parse_states("").unwrap().is_empty());
+        assert!(IpTransformCommand::parse_states("src 192.0.2.10 dst 192.0.2.20\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_policies() {
+        let policies = IpTransformCommand::parse_policies(concat!(
+            "src 10.0.0.0/24 dst 10.0.1.0/24 proto tcp \n",
+            "\tdir fwd priority 5 ptype main \n",
+            "\ttmpl src 192.0.2.10 dst 192.0.2.20\n",
+            "\t\tproto esp reqid 0 mode tunnel\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            policies,
+            vec![TransformPolicy {
+                selector: TransformSelector {
+                    source: "10.0.0.0/24".into(),
+                    destination: "10.0.1.0/24".into(),
+                    protocol: Some("tcp".into()),
+                    device: None,
+                },
+                direction: TransformDirection::Forward,
+                priority: Some(5),
+                policy_type: Some("main".into()),
+            }]
+        );
+        assert!(IpTransformCommand::parse_policies("src 10.0.0.0/24 dst 10.0.1.0/24\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let test_namespace = "ip-command-test-xfrm-get-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let selector = TransformSelector {
+            source: "10.0.0.0/24".into(),
+            destination: "10.0.1.0/24".into(),
+            protocol: Some("tcp".into()),
+            ..Default::default()
+        };
+        let args: Vec<String> = vec![
+            "xfrm",
+            "policy",
+            "add",
+            "src",
+            "10.0.0.0/24",
+            "dst",
+            "10.0.1.0/24",
+            "proto",
+            "tcp",
+            "dir",
+            "out",
+            "priority",
+            "5",
+            "tmpl",
+            "src",
+            "192.0.2.10",
+            "dst",
+            "192.0.2.20",
+            "proto",
+            "esp",
+            "mode",
+            "tunnel",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let result = namespace_client.command_without_output(&args, None).await;
+        let policy = namespace_client
+            .transform()
+            .policy_get(selector.clone(), TransformDirection::Output)
+            .await;
+        let missing_policy = namespace_client
+            .transform()
+            .policy_get(selector.clone(), TransformDirection::Input)
+            .await;
+        let missing_state = namespace_client
+            .transform()
+            .state_get("192.0.2.10", "192.0.2.20", "esp", 0x1000)
+            .await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        result.unwrap();
+        let policy = policy.unwrap();
+        assert_eq!(policy.selector, selector);
+        assert_eq!(policy.direction, TransformDirection::Output);
+        assert_eq!(policy.priority, Some(5));
+        assert!(matches!(missing_policy, Err(Error::NotFoundError {})));
+        assert!(matches!(missing_state, Err(Error::NotFoundError {})));
+    }
+
+    #[tokio::test]
+    async fn test_state_flush() {
+        let test_namespace = "ip-command-test-xfrm-flush-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let args: Vec<String> = vec![
+            "xfrm",
+            "state",
+            "add",
+            "src",
+            "192.0.2.10",
+            "dst",
+            "192.0.2.20",
+            "proto",
+            "esp",
+            "spi",
+            "0x1000",
+            "mode",
+            "transport",
+            "enc",
+            "cbc(aes)",
+            "0x000102030405060708090a0b0c0d0e0f",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let result = namespace_client.command_without_output(&args, None).await;
+        let states = namespace_client.transform().state_list().await;
+        let flushed = namespace_client.transform().state_flush().await;
+        let flushed_states = namespace_client.transform().state_list().await;
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        result.unwrap();
+        assert_eq!(states.unwrap()[0].spi, 0x1000);
+        flushed.unwrap();
+        assert!(flushed_states.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "streaming")]
+    #[tokio::test]
+    async fn test_policy_monitor_and_flush() {
+        let test_namespace = "ip-command-test-xfrm-monitor-namespace";
+        let client = IpCommand::new().unwrap();
+        client.netns().add(test_namespace).await.unwrap();
+
+        let namespace_client = client.with_namespace(test_namespace);
+        let mut events = namespace_client.transform().monitor().await.unwrap();
+        delay_for(Duration::from_millis(500)).await;
+        let args: Vec<String> = vec![
+            "xfrm",
+            "policy",
+            "add",
+            "src",
+            "10.0.0.0/24",
+            "dst",
+            "10.0.1.0/24",
+            "dir",
+            "out",
+            "tmpl",
+            "src",
+            "192.0.2.10",
+            "dst",
+            "192.0.2.20",
+            "proto",
+            "esp",
+            "mode",
+            "tunnel",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let result = namespace_client.command_without_output(&args, None).await;
+        let event = timeout(Duration::from_secs(2), events.next()).await;
+        let count = namespace_client.transform().policy_count().await;
+        let flushed = namespace_client.transform().policy_flush().await;
+        let flushed_count = namespace_client.transform().policy_count().await;
+        drop(events);
+
+        client.netns().delete(test_namespace).await.unwrap();
+
+        result.unwrap();
+        let event = event.unwrap().unwrap().unwrap();
+        assert_eq!(event.line.trim(), "src 10.0.0.0/24 dst 10.0.1.0/24");
+        assert_eq!(count.unwrap().output, 1);
+        flushed.unwrap();
+        assert_eq!(flushed_count.unwrap().output, 0);
+    }
+
+    #[tokio::test]
+    async fn test_state_count() {
+        let client = IpCommand::new().unwrap();
+        let states = [("0x1000", "192.0.2.20"), ("0x1001", "192.0.2.21")];
+        let state_args = |operation: &str, spi: &str, destination: &str| -> Vec<String> {
+            vec![
+                "xfrm",
+                "state",
+                operation,
+                "src",
+                "192.0.2.10",
+                "dst",
+                destination,
+                "proto",
+                "esp",
+                "spi",
+                spi,
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect()
+        };
+
+        for (spi, destination) in &states {
+            let mut args = state_args("add", spi, destination);
+            args.extend(
+                vec![
+                    "mode",
+                    "transport",
+                    "enc",
+                    "cbc(aes)",
+                    "0x000102030405060708090a0b0c0d0e0f",
+                ]
+                .into_iter()
+                .map(String::from),
+            );
+            client.command_without_output(&args, None).await.unwrap();
+        }
+
+        let count = client.transform().state_count().await;
+
+        for (spi, destination) in &states {
+            client
+                .command_without_output(&state_args("delete", spi, destination), None)
+                .await
+                .unwrap();
+        }
+
+        assert!(count.unwrap() >= states.len() as u32);
     }
 }
@@ -14,7 +14,144 @@
  * limitations under the License.
  */
 
-use crate::{Error, IpCommand};
+use crate::command::neighbor::deserialize_flag;
+use crate::*;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_command_opts::{BooleanType, Serializer};
+use snafu::ResultExt;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Mode of a tunnel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TunnelMode {
+    /// Generic routing encapsulation over IPv4.
+    Gre,
+    /// IPv4 over IPv4.
+    Ipip,
+    /// Intra-site automatic tunnel addressing protocol (IPv6 over IPv4).
+    Isatap,
+    /// Simple internet transition (IPv6 over IPv4).
+    Sit,
+    /// Virtual tunnel interface (for IPSec).
+    Vti,
+}
+
+impl fmt::Display for TunnelMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Gre => "gre",
+            Self::Ipip => "ipip",
+            Self::Isatap => "isatap",
+            Self::Sit => "sit",
+            Self::Vti => "vti",
+        })
+    }
+}
+
+impl Serialize for TunnelMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Add (or change) tunnel configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TunnelAddConfiguration {
+    /// Name of the tunnel device.
+    pub name: String,
+    /// Mode of the tunnel, required when adding.
+    pub mode: Option<TunnelMode>,
+    /// Remote endpoint of the tunnel.
+    pub remote: Option<String>,
+    /// Local endpoint of the tunnel.
+    pub local: Option<String>,
+    /// Time to live of the encapsulating packets (inherited when unset).
+    pub ttl: Option<u8>,
+    /// Type of service of the encapsulating packets (eg. "inherit" or "0x10").
+    pub tos: Option<String>,
+    /// Key of both received and sent packets (GRE only).
+    pub key: Option<String>,
+    /// Key of received packets (GRE only).
+    #[serde(rename = "ikey")]
+    pub input_key: Option<String>,
+    /// Key of sent packets (GRE only).
+    #[serde(rename = "okey")]
+    pub output_key: Option<String>,
+    /// Bind the tunnel to the device, so that packets are only routed via it.
+    #[serde(rename = "dev")]
+    pub device: Option<String>,
+    /// Disable path MTU discovery, incompatible with a fixed time to live.
+    #[serde(skip)]
+    pub no_path_mtu_discovery: bool,
+}
+
+pub type TunnelChangeConfiguration = TunnelAddConfiguration;
+
+/// IPv6 rapid deployment (6rd) configuration of a SIT tunnel.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SixRdConfiguration {
+    /// Name of the tunnel device.
+    #[serde(rename = "dev")]
+    pub device: String,
+    /// The IPv6 prefix of the provider (eg. "2001:db8::/32").
+    #[serde(rename = "6rd-prefix")]
+    pub prefix: Option<String>,
+    /// The IPv4 prefix common to the relays and customer edges of the provider (eg. "192.0.0.0/8").
+    #[serde(rename = "6rd-relay_prefix")]
+    pub relay_prefix: Option<String>,
+    /// Reset the prefixes to their defaults.
+    #[serde(skip)]
+    pub reset: bool,
+}
+
+/// The returned tunnel structure.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tunnel {
+    #[serde(rename = "ifname")]
+    pub name: String,
+    /// Encapsulated and encapsulating protocols (eg. "ipv6/ip" for a SIT tunnel).
+    pub mode: String,
+    pub remote: Option<String>,
+    pub local: Option<String>,
+    /// The device the tunnel is bound to.
+    #[serde(rename = "link")]
+    pub device: Option<String>,
+    /// Time to live of the encapsulating packets, none when inherited.
+    #[serde(default, deserialize_with = "deserialize_ttl")]
+    pub ttl: Option<u8>,
+    #[serde(rename = "nopmtudisc", default, deserialize_with = "deserialize_flag")]
+    pub no_path_mtu_discovery: bool,
+    pub key: Option<String>,
+    #[serde(rename = "ikey")]
+    pub input_key: Option<String>,
+    #[serde(rename = "okey")]
+    pub output_key: Option<String>,
+    /// The 6rd prefix (SIT tunnels only).
+    #[serde(rename = "prefix")]
+    pub six_rd_prefix: Option<String>,
+    #[serde(rename = "prefixlen")]
+    pub six_rd_prefix_length: Option<u8>,
+    /// The 6rd relay prefix (SIT tunnels only).
+    #[serde(rename = "relay_prefix")]
+    pub six_rd_relay_prefix: Option<String>,
+    #[serde(rename = "relay_prefixlen")]
+    pub six_rd_relay_prefix_length: Option<u8>,
+}
+
+/// The time to live is reported as "inherit" rather than zero.
+fn deserialize_ttl<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    serde_json::Value::deserialize(deserializer)?
+        .as_u64()
+        .map(|ttl| u8::try_from(ttl).map_err(serde::de::Error::custom))
+        .transpose()
+}
 
 #[derive(Clone)]
 pub struct IpTunnelCommand<'l> {
@@ -27,23 +164,42 @@ impl<'l> IpTunnelCommand<'l> {
     }
 
     /// Add a new tunnel.
-    pub async fn add(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn add(&self, configuration: TunnelAddConfiguration) -> Result<(), Error> {
+        self.modify("add", configuration).await
     }
 
-    /// Change an existing tunnel.
-    pub async fn change(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Change an existing tunnel, attributes left unset keep their current values.
+    pub async fn change(&self, configuration: TunnelChangeConfiguration) -> Result<(), Error> {
+        self.modify("change", configuration).await
+    }
+
+    async fn modify(&self, verb: &str, configuration: TunnelAddConfiguration) -> Result<(), Error> {
+        let mut args: Vec<String> = vec!["tunnel".into(), verb.into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        if configuration.no_path_mtu_discovery {
+            args.push("nopmtudisc".into());
+        }
+        self.ip_command.command_without_output(&args, None).await
     }
 
     /// Destroy a tunnel.
-    pub async fn delete(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn delete(&self, name: &str) -> Result<(), Error> {
+        let args: Vec<String> = vec!["tunnel".into(), "del".into(), name.into()];
+        self.ip_command.command_without_output(&args, None).await
     }
 
-    /// List tunnels.
-    pub async fn show(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// List tunnels, or only the named one.
+    pub async fn show(&self, name: Option<&str>) -> Result<Vec<Tunnel>, Error> {
+        let mut args: Vec<String> = vec!["tunnel".into(), "show".into()];
+        if let Some(name) = name {
+            args.push(name.into());
+        }
+        let output = self.ip_command.command(&args, true, false, None).await?;
+        serde_json::from_str(&output).context(JsonDeserializationError {})
     }
 
     /// Potential router list.
@@ -51,8 +207,130 @@ impl<'l> IpTunnelCommand<'l> {
         unimplemented!()
     }
 
-    /// Configure ipv6 rapid development (6rd) tunnel.
-    pub async fn ipv6_rapid_development(&self) -> Result<(), Error> {
-        unimplemented!()
+    /// Configure ipv6 rapid deployment (6rd) of a SIT tunnel.
+    pub async fn ipv6_rapid_development(
+        &self,
+        configuration: SixRdConfiguration,
+    ) -> Result<(), Error> {
+        let mut args: Vec<String> = vec!["tunnel".into(), "6rd".into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        if configuration.reset {
+            args.push("6rd-reset".into());
+        }
+        self.ip_command.command_without_output(&args, None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sit_six_rd() {
+        let tunnel_name = "test_sit0";
+        let client = IpCommand::new().unwrap();
+
+        client
+            .tunnel()
+            .add(TunnelAddConfiguration {
+                name: tunnel_name.into(),
+                mode: Some(TunnelMode::Sit),
+                remote: Some("192.0.2.1".into()),
+                local: Some("192.0.2.2".into()),
+                ttl: Some(64),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let result = client
+            .tunnel()
+            .ipv6_rapid_development(SixRdConfiguration {
+                device: tunnel_name.into(),
+                prefix: Some("2001:db8::/32".into()),
+                relay_prefix: Some("192.0.0.0/8".into()),
+                ..Default::default()
+            })
+            .await;
+        let tunnels = client.tunnel().show(Some(tunnel_name)).await;
+
+        client.tunnel().delete(tunnel_name).await.unwrap();
+
+        result.unwrap();
+        let tunnel = tunnels.unwrap().pop().unwrap();
+        assert_eq!(tunnel.name, tunnel_name);
+        assert_eq!(tunnel.mode, "ipv6/ip");
+        assert_eq!(tunnel.remote, Some("192.0.2.1".into()));
+        assert_eq!(tunnel.ttl, Some(64));
+        assert_eq!(tunnel.six_rd_prefix, Some("2001:db8::".into()));
+        assert_eq!(tunnel.six_rd_prefix_length, Some(32));
+        assert_eq!(tunnel.six_rd_relay_prefix, Some("192.0.0.0".into()));
+        assert_eq!(tunnel.six_rd_relay_prefix_length, Some(8));
+    }
+
+    #[tokio::test]
+    async fn test_change_and_reset() {
+        let client = IpCommand::new().unwrap().dry_run();
+        client
+            .tunnel()
+            .change(TunnelChangeConfiguration {
+                name: "sit1".into(),
+                remote: Some("192.0.2.3".into()),
+                ttl: Some(32),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        client
+            .tunnel()
+            .ipv6_rapid_development(SixRdConfiguration {
+                device: "sit1".into(),
+                reset: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            client.recorded_commands(),
+            vec![
+                vec![
+                    "tunnel",
+                    "change",
+                    "name",
+                    "sit1",
+                    "remote",
+                    "192.0.2.3",
+                    "ttl",
+                    "32"
+                ],
+                vec!["tunnel", "6rd", "dev", "sit1", "6rd-reset"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_inherited_ttl() {
+        let tunnels: Vec<Tunnel> = serde_json::from_str(
+            r#"[{"ifname":"sit0","mode":"ipv6/ip","remote":"any","local":"any","ttl":"inherit","nopmtudisc":null}]"#,
+        )
+        .unwrap();
+        assert_eq!(tunnels[0].ttl, None);
+        assert!(tunnels[0].no_path_mtu_discovery);
+    }
+
+    #[test]
+    fn test_deserialize_out_of_range_ttl() {
+        let tunnels: Vec<Tunnel> = serde_json::from_str(
+            r#"[{"ifname":"gre1","mode":"gre/ip","remote":"10.0.0.1","local":"10.0.0.2","ttl":64}]"#,
+        )
+        .unwrap();
+        assert_eq!(tunnels[0].ttl, Some(64));
+        assert!(serde_json::from_str::<Vec<Tunnel>>(
+            r#"[{"ifname":"gre1","mode":"gre/ip","remote":"10.0.0.1","local":"10.0.0.2","ttl":300}]"#,
+        )
+        .is_err());
     }
 }
@@ -14,7 +14,82 @@
  * limitations under the License.
  */
 
-use crate::{Error, IpCommand};
+use crate::*;
+use serde::{Deserialize, Serialize};
+use serde_command_opts::{BooleanType, Serializer};
+use snafu::ResultExt;
+use std::fmt;
+
+/// Mode of a TUN/TAP device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum TunTapMode {
+    /// Layer 3 (IP packet) device.
+    #[default]
+    Tun,
+    /// Layer 2 (ethernet frame) device.
+    Tap,
+}
+
+impl fmt::Display for TunTapMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tun => "tun",
+            Self::Tap => "tap",
+        })
+    }
+}
+
+impl Serialize for TunTapMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Add TUN/TAP device configuration, the device is persistent.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TunTapAddConfiguration {
+    /// Name of the device.
+    #[serde(rename = "dev")]
+    pub name: String,
+    /// Mode of the device.
+    pub mode: TunTapMode,
+    /// The user (name or id) allowed to attach to the device.
+    pub user: Option<String>,
+    /// The group (name or id) allowed to attach to the device.
+    pub group: Option<String>,
+    /// Prefix packets with packet information (flags and protocol).
+    #[serde(skip)]
+    pub packet_info: bool,
+    /// Prefix packets with a virtio net header.
+    #[serde(skip)]
+    pub vnet_header: bool,
+    /// Allow attaching a queue per file descriptor.
+    #[serde(skip)]
+    pub multi_queue: bool,
+}
+
+/// Delete TUN/TAP device configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TunTapDeleteConfiguration {
+    /// Name of the device.
+    #[serde(rename = "dev")]
+    pub name: String,
+    /// Mode of the device.
+    pub mode: TunTapMode,
+}
+
+/// The returned TUN/TAP device structure.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TunTap {
+    #[serde(rename = "ifname")]
+    pub name: String,
+    /// The mode and flags of the device (eg. "tun", "persist").
+    #[serde(default)]
+    pub flags: Vec<String>,
+}
 
 #[derive(Clone)]
 pub struct IpTunTapCommand<'l> {
@@ -27,17 +102,143 @@ impl<'l> IpTunTapCommand<'l> {
     }
 
     /// Add a new TAP/TUN device.
-    pub async fn add(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn add(&self, configuration: TunTapAddConfiguration) -> Result<(), Error> {
+        let mut args: Vec<String> = vec!["tuntap".into(), "add".into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        for (flag, set) in &[
+            ("pi", configuration.packet_info),
+            ("vnet_hdr", configuration.vnet_header),
+            ("multi_queue", configuration.multi_queue),
+        ] {
+            if *set {
+                args.push((*flag).into());
+            }
+        }
+        self.ip_command.command_without_output(&args, None).await
     }
 
     /// Delete an existing TAP/TUN device.
-    pub async fn delete(&self) -> Result<(), Error> {
-        unimplemented!()
+    ///
+    /// Only the persistence of the device is removed, it remains until closed by any process
+    /// attached to it.
+    pub async fn delete(&self, configuration: TunTapDeleteConfiguration) -> Result<(), Error> {
+        let mut args: Vec<String> = vec!["tuntap".into(), "del".into()];
+        args.append(
+            &mut Serializer::new(BooleanType::OnOff)
+                .into_args(&configuration)
+                .context(CommandOptionsSerializationError {})?,
+        );
+        self.ip_command.command_without_output(&args, None).await
+    }
+
+    /// Make an existing TAP/TUN device persistent, or not. A device which is not persistent
+    /// disappears once no process is attached to it.
+    ///
+    /// A device can only be made persistent (again) while no process is attached to it.
+    pub async fn set_persist(
+        &self,
+        name: &str,
+        mode: TunTapMode,
+        persist: bool,
+    ) -> Result<(), Error> {
+        if persist {
+            self.add(TunTapAddConfiguration {
+                name: name.into(),
+                mode,
+                ..Default::default()
+            })
+            .await
+        } else {
+            self.delete(TunTapDeleteConfiguration {
+                name: name.into(),
+                mode,
+            })
+            .await
+        }
     }
 
     /// List all TAP/TUN devices.
-    pub async fn show(&self) -> Result<(), Error> {
-        unimplemented!()
+    pub async fn show(&self) -> Result<Vec<TunTap>, Error> {
+        let output = self
+            .ip_command
+            .command(&["tuntap", "show"], true, false, None)
+            .await?;
+        serde_json::from_str(&output).context(JsonDeserializationError {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_set_persist() {
+        let link_name = "test_tun0";
+
+        let client = IpCommand::new().unwrap();
+        client
+            .tuntap()
+            .add(TunTapAddConfiguration {
+                name: link_name.into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let persistent = client.tuntap().show().await.unwrap();
+
+        client
+            .tuntap()
+            .set_persist(link_name, TunTapMode::Tun, false)
+            .await
+            .unwrap();
+        let removed = client.tuntap().show().await.unwrap();
+
+        let find = |devices: &[TunTap]| {
+            devices
+                .iter()
+                .find(|device| device.name == link_name)
+                .cloned()
+        };
+        let device = find(&persistent).unwrap();
+        assert!(device.flags.contains(&"tun".to_string()));
+        assert!(device.flags.contains(&"persist".to_string()));
+        // No process was attached, so the device is gone.
+        assert!(find(&removed).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_add_flags() {
+        let client = IpCommand::new().unwrap().dry_run();
+        client
+            .tuntap()
+            .add(TunTapAddConfiguration {
+                name: "tap0".into(),
+                mode: TunTapMode::Tap,
+                user: Some("1000".into()),
+                vnet_header: true,
+                multi_queue: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            client.recorded_commands(),
+            vec![vec![
+                "tuntap",
+                "add",
+                "dev",
+                "tap0",
+                "mode",
+                "tap",
+                "user",
+                "1000",
+                "vnet_hdr",
+                "multi_queue"
+            ]]
+        );
     }
 }
@@ -16,31 +16,82 @@
 
 //! A Rust wrapper around the Linux ip(8) command. Show / manipulate routing, network devices, interfaces and tunnels.
 
+// The serde and snafu derives expand to code that newer compilers lint against.
+#![allow(unknown_lints, non_local_definitions, unexpected_cfgs)]
+
+use crate::command::link::{LinkDeviceOrGroup, LinkShowConfiguration};
+use crate::command::route::RouteCache;
 use crate::command::*;
-use futures::ready;
+#[cfg(feature = "streaming")]
+use futures::channel::mpsc;
+use futures::channel::oneshot;
+#[cfg(feature = "streaming")]
+use futures::future::Either;
+use futures::future::{self, FutureExt, Shared};
+#[cfg(feature = "streaming")]
 use futures::task::{Context, Poll};
+#[cfg(feature = "streaming")]
 use futures::Stream;
+#[cfg(feature = "streaming")]
+use futures::{executor, ready, SinkExt};
+#[cfg(feature = "streaming")]
+use serde::de::{DeserializeOwned, SeqAccess, Visitor};
+#[cfg(feature = "streaming")]
+use serde::{Deserialize, Deserializer};
 use snafu::{ensure, OptionExt, ResultExt, Snafu};
-use std::iter::FromIterator;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::future::Future;
+#[cfg(feature = "streaming")]
+use std::io::Read;
+use std::iter;
+use std::num::NonZeroUsize;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "streaming")]
 use std::pin::Pin;
-use std::process::Stdio;
+use std::process::{ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::{env, io};
+#[cfg(feature = "streaming")]
 use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncWriteExt;
+#[cfg(feature = "streaming")]
 use tokio::io::BufReader;
+#[cfg(feature = "streaming")]
 use tokio::process::Child;
 use tokio::process::Command;
+#[cfg(feature = "streaming")]
 use tokio::stream::StreamExt;
-use tokio::time::timeout;
+use tokio::sync::Semaphore;
+#[cfg(feature = "streaming")]
+use tokio::task;
+use tokio::time::{timeout, timeout_at, Instant};
 
+/// Blocking (synchronous) ip(8) command client.
+pub mod blocking;
 /// Various ip(8) subcommands.
 pub mod command;
 
 /// Errors produced by the ip(8) command client.
 #[derive(Debug, Snafu)]
 pub enum Error {
+    #[snafu(display(
+        "Batch command failed at line {} ({} applied), stderr: \"{}\"",
+        line,
+        applied,
+        stderr
+    ))]
+    BatchFailedError {
+        line: usize,
+        applied: usize,
+        stderr: String,
+    },
+
+    #[snafu(display("Ip command cancelled"))]
+    CancelledError {},
+
     #[snafu(display("Ip command error: {}", source))]
     CommandError { source: io::Error },
 
@@ -56,11 +107,159 @@ pub enum Error {
     #[snafu(display("Ip command timed out: {}", source))]
     CommandTimeoutError { source: tokio::time::Elapsed },
 
+    #[snafu(display("Conflicting arguments {} and {}", field, conflicting_field))]
+    ConflictingConfigurationError {
+        field: String,
+        conflicting_field: String,
+    },
+
     #[snafu(display("Failed to deserialize json: {}", source))]
     JsonDeserializationError { source: serde_json::Error },
 
+    #[snafu(display("Ip command does not support json output: \"{}\"", version))]
+    JsonUnsupportedError { version: String },
+
+    #[snafu(display("Invalid argument {}: \"{}\"", field, value))]
+    InvalidArgumentError { field: String, value: String },
+
+    #[snafu(display("Network namespace not found: {}", name))]
+    NamespaceNotFoundError { name: String },
+
+    #[snafu(display("No matching object was found"))]
+    NotFoundError {},
+
+    #[snafu(display("Address {} overlaps {} on {}", address, existing, device))]
+    OverlappingAddressError {
+        address: String,
+        existing: String,
+        device: String,
+    },
+
+    #[snafu(display(
+        "Ip command not permitted (requires CAP_NET_ADMIN), stderr: \"{}\"",
+        stderr
+    ))]
+    PermissionDeniedError { stderr: String },
+
+    #[snafu(display("Refused unsafe operation: {}", operation))]
+    RefusedUnsafeOperationError { operation: String },
+
+    #[snafu(display("Rollback failed: {}, after: {}", rollback_error, error))]
+    RollbackFailedError {
+        error: Box<Error>,
+        rollback_error: Box<Error>,
+    },
+
+    #[snafu(display("Unable to start runtime: {}", source))]
+    RuntimeError { source: io::Error },
+
     #[snafu(display("Unable to spawn process: {}", source))]
     SpawnError { source: io::Error },
+
+    #[snafu(display("Unable to write kernel parameter {}: {}", key, source))]
+    SysctlError { key: String, source: io::Error },
+
+    #[snafu(display("Unexpected command output: \"{}\"", output))]
+    UnexpectedOutputError { output: String },
+
+    #[snafu(display("Timed out waiting for condition: {}", source))]
+    WaitTimeoutError { source: tokio::time::Elapsed },
+}
+
+/// The first iproute2 snapshot (and release) with json output for all of the commonly used
+/// subcommands.
+const JSON_OUTPUT_SNAPSHOT: u32 = 180_129;
+const JSON_OUTPUT_RELEASE: (u32, u32) = (4, 15);
+
+/// Devices of the network namespace of the process, by name.
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// The size of a device name buffer in the kernel, including the terminating nul.
+const IFNAMSIZ: usize = 16;
+
+/// The version of the ip(8) command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IpVersion {
+    /// The raw version string (eg. "ip utility, iproute2-ss200127").
+    pub raw: String,
+    /// The iproute2 release (eg. "ss200127" or "6.1.0").
+    pub iproute2: Option<String>,
+    /// The iproute2 snapshot date (eg. 200127), only reported by older releases.
+    pub snapshot: Option<u32>,
+}
+
+impl IpVersion {
+    fn parse(raw: &str) -> Self {
+        let raw = raw.trim();
+        let iproute2 = raw
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .find_map(|token| token.strip_prefix("iproute2-"))
+            .map(String::from);
+        let snapshot = iproute2
+            .as_ref()
+            .and_then(|iproute2| iproute2.strip_prefix("ss"))
+            .and_then(|snapshot| snapshot.parse().ok());
+        Self {
+            raw: raw.into(),
+            iproute2,
+            snapshot,
+        }
+    }
+
+    /// The (major, minor) iproute2 release, only reported by newer releases.
+    fn release(&self) -> Option<(u32, u32)> {
+        let mut numbers = self.iproute2.as_ref()?.split('.').map(|n| n.parse().ok());
+        Some((numbers.next()??, numbers.next()??))
+    }
+
+    /// Was this release made after the given snapshot / release (whichever it reports)?
+    /// Unrecognized versions are assumed to be recent.
+    fn is_at_least(&self, snapshot: u32, release: (u32, u32)) -> bool {
+        match (self.snapshot, self.release()) {
+            (Some(current), _) => current >= snapshot,
+            (None, Some(current)) => current >= release,
+            (None, None) => true,
+        }
+    }
+}
+
+/// A network device along with its protocol addresses (see `IpCommand::interfaces`).
+#[derive(Clone, Debug)]
+pub struct Interface {
+    pub index: u32,
+    pub name: String,
+    pub mtu: u32,
+    /// The operational state (eg. "UP", "DOWN" or "UNKNOWN").
+    pub state: String,
+    /// The link layer address, none for devices without one (eg. a TUN device).
+    pub mac: Option<String>,
+    pub addresses: Vec<address::AddressInfo>,
+}
+
+impl From<address::Interface> for Interface {
+    fn from(interface: address::Interface) -> Self {
+        Self {
+            index: interface.link.interface_index,
+            name: interface.link.name,
+            mtu: interface.link.mtu,
+            state: interface.link.state,
+            mac: interface.link.address,
+            addresses: interface.addresses,
+        }
+    }
+}
+
+/// Optional functionality of the ip(8) command, which depends upon the iproute2 release.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Feature {
+    /// Json output (`-json`) for all of the commonly used subcommands, since iproute2 4.15.
+    JsonOutput,
+    /// Label configuration for protocol address selection (`ip addrlabel`).
+    AddressLabel,
+    /// IPSec policies (`ip xfrm`).
+    Transform,
+    /// Virtual routing and forwarding devices (`ip vrf`).
+    Vrf,
 }
 
 /// Ip(8) command client.
@@ -69,6 +268,136 @@ pub struct IpCommand {
     command: PathBuf,
     timeout: Duration,
     namespace: Option<String>,
+    dry_run: Option<DryRun>,
+    route_cache: Option<Arc<Mutex<RouteCache>>>,
+    cancellation: Option<CancellationToken>,
+    concurrency: Option<Arc<Semaphore>>,
+    elevation: Elevation,
+    oneline: bool,
+    environment: BTreeMap<String, String>,
+    /// The version of the command, once a command requiring json output has looked it up.
+    version: Arc<Mutex<Option<IpVersion>>>,
+}
+
+/// Commands recorded, rather than executed, by a dry run client.
+#[derive(Clone, Default)]
+struct DryRun {
+    commands: Arc<Mutex<Vec<Vec<String>>>>,
+    output: Vec<u8>,
+}
+
+impl DryRun {
+    fn record(&self, args: &[&str]) {
+        let args = args.iter().map(|arg| (*arg).to_owned()).collect();
+        self.commands.lock().unwrap().push(args);
+    }
+}
+
+/// How commands are run with the privilege (CAP_NET_ADMIN) most of them require.
+///
+/// The elevating program is given the full command line, so allowing it for an unprivileged
+/// user grants all of ip(8), including running arbitrary programs (`netns exec`). Restrict any
+/// sudoers rule to the absolute path of ip(8) (and bridge(8), stdbuf(1) as used). It must not
+/// prompt, as commands have no terminal, and may discard the environment of the client.
+/// Kernel parameters written directly (eg. `set_stable_secret` outside a namespace) are not
+/// elevated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Elevation {
+    /// Run commands directly.
+    #[default]
+    None,
+    /// Run commands with `sudo -n` (failing rather than prompting for a password).
+    Sudo,
+    /// Run commands prefixed with this program and its arguments (eg. `["pkexec"]`).
+    Custom(Vec<String>),
+}
+
+/// Cancels the commands of every client it was given to (see `IpCommand::with_cancellation`),
+/// once cancelled it remains so.
+#[derive(Clone)]
+pub struct CancellationToken {
+    sender: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    cancelled: Shared<oneshot::Receiver<()>>,
+}
+
+impl CancellationToken {
+    /// Create a new, not yet cancelled, token.
+    pub fn new() -> Self {
+        let (sender, receiver) = oneshot::channel();
+        Self {
+            sender: Arc::new(Mutex::new(Some(sender))),
+            cancelled: receiver.shared(),
+        }
+    }
+
+    /// Kill the running commands of the clients given this token, and fail any further ones.
+    pub fn cancel(&self) {
+        if let Some(sender) = self.sender.lock().unwrap().take() {
+            let _ = sender.send(());
+        }
+    }
+
+    /// Has the token been cancelled?
+    pub fn is_cancelled(&self) -> bool {
+        self.sender.lock().unwrap().is_none()
+    }
+
+    /// Resolves once the token is cancelled.
+    async fn cancelled(&self) {
+        // The sender can't be dropped without sending, it is owned by the token.
+        let _ = self.cancelled.clone().await;
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Tracing of a spawned command (with the `tracing` feature), a debug event with the full
+/// argv on spawn and a span recording the namespace, duration and exit status.
+struct CommandTrace {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    started: std::time::Instant,
+}
+
+impl CommandTrace {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn spawn(program: &Path, args: &[&str], namespace: Option<&str>) -> Self {
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::debug_span!(
+                "command",
+                namespace,
+                duration_ms = tracing::field::Empty,
+                status = tracing::field::Empty
+            );
+            span.in_scope(|| tracing::debug!(program = %program.display(), ?args, "spawn"));
+            Self {
+                span,
+                started: std::time::Instant::now(),
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        Self {}
+    }
+
+    /// Record the exit status, none if the command could not be waited on (eg. timed out).
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn exit(&self, status: Option<std::process::ExitStatus>) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span
+                .record("duration_ms", self.started.elapsed().as_millis() as u64);
+            if let Some(status) = status {
+                self.span.record("status", tracing::field::display(status));
+            }
+            self.span.in_scope(|| tracing::debug!(?status, "exit"));
+        }
+    }
 }
 
 impl IpCommand {
@@ -78,156 +407,555 @@ impl IpCommand {
             command: Self::path("ip").context(CommandNotFoundError {})?,
             timeout: Duration::from_millis(5_000),
             namespace: None,
+            dry_run: None,
+            route_cache: None,
+            cancellation: None,
+            concurrency: None,
+            elevation: Elevation::None,
+            oneline: false,
+            // Keep messages (eg. "RTNETLINK answers: ...") and number formatting stable.
+            environment: vec![("LC_ALL".into(), "C".into())].into_iter().collect(),
+            version: Arc::new(Mutex::new(None)),
         })
     }
 
+    /// Create a new blocking ip(8) command client (see the `blocking` module).
+    pub fn new_blocking() -> Result<blocking::IpCommand, Error> {
+        blocking::IpCommand::new()
+    }
+
     /// Return the current version of the ip(8) command.
     pub async fn version(&self) -> Result<String, Error> {
-        self.command(&["-Version".into()], false, None).await
+        self.command(&["-Version"], false, false, None).await
+    }
+
+    /// Run ip(8) with arbitrary arguments, writing `stdin` to its standard input (eg.
+    /// `["-batch", "-"]` and a script). The global options of the client (eg. the namespace)
+    /// apply, the output is returned as is.
+    pub async fn raw_with_stdin(&self, args: &[&str], stdin: Vec<u8>) -> Result<String, Error> {
+        self.command(args, false, false, Some(stdin)).await
+    }
+
+    /// Return the current version of the ip(8) command, parsed.
+    pub async fn version_info(&self) -> Result<IpVersion, Error> {
+        Ok(IpVersion::parse(&self.version().await?))
+    }
+
+    /// Does the ip(8) command support the given feature? Decided by the version of the command,
+    /// or for subcommands, by whether the command recognizes them.
+    pub async fn supports(&self, feature: Feature) -> Result<bool, Error> {
+        match feature {
+            Feature::JsonOutput => Ok(self
+                .version_info()
+                .await?
+                .is_at_least(JSON_OUTPUT_SNAPSHOT, JSON_OUTPUT_RELEASE)),
+            Feature::AddressLabel => self.supports_object("addrlabel").await,
+            Feature::Transform => self.supports_object("xfrm").await,
+            Feature::Vrf => self.supports_object("vrf").await,
+        }
+    }
+
+    /// Wait for an arbitrary condition (eg. a route appearing), polled every `interval` until it
+    /// returns a value or until `wait_timeout` has elapsed. The condition is given a client
+    /// equivalent to this one, any error it returns ends the wait.
+    pub async fn wait_for<F, Fut, T>(
+        &self,
+        interval: Duration,
+        wait_timeout: Duration,
+        mut condition: F,
+    ) -> Result<T, Error>
+    where
+        F: FnMut(IpCommand) -> Fut,
+        Fut: Future<Output = Result<Option<T>, Error>>,
+    {
+        poll_until(interval, wait_timeout, || condition(self.clone())).await
+    }
+
+    /// Subcommands print their usage (and fail) when asked for help, unknown ones complain instead.
+    async fn supports_object(&self, object: &str) -> Result<bool, Error> {
+        match self.command(&[object, "help"], false, true, None).await {
+            Ok(_) => Ok(true),
+            Err(Error::CommandFailedError { stdout, stderr }) => {
+                Ok(!(stdout.contains("is unknown") || stderr.contains("is unknown")))
+            }
+            Err(error) => Err(error),
+        }
     }
 
     /// Create a new ip(8) command client for the specified network namespace.
+    ///
+    /// Commands which enter a namespace by other means (eg. `netns exec`) are not additionally
+    /// wrapped with `-netns`, the namespace they name explicitly takes precedence.
     pub fn with_namespace(&self, namespace: &str) -> Self {
         let mut instance = self.clone();
         instance.namespace = Some(namespace.into());
         instance
     }
 
+    /// Create a new ip(8) command client which sets the environment variable for every command
+    /// (`LC_ALL=C` is set by default).
+    pub fn with_env(&self, key: &str, value: &str) -> Self {
+        let mut instance = self.clone();
+        instance.environment.insert(key.into(), value.into());
+        instance
+    }
+
+    /// Create a new ip(8) command client whose commands are cancelled by the token (failing with
+    /// `CancelledError`), the running process is killed. Streaming output simply ends.
+    pub fn with_cancellation(&self, cancellation: &CancellationToken) -> Self {
+        let mut instance = self.clone();
+        instance.cancellation = Some(cancellation.clone());
+        instance
+    }
+
+    /// Create a new ip(8) command client which records the arguments of each command instead of
+    /// executing it. Every command succeeds with empty output, so commands which parse their
+    /// output will fail to deserialize.
+    pub fn dry_run(&self) -> Self {
+        let mut instance = self.clone();
+        instance.dry_run = Some(DryRun::default());
+        instance
+    }
+
+    /// Create a new dry run client whose commands all produce the given output.
+    #[cfg(test)]
+    pub(crate) fn dry_run_with_output(&self, output: &str) -> Self {
+        let mut instance = self.clone();
+        instance.dry_run = Some(DryRun {
+            output: output.as_bytes().to_vec(),
+            ..Default::default()
+        });
+        instance
+    }
+
+    /// Create a new ip(8) command client which caches up to `capacity` resolved routes
+    /// (see `route().get()`) for at most `time_to_live`. The cache is shared with any clients
+    /// derived from this one (routes are cached per network namespace), and cleared by any
+    /// route modification through them.
+    pub fn with_route_cache(&self, capacity: usize, time_to_live: Duration) -> Self {
+        let mut instance = self.clone();
+        instance.route_cache = Some(Arc::new(Mutex::new(RouteCache::new(
+            capacity,
+            time_to_live,
+        ))));
+        instance
+    }
+
+    /// Create a new ip(8) command client which runs every command (including streaming ones)
+    /// with elevated privilege, see `Elevation` for the security considerations.
+    pub fn with_elevation(&self, elevation: Elevation) -> Self {
+        let mut instance = self.clone();
+        instance.elevation = elevation;
+        instance
+    }
+
+    /// Create a new ip(8) command client which lists links and addresses with one line per record
+    /// (`-oneline`) rather than json, for releases lacking json output (see
+    /// `supports(Feature::JsonOutput)`). Only the basic attributes are parsed, other commands
+    /// still require json output.
+    pub fn with_oneline(&self, oneline: bool) -> Self {
+        let mut instance = self.clone();
+        instance.oneline = oneline;
+        instance
+    }
+
+    /// Are links and addresses listed from `-oneline` output?
+    pub(crate) fn is_oneline(&self) -> bool {
+        self.oneline
+    }
+
+    /// Create a new ip(8) command client which runs at most `max_concurrency` commands at once,
+    /// further commands wait for one to exit (the wait counts towards their timeout, and is
+    /// cancellable). The limit is shared with any clients derived from this one, it does not apply
+    /// to streaming output (eg. monitor).
+    pub fn with_max_concurrency(&self, max_concurrency: NonZeroUsize) -> Self {
+        let mut instance = self.clone();
+        instance.concurrency = Some(Arc::new(Semaphore::new(max_concurrency.get())));
+        instance
+    }
+
+    /// The arguments (including global options) of every command issued by a dry run client.
+    pub fn recorded_commands(&self) -> Vec<Vec<String>> {
+        self.dry_run
+            .as_ref()
+            .map(|dry_run| dry_run.commands.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
     /// Network device.
-    pub fn link(&self) -> IpLinkCommand {
+    pub fn link(&self) -> IpLinkCommand<'_> {
         IpLinkCommand::new(self)
     }
 
     /// Protocol (IP or IPv6) address on a device.
-    pub fn address(&self) -> IpAddressCommand {
+    pub fn address(&self) -> IpAddressCommand<'_> {
         IpAddressCommand::new(self)
     }
 
+    /// Every device along with its protocol addresses, from a single command (rather than
+    /// querying links and addresses separately).
+    pub async fn snapshot(&self) -> Result<Vec<address::Interface>, Error> {
+        self.address().interfaces().await
+    }
+
+    /// Every device (its index, name, MTU, state and MAC address) along with its protocol
+    /// addresses, from a single command. See `snapshot` for all of the device attributes.
+    pub async fn interfaces(&self) -> Result<Vec<Interface>, Error> {
+        Ok(self
+            .snapshot()
+            .await?
+            .into_iter()
+            .map(Interface::from)
+            .collect())
+    }
+
+    /// The index of the named device. Outside a network namespace (or a dry run) it is read from
+    /// sysfs, rather than spawning ip(8), fails with `NotFoundError` when there is no such device.
+    pub async fn index_of(&self, name: &str) -> Result<u32, Error> {
+        if !self.reads_sysfs() {
+            let result = self
+                .link()
+                .show(Some(LinkShowConfiguration {
+                    device: LinkDeviceOrGroup::Device(name.into()),
+                    ..Default::default()
+                }))
+                .await;
+            return match result {
+                Ok(links) => links
+                    .first()
+                    .map(|link| link.interface_index)
+                    .context(NotFoundError {}),
+                Err(Error::CommandFailedError { stderr, .. })
+                    if IpLinkCommand::device_missing(&stderr) =>
+                {
+                    Err(Error::NotFoundError {})
+                }
+                Err(error) => Err(error),
+            };
+        }
+        Self::ensure_device_name("name", name)?;
+        match Self::read_interface_index(&Path::new(SYS_CLASS_NET).join(name)).await {
+            Err(Error::CommandError { source }) if source.kind() == io::ErrorKind::NotFound => {
+                Err(Error::NotFoundError {})
+            }
+            result => result,
+        }
+    }
+
+    /// The name of the device with the index. Outside a network namespace (or a dry run) it is
+    /// read from sysfs, rather than spawning ip(8), fails with `NotFoundError` when there is no
+    /// such device.
+    pub async fn name_of(&self, index: u32) -> Result<String, Error> {
+        if !self.reads_sysfs() {
+            return self
+                .link()
+                .show(None)
+                .await?
+                .into_iter()
+                .find(|link| link.interface_index == index)
+                .map(|link| link.name)
+                .context(NotFoundError {});
+        }
+        let mut entries = tokio::fs::read_dir(SYS_CLASS_NET)
+            .await
+            .context(CommandError {})?;
+        while let Some(entry) = entries.next_entry().await.context(CommandError {})? {
+            // A device may be removed while listing.
+            if let Ok(entry_index) = Self::read_interface_index(&entry.path()).await {
+                if entry_index == index {
+                    return Ok(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+        Err(Error::NotFoundError {})
+    }
+
+    /// Sysfs only reflects the devices of this network namespace, and reading it would bypass
+    /// the commands recorded by a dry run.
+    fn reads_sysfs(&self) -> bool {
+        self.namespace.is_none() && self.dry_run.is_none()
+    }
+
+    /// Device names can't contain a slash, nor be a relative path component, and fit IFNAMSIZ
+    /// (including the terminating nul), so a valid name is safe to join to a sysfs or procfs path.
+    pub(crate) fn ensure_device_name(field: &str, name: &str) -> Result<(), Error> {
+        ensure!(
+            !name.is_empty()
+                && name.len() < IFNAMSIZ
+                && !name.contains('/')
+                && name != "."
+                && name != "..",
+            InvalidArgumentError { field, value: name }
+        );
+        Ok(())
+    }
+
+    async fn read_interface_index(device_path: &Path) -> Result<u32, Error> {
+        let index = tokio::fs::read_to_string(device_path.join("ifindex"))
+            .await
+            .context(CommandError {})?;
+        index
+            .trim()
+            .parse()
+            .map_err(|_| Error::UnexpectedOutputError { output: index })
+    }
+
     /// Label configuration for protocol address selection.
-    pub fn address_label(&self) -> IpAddressLabelCommand {
+    pub fn address_label(&self) -> IpAddressLabelCommand<'_> {
         IpAddressLabelCommand::new(self)
     }
 
     /// Routing table entry.
-    pub fn route(&self) -> IpRouteCommand {
+    pub fn route(&self) -> IpRouteCommand<'_> {
         IpRouteCommand::new(self)
     }
 
     /// Rule in routing policy database.
-    pub fn rule(&self) -> IpRuleCommand {
+    pub fn rule(&self) -> IpRuleCommand<'_> {
         IpRuleCommand::new(self)
     }
 
     /// Manage ARP or NDISC cache entries.
-    pub fn neighbor(&self) -> IpNeighborCommand {
+    pub fn neighbor(&self) -> IpNeighborCommand<'_> {
         IpNeighborCommand::new(self)
     }
 
     /// Manage the neighbor cache's operation.
-    pub fn neighbor_table(&self) -> IpNeighborTableCommand {
+    pub fn neighbor_table(&self) -> IpNeighborTableCommand<'_> {
         IpNeighborTableCommand::new(self)
     }
 
     /// Tunnel over IP.
-    pub fn tunnel(&self) -> IpTunnelCommand {
+    pub fn tunnel(&self) -> IpTunnelCommand<'_> {
         IpTunnelCommand::new(self)
     }
 
     /// Manage TUN/TAP devices.
-    pub fn tuntap(&self) -> IpTunTapCommand {
+    pub fn tuntap(&self) -> IpTunTapCommand<'_> {
         IpTunTapCommand::new(self)
     }
 
+    /// Bridge port VLAN membership (via bridge(8)).
+    pub fn bridge_vlan(&self) -> IpBridgeVlanCommand<'_> {
+        IpBridgeVlanCommand::new(self)
+    }
+
+    /// Run several commands from a single ip(8) process.
+    pub fn batch(&self) -> IpBatchCommand<'_> {
+        IpBatchCommand::new(self)
+    }
+
+    /// Foo over UDP receive ports (see also the tunnel encapsulation of link types).
+    pub fn fou(&self) -> IpFouCommand<'_> {
+        IpFouCommand::new(self)
+    }
+
     /// Multicast address.
-    pub fn multicast_address(&self) -> IpMulticastAddressCommand {
+    pub fn multicast_address(&self) -> IpMulticastAddressCommand<'_> {
         IpMulticastAddressCommand::new(self)
     }
 
     /// Multicast routing cache entry.
-    pub fn multicast_route(&self) -> IpMulticastRouteCommand {
+    pub fn multicast_route(&self) -> IpMulticastRouteCommand<'_> {
         IpMulticastRouteCommand::new(self)
     }
 
     /// Rule in multicast routing policy database.
-    pub fn multicast_rule(&self) -> IpMulticastRuleCommand {
+    pub fn multicast_rule(&self) -> IpMulticastRuleCommand<'_> {
         IpMulticastRuleCommand::new(self)
     }
 
     /// Watch for netlink messages.
-    pub fn monitor(&self) -> IpMonitorCommand {
+    pub fn monitor(&self) -> IpMonitorCommand<'_> {
         IpMonitorCommand::new(self)
     }
 
     /// Manage IPSec policies.
-    pub fn transform(&self) -> IpTransformCommand {
+    pub fn transform(&self) -> IpTransformCommand<'_> {
         IpTransformCommand::new(self)
     }
 
     /// Manage network namespaces.
-    pub fn netns(&self) -> IpNetNamespaceCommand {
+    pub fn netns(&self) -> IpNetNamespaceCommand<'_> {
         IpNetNamespaceCommand::new(self)
     }
 
     /// Tunnel ethernet over IP (L2TPv3).
-    pub fn l2tp(&self) -> IpL2tpCommand {
+    pub fn l2tp(&self) -> IpL2tpCommand<'_> {
         IpL2tpCommand::new(self)
     }
 
     /// Manage TCP Metrics.
-    pub fn tcp_metrics(&self) -> IpTcpMetricsCommand {
+    pub fn tcp_metrics(&self) -> IpTcpMetricsCommand<'_> {
         IpTcpMetricsCommand::new(self)
     }
 
     /// Manage tokenized interface identifiers.
-    pub fn token(&self) -> IpTokenCommand {
+    pub fn token(&self) -> IpTokenCommand<'_> {
         IpTokenCommand::new(self)
     }
 
     /// MACsec device configuration.
-    pub fn macsec(&self) -> IpMACsecCommand {
+    pub fn macsec(&self) -> IpMACsecCommand<'_> {
         IpMACsecCommand::new(self)
     }
 
-    pub(crate) async fn command(
+    pub(crate) async fn command<S: AsRef<str>>(
         &self,
-        args: &[String],
+        args: &[S],
+        json: bool,
         combined_output: bool,
         stdin_buffer: Option<Vec<u8>>,
     ) -> Result<String, Error> {
         Ok(String::from_utf8(
-            self.command_with_raw_output(args, combined_output, stdin_buffer)
+            self.command_with_raw_output(args, json, combined_output, stdin_buffer)
                 .await?,
         )
         .unwrap())
     }
 
-    pub(crate) async fn command_with_raw_output(
+    /// Run a command which is not expected to produce any output (eg. add / delete).
+    pub(crate) async fn command_without_output<S: AsRef<str>>(
+        &self,
+        args: &[S],
+        stdin_buffer: Option<Vec<u8>>,
+    ) -> Result<(), Error> {
+        let output = self.command(args, false, false, stdin_buffer).await?;
+        Self::check_empty_output(&output)
+    }
+
+    /// Any output from a successful mutating command is informational only, however
+    /// structured output likely means iproute2 has started reporting something we should parse.
+    /// Debug builds fail with `UnexpectedOutputError` on structured output, release builds ignore
+    /// it (reported as a warning event with the `tracing` feature).
+    fn check_empty_output(output: &str) -> Result<(), Error> {
+        let output = output.trim();
+        let structured = (output.starts_with('{') || output.starts_with('['))
+            && output != "{}"
+            && output != "[]";
+        #[cfg(feature = "tracing")]
+        {
+            if structured {
+                tracing::warn!(output, "unexpected structured output");
+            }
+        }
+        ensure!(
+            !(cfg!(debug_assertions) && structured),
+            UnexpectedOutputError { output }
+        );
+        Ok(())
+    }
+
+    pub(crate) async fn command_with_raw_output<S: AsRef<str>>(
         &self,
-        args: &[String],
+        args: &[S],
+        json: bool,
         combined_output: bool,
         stdin_buffer: Option<Vec<u8>>,
     ) -> Result<Vec<u8>, Error> {
-        let args = self.concat_args(args)?;
-        let mut process = Command::new(&self.command)
-            .args(args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .context(SpawnError {})?;
+        if json {
+            self.ensure_json_output().await?;
+        }
+        self.execute(&self.command, args, json, combined_output, stdin_buffer)
+            .await
+    }
+
+    /// Releases before iproute2 4.15 lack json output (for most subcommands), fail clearly
+    /// rather than with the usage of whichever subcommand was run.
+    async fn ensure_json_output(&self) -> Result<(), Error> {
+        if self.dry_run.is_some() {
+            return Ok(());
+        }
+        let cached_version = self.version.lock().unwrap().clone();
+        let version = match cached_version {
+            Some(version) => version,
+            None => {
+                let output = self
+                    .execute(&self.command, &["-Version"], false, false, None)
+                    .await?;
+                let version = IpVersion::parse(&String::from_utf8_lossy(&output));
+                *self.version.lock().unwrap() = Some(version.clone());
+                version
+            }
+        };
+        ensure!(
+            version.is_at_least(JSON_OUTPUT_SNAPSHOT, JSON_OUTPUT_RELEASE),
+            JsonUnsupportedError {
+                version: version.raw
+            }
+        );
+        Ok(())
+    }
 
-        if let Some(stdin_buffer) = stdin_buffer {
-            let mut stdin = process.stdin.take().unwrap();
-            stdin.write_all(&stdin_buffer[..]).await.unwrap();
+    /// Write a kernel parameter (eg. `["net", "ipv6", "conf", "eth0", "stable_secret"]`).
+    ///
+    /// For a client of a network namespace the parameter is written from within the
+    /// namespace (`netns exec ... tee`), otherwise the file under /proc/sys is written directly,
+    /// which a dry run client skips.
+    pub(crate) async fn write_sysctl(&self, key: &[&str], value: &str) -> Result<(), Error> {
+        let path = key
+            .iter()
+            .fold(PathBuf::from("/proc/sys"), |path, component| {
+                path.join(component)
+            });
+        match &self.namespace {
+            Some(namespace) => {
+                let args: Vec<String> = vec![
+                    "netns".into(),
+                    "exec".into(),
+                    namespace.clone(),
+                    "tee".into(),
+                    path.to_string_lossy().into(),
+                ];
+                // tee echoes the value back.
+                self.command(&args, false, false, Some(value.as_bytes().to_vec()))
+                    .await
+                    .map(|_| ())
+            }
+            None if self.dry_run.is_some() => Ok(()),
+            None => tokio::fs::write(&path, value)
+                .await
+                .context(SysctlError { key: key.join(".") }),
         }
+    }
 
-        let result = timeout(self.timeout, process.wait_with_output())
-            .await
-            .context(CommandTimeoutError {})?
-            .context(CommandError {})?;
+    /// Run a bridge(8) command, it shares the global options (and namespace) of ip(8).
+    pub(crate) async fn bridge_command<S: AsRef<str>>(
+        &self,
+        args: &[S],
+        json: bool,
+    ) -> Result<String, Error> {
+        let bridge = Self::path("bridge").context(CommandNotFoundError {})?;
+        Ok(String::from_utf8(self.execute(&bridge, args, json, false, None).await?).unwrap())
+    }
+
+    async fn execute<S: AsRef<str>>(
+        &self,
+        program: &Path,
+        args: &[S],
+        json: bool,
+        combined_output: bool,
+        stdin_buffer: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>, Error> {
+        let result = self
+            .execute_to_completion(program, args, json, stdin_buffer)
+            .await?;
 
         let mut stdout = result.stdout.clone();
         let mut stderr = result.stderr.clone();
+        if let Some(namespace) = &self.namespace {
+            ensure!(
+                result.status.success()
+                    || !Self::namespace_missing(&String::from_utf8_lossy(&stderr)),
+                NamespaceNotFoundError { name: namespace }
+            );
+        }
+        ensure!(
+            result.status.success() || !Self::permission_denied(&String::from_utf8_lossy(&stderr)),
+            PermissionDeniedError {
+                stderr: String::from_utf8(stderr).unwrap()
+            }
+        );
         ensure!(
             result.status.success(),
             CommandFailedError {
@@ -246,39 +974,349 @@ impl IpCommand {
         })
     }
 
-    pub(crate) async fn command_with_streaming_output(
+    /// Run the command to completion, whatever its exit status. A dry run records the command
+    /// and succeeds with the configured output.
+    pub(crate) async fn execute_to_completion<S: AsRef<str>>(
+        &self,
+        program: &Path,
+        args: &[S],
+        json: bool,
+        stdin_buffer: Option<Vec<u8>>,
+    ) -> Result<Output, Error> {
+        let args = self.concat_args(args, json)?;
+        if let Some(dry_run) = &self.dry_run {
+            dry_run.record(&args);
+            return Ok(Output {
+                status: ExitStatus::from_raw(0),
+                stdout: dry_run.output.clone(),
+                stderr: vec![],
+            });
+        }
+        self.ensure_not_cancelled()?;
+        // Waiting for a permit counts towards the timeout. Held until the process has exited.
+        let deadline = Instant::now() + self.timeout;
+        let _permit = match &self.concurrency {
+            Some(concurrency) => Some(self.until_deadline(deadline, concurrency.acquire()).await?),
+            None => None,
+        };
+        let trace = CommandTrace::spawn(program, &args, self.namespace.as_deref());
+        let mut process = self
+            .new_command(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(SpawnError {})?;
+
+        // Written while the output is read, otherwise a process blocked on writing its output
+        // (once the pipe is full) never reads the rest of a large input. Closed once written.
+        let stdin = process.stdin.take();
+        let write_stdin = async move {
+            match (stdin, stdin_buffer) {
+                (Some(mut stdin), Some(stdin_buffer)) => stdin.write_all(&stdin_buffer[..]).await,
+                _ => Ok(()),
+            }
+        };
+
+        // The process is killed once the future waiting on it is dropped (ie. on cancellation).
+        let result = self
+            .until_deadline(
+                deadline,
+                future::join(write_stdin, process.wait_with_output()),
+            )
+            .await;
+        trace.exit(match &result {
+            Ok((_, Ok(output))) => Some(output.status),
+            _ => None,
+        });
+        let (written, output) = result?;
+        let output = output.context(CommandError {})?;
+        match written {
+            // The process exited without reading all of its input, its status tells why.
+            Err(error) if error.kind() != io::ErrorKind::BrokenPipe => {
+                Err(error).context(CommandError {})
+            }
+            _ => Ok(output),
+        }
+    }
+
+    /// Wait for the future until the deadline (failing with `CommandTimeoutError`), or until the
+    /// client is cancelled (failing with `CancelledError`), whichever is first.
+    async fn until_deadline<F: Future>(
+        &self,
+        deadline: Instant,
+        future: F,
+    ) -> Result<F::Output, Error> {
+        let wait = timeout_at(deadline, future);
+        let result = match &self.cancellation {
+            Some(cancellation) => tokio::select! {
+                result = wait => result,
+                _ = cancellation.cancelled() => return CancelledError {}.fail(),
+            },
+            None => wait.await,
+        };
+        result.context(CommandTimeoutError {})
+    }
+
+    #[cfg(feature = "streaming")]
+    pub(crate) async fn command_with_streaming_output<S: AsRef<str>>(
         &self,
-        args: &[String],
+        args: &[S],
+        json: bool,
         combined_output: bool,
     ) -> Result<ConsoleStream, Error> {
-        // Disable console buffering using the stdbuf tool
-        let mut combined_args: Vec<String> = vec![
-            "-i0".into(),
-            "-o0".into(),
-            "-e0".into(),
-            self.command.to_string_lossy().into(),
-        ];
-        combined_args.append(&mut self.concat_args(args)?);
-        let process = Command::new(&Self::path("stdbuf").context(CommandNotFoundError {})?)
-            .args(&combined_args)
+        let args = self.concat_args(args, json)?;
+        if let Some(dry_run) = &self.dry_run {
+            dry_run.record(&args);
+            return Ok(ConsoleStream::empty());
+        }
+        self.ensure_not_cancelled()?;
+        let trace = CommandTrace::spawn(&self.command, &args, self.namespace.as_deref());
+        let process = self
+            .streaming_command(Self::path("stdbuf").as_deref(), &args)
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
             .context(SpawnError {})?;
-        ConsoleStream::new(process, combined_output)
+        ConsoleStream::new(
+            process,
+            combined_output,
+            self.cancellation.clone(),
+            Some(trace),
+        )
     }
 
-    fn concat_args(&self, args: &[String]) -> Result<Vec<String>, Error> {
-        let mut combined_args: Vec<String> = vec!["-json".into()];
+    /// Run a command with json output, deserializing the elements of the top level array as
+    /// they are read, rather than buffering the whole output.
+    ///
+    /// The output is read (and parsed) by a blocking task, which pauses while the stream is not
+    /// polled. The client timeout does not apply.
+    #[cfg(feature = "streaming")]
+    pub(crate) async fn command_with_json_stream<T, S>(
+        &self,
+        args: &[S],
+    ) -> Result<JsonStream<T>, Error>
+    where
+        T: DeserializeOwned + Send + 'static,
+        S: AsRef<str>,
+    {
+        self.ensure_json_output().await?;
+        let args = self.concat_args(args, true)?;
+        let (sender, receiver) = mpsc::channel(JSON_STREAM_BUFFER);
+        if let Some(dry_run) = &self.dry_run {
+            dry_run.record(&args);
+            let output = dry_run.output.clone();
+            task::spawn_blocking(move || {
+                if let Err(error) = send_json_elements(&output[..], sender.clone()) {
+                    let _ = executor::block_on(sender.clone().send(Err(error)));
+                }
+            });
+            return Ok(Box::pin(receiver));
+        }
+        self.ensure_not_cancelled()?;
+        let trace = CommandTrace::spawn(&self.command, &args, self.namespace.as_deref());
+        let (program, elevation_args) = self.elevated_program(&self.command);
+        let mut process = std::process::Command::new(program)
+            .args(elevation_args)
+            .envs(&self.environment)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(SpawnError {})?;
+        let stdout = process.stdout.take().unwrap();
+        let mut stderr = process.stderr.take().unwrap();
+        let process = Arc::new(Mutex::new(process));
+        let (finished_sender, finished) = oneshot::channel::<()>();
+        if let Some(cancellation) = self.cancellation.clone() {
+            let process = process.clone();
+            task::spawn(async move {
+                if let Either::Left(_) =
+                    future::select(Box::pin(cancellation.cancelled()), finished).await
+                {
+                    // Fails harmlessly once the process has been waited on.
+                    let _ = process.lock().unwrap().kill();
+                }
+            });
+        }
+        let namespace = self.namespace.clone();
+        let cancellation = self.cancellation.clone();
+        task::spawn_blocking(move || {
+            let result = send_json_elements(io::BufReader::new(stdout), sender.clone());
+            let mut process = process.lock().unwrap();
+            if sender.is_closed() {
+                let _ = process.kill();
+            }
+            let mut error_output = String::new();
+            let status = stderr
+                .read_to_string(&mut error_output)
+                .and_then(|_| process.wait());
+            drop(finished_sender);
+            trace.exit(status.as_ref().ok().copied());
+            let cancelled =
+                matches!(cancellation, Some(cancellation) if cancellation.is_cancelled());
+            let error = match status {
+                Err(error) => Some(Error::CommandError { source: error }),
+                Ok(status) if !status.success() && cancelled => Some(Error::CancelledError {}),
+                Ok(status) if !status.success() => Some(match namespace {
+                    Some(name) if Self::namespace_missing(&error_output) => {
+                        Error::NamespaceNotFoundError { name }
+                    }
+                    _ if Self::permission_denied(&error_output) => Error::PermissionDeniedError {
+                        stderr: error_output,
+                    },
+                    _ => Error::CommandFailedError {
+                        stdout: "".into(),
+                        stderr: error_output,
+                    },
+                }),
+                Ok(_) => result.err(),
+            };
+            if let Some(error) = error {
+                let _ = executor::block_on(sender.clone().send(Err(error)));
+            }
+        });
+        Ok(Box::pin(receiver))
+    }
+
+    /// Disable console buffering using the stdbuf tool, if available. Otherwise ip(8) is run
+    /// directly, it flushes each line of its own streaming output (eg. monitor), however programs
+    /// run via `netns exec` may buffer theirs.
+    #[cfg(feature = "streaming")]
+    fn streaming_command(&self, stdbuf: Option<&Path>, args: &[&str]) -> Command {
+        match stdbuf {
+            Some(stdbuf) => {
+                let mut command = self.new_command(stdbuf);
+                command
+                    .args(["-i0", "-o0", "-e0"])
+                    .arg(&self.command)
+                    .args(args);
+                command
+            }
+            None => {
+                let mut command = self.new_command(&self.command);
+                command.args(args);
+                command
+            }
+        }
+    }
+
+    /// A command for the program, with the environment of the client. The process is killed
+    /// if dropped before it exits.
+    fn new_command(&self, program: &Path) -> Command {
+        let (program, args) = self.elevated_program(program);
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .envs(&self.environment)
+            .kill_on_drop(true);
+        command
+    }
+
+    /// The program (and any leading arguments) which runs the given one with the elevation of
+    /// the client.
+    fn elevated_program(&self, program: &Path) -> (PathBuf, Vec<OsString>) {
+        let prefix: Vec<String> = match &self.elevation {
+            Elevation::None => vec![],
+            Elevation::Sudo => vec!["sudo".into(), "-n".into()],
+            Elevation::Custom(prefix) => prefix.clone(),
+        };
+        match prefix.split_first() {
+            Some((elevating_program, args)) => (
+                elevating_program.into(),
+                args.iter()
+                    .map(OsString::from)
+                    .chain(iter::once(program.as_os_str().to_owned()))
+                    .collect(),
+            ),
+            None => (program.to_owned(), vec![]),
+        }
+    }
+
+    /// Prefix the subcommand with the global options, `-json` is only requested by
+    /// subcommands which parse their output as such.
+    ///
+    /// The arguments are borrowed, the only allocation is the argv itself (of at most three
+    /// global options more than the subcommand).
+    fn concat_args<'a, S: AsRef<str>>(
+        &'a self,
+        args: &'a [S],
+        json: bool,
+    ) -> Result<Vec<&'a str>, Error> {
+        let mut combined_args = Vec::with_capacity(args.len() + 3);
+        if json {
+            combined_args.push("-json");
+        } else if self.oneline && !args.iter().any(|arg| arg.as_ref() == "-oneline") {
+            combined_args.push("-oneline");
+        }
         if let Some(namespace) = &self.namespace {
-            combined_args.push("-netns".into());
-            combined_args.push(namespace.clone());
+            if !Self::enters_namespace(args) {
+                combined_args.push("-netns");
+                combined_args.push(namespace);
+            }
         }
-        combined_args.append(&mut Vec::from_iter(args.iter().cloned()));
+        combined_args.extend(args.iter().map(AsRef::as_ref));
         Ok(combined_args)
     }
 
+    /// Did the command fail for lack of privilege (eg. "RTNETLINK answers: Operation not permitted")?
+    pub(crate) fn permission_denied(stderr: &str) -> bool {
+        stderr.contains("Operation not permitted") || stderr.contains("Permission denied")
+    }
+
+    /// The segments of a record of `-oneline` output, iproute2 joins the lines of each record with
+    /// a backslash (eg. "1: lo: <LOOPBACK> mtu 65536 ...\    link/loopback 00:00:00:00:00:00 ...").
+    pub(crate) fn oneline_segments(record: &str) -> Vec<&str> {
+        record
+            .split('\\')
+            .map(str::trim)
+            .filter(|segment| !segment.is_empty())
+            .collect()
+    }
+
+    /// Copy the attributes named by `keys` (key in the output, json key, whether it is numeric)
+    /// from the tokens of a `-oneline` record, any other tokens are skipped.
+    pub(crate) fn insert_oneline_attributes<'t>(
+        fields: &mut serde_json::Map<String, serde_json::Value>,
+        mut tokens: impl Iterator<Item = &'t str>,
+        keys: &[(&str, &str, bool)],
+    ) {
+        while let Some(token) = tokens.next() {
+            if let Some((_, key, numeric)) = keys.iter().find(|(name, ..)| *name == token) {
+                if let Some(value) = tokens.next() {
+                    let value = match value.parse::<i64>() {
+                        Ok(number) if *numeric => number.into(),
+                        _ => value.into(),
+                    };
+                    fields.insert((*key).into(), value);
+                }
+            }
+        }
+    }
+
+    /// Commands of a cancelled client fail without being run.
+    fn ensure_not_cancelled(&self) -> Result<(), Error> {
+        ensure!(
+            !matches!(&self.cancellation, Some(cancellation) if cancellation.is_cancelled()),
+            CancelledError {}
+        );
+        Ok(())
+    }
+
+    /// Did the command fail to enter a network namespace because it doesn't exist?
+    pub(crate) fn namespace_missing(stderr: &str) -> bool {
+        stderr.contains("Cannot open network namespace")
+            && stderr.contains("No such file or directory")
+    }
+
+    /// Does the subcommand switch network namespace on its own (and thus must not be prefixed with `-netns`).
+    fn enters_namespace<S: AsRef<str>>(args: &[S]) -> bool {
+        args.len() >= 2 && args[0].as_ref() == "netns" && args[1].as_ref() == "exec"
+    }
+
     fn path(name: &str) -> Option<PathBuf> {
         env::var_os("PATH").and_then(|paths| {
             env::split_paths(&paths)
@@ -295,37 +1333,200 @@ impl IpCommand {
     }
 }
 
-/// A stream of strings corresponding to console lines.
+/// Poll the condition every `interval` until it returns a value, or until `wait_timeout` has
+/// elapsed (`WaitTimeoutError`). Errors returned by the condition are not retried.
+pub(crate) async fn poll_until<F, Fut, T>(
+    interval: Duration,
+    wait_timeout: Duration,
+    mut condition: F,
+) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Option<T>, Error>>,
+{
+    timeout(wait_timeout, async {
+        loop {
+            if let Some(value) = condition().await? {
+                return Ok(value);
+            }
+            tokio::time::delay_for(interval).await;
+        }
+    })
+    .await
+    .context(WaitTimeoutError {})?
+}
+
+/// Elements deserialized by `IpCommand::command_with_json_stream`, as they are read.
+#[cfg(feature = "streaming")]
+pub type JsonStream<T> = Pin<Box<dyn Stream<Item = Result<T, Error>> + Send>>;
+
+/// Elements parsed ahead of the consumer of a `JsonStream`.
+#[cfg(feature = "streaming")]
+const JSON_STREAM_BUFFER: usize = 64;
+
+/// Deserialize the elements of a json array, sending each as it is read. Stops early (without
+/// error) once the receiver is dropped.
+#[cfg(feature = "streaming")]
+fn send_json_elements<R, T>(reader: R, sender: mpsc::Sender<Result<T, Error>>) -> Result<(), Error>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    struct Elements<T> {
+        sender: mpsc::Sender<Result<T, Error>>,
+    }
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for Elements<T> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("an array")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+            while let Some(element) = seq.next_element()? {
+                if executor::block_on(self.sender.send(Ok(element))).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    match deserializer.deserialize_seq(Elements {
+        sender: sender.clone(),
+    }) {
+        // Unconsumed elements are only left behind once the receiver is gone.
+        Err(_) if sender.is_closed() => Ok(()),
+        result => result.context(JsonDeserializationError {}),
+    }
+}
+
+/// The console stream a line was written to.
+#[cfg(feature = "streaming")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamSource {
+    Stdout,
+    Stderr,
+}
+
+/// A console line, tagged with the stream it was written to.
+#[cfg(feature = "streaming")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsoleLine {
+    pub source: StreamSource,
+    pub line: String,
+}
+
+/// A stream of console lines.
+///
+/// When stdout and stderr are combined, lines from each are yielded in order, however the
+/// interleaving of lines from the two streams is not guaranteed.
+///
+/// The stream ends once the process has exited (after the end of its output), a process which
+/// is still running when the stream is dropped is killed.
+#[cfg(feature = "streaming")]
 pub struct ConsoleStream {
-    _process: Child,
-    inner: Pin<Box<dyn Stream<Item = tokio::io::Result<String>> + Send>>,
+    process: Option<Child>,
+    inner: Pin<Box<dyn Stream<Item = tokio::io::Result<ConsoleLine>> + Send>>,
+    cancelled: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    trace: Option<CommandTrace>,
 }
 
+#[cfg(feature = "streaming")]
 impl ConsoleStream {
-    fn new(mut process: Child, combined_output: bool) -> Result<Self, Error> {
-        let stdout = BufReader::new(process.stdout.take().unwrap()).lines();
-        let inner: Pin<Box<dyn Stream<Item = tokio::io::Result<String>> + Send>> =
+    fn new(
+        mut process: Child,
+        combined_output: bool,
+        cancellation: Option<CancellationToken>,
+        trace: Option<CommandTrace>,
+    ) -> Result<Self, Error> {
+        let stdout = Self::tagged_lines(process.stdout.take().unwrap(), StreamSource::Stdout);
+        let inner: Pin<Box<dyn Stream<Item = tokio::io::Result<ConsoleLine>> + Send>> =
             if combined_output {
-                let stderr = BufReader::new(process.stderr.take().unwrap()).lines();
+                let stderr =
+                    Self::tagged_lines(process.stderr.take().unwrap(), StreamSource::Stderr);
                 Box::pin(stdout.merge(stderr))
             } else {
                 Box::pin(stdout)
             };
         Ok(Self {
-            _process: process,
+            process: Some(process),
             inner,
+            cancelled: cancellation.map(|cancellation| {
+                let cancelled: Pin<Box<dyn Future<Output = ()> + Send>> =
+                    Box::pin(async move { cancellation.cancelled().await });
+                cancelled
+            }),
+            trace,
         })
     }
+
+    fn empty() -> Self {
+        Self {
+            process: None,
+            inner: Box::pin(futures::stream::empty()),
+            cancelled: None,
+            trace: None,
+        }
+    }
+
+    /// A console stream produced without a process (eg. by polling).
+    pub(crate) fn from_stream(
+        inner: impl Stream<Item = tokio::io::Result<ConsoleLine>> + Send + 'static,
+    ) -> Self {
+        Self {
+            process: None,
+            inner: Box::pin(inner),
+            cancelled: None,
+            trace: None,
+        }
+    }
+
+    fn tagged_lines<R: tokio::io::AsyncRead + Send + Unpin + 'static>(
+        reader: R,
+        source: StreamSource,
+    ) -> impl Stream<Item = tokio::io::Result<ConsoleLine>> + Send {
+        BufReader::new(reader)
+            .lines()
+            .map(move |line| line.map(|line| ConsoleLine { source, line }))
+    }
 }
 
+#[cfg(feature = "streaming")]
 impl Stream for ConsoleStream {
-    type Item = tokio::io::Result<String>;
+    type Item = tokio::io::Result<ConsoleLine>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(cancelled) = self.cancelled.as_mut() {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                // Killed on drop.
+                self.process = None;
+                self.cancelled = None;
+                self.inner = Box::pin(futures::stream::empty());
+            }
+        }
         if let Some(line) = ready!(self.inner.as_mut().poll_next(cx)) {
-            Poll::Ready(Some(line))
-        } else {
-            Poll::Ready(None)
+            return Poll::Ready(Some(line));
+        }
+        if let Some(process) = self.process.as_mut() {
+            let status = ready!(Pin::new(process).poll(cx));
+            self.process = None;
+            if let Some(trace) = self.trace.take() {
+                trace.exit(status.ok());
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+#[cfg(feature = "streaming")]
+impl Drop for ConsoleStream {
+    fn drop(&mut self) {
+        // Killed (or cancelled) before exiting.
+        if let Some(trace) = self.trace.take() {
+            trace.exit(None);
         }
     }
 }
@@ -333,6 +1534,7 @@ impl Stream for ConsoleStream {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::link::{LinkAddConfiguration, LinkSetConfiguration, LinkStatus};
     use regex::Regex;
 
     #[tokio::test]
@@ -343,4 +1545,565 @@ mod tests {
             .unwrap()
             .is_match(&version));
     }
+
+    #[tokio::test]
+    async fn test_raw_with_stdin() {
+        let ip_command = IpCommand::new().unwrap();
+        // Both the input and output of the batch are larger than a pipe buffer.
+        let script = "link show dev lo\n".repeat(8_000);
+        let output = ip_command
+            .raw_with_stdin(&["-batch", "-"], script.into_bytes())
+            .await
+            .unwrap();
+        assert_eq!(output.matches(": lo: ").count(), 8_000);
+
+        let error = ip_command
+            .raw_with_stdin(&["-batch", "-"], b"link frobnicate\n".to_vec())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::CommandFailedError { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_check_empty_output() {
+        let set_up = |output: &str| {
+            let ip_command = IpCommand::new().unwrap().dry_run_with_output(output);
+            async move {
+                ip_command
+                    .link()
+                    .set(LinkSetConfiguration {
+                        device: LinkDeviceOrGroup::Device("lo".into()),
+                        state: Some(LinkStatus::Up),
+                        ..Default::default()
+                    })
+                    .await
+            }
+        };
+        set_up("").await.unwrap();
+        set_up("{}\n").await.unwrap();
+        set_up("Warning: some informational message\n")
+            .await
+            .unwrap();
+
+        let result = set_up("[{\"unexpected\":true}]").await;
+        if cfg!(debug_assertions) {
+            assert!(matches!(
+                result,
+                Err(Error::UnexpectedOutputError { output }) if output == "[{\"unexpected\":true}]"
+            ));
+        } else {
+            result.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interfaces() {
+        let ip_command = IpCommand::new().unwrap();
+        let interfaces = ip_command.interfaces().await.unwrap();
+        let loopback = interfaces
+            .iter()
+            .find(|interface| interface.name == "lo")
+            .unwrap();
+        assert_eq!(loopback.index, ip_command.index_of("lo").await.unwrap());
+        assert!(loopback
+            .addresses
+            .iter()
+            .any(|address| address.local == Some("127.0.0.1".into())));
+
+        let ip_command = ip_command.dry_run_with_output(
+            r#"[{"ifindex":2,"ifname":"eth0","flags":["BROADCAST","MULTICAST","UP","LOWER_UP"],"mtu":1500,"qdisc":"fq_codel","operstate":"UP","group":"default","txqlen":1000,"link_type":"ether","address":"52:54:00:12:34:56","broadcast":"ff:ff:ff:ff:ff:ff","addr_info":[{"family":"inet","local":"192.0.2.2","prefixlen":24,"scope":"global","label":"eth0","valid_life_time":4294967295,"preferred_life_time":4294967295},{}]},{"ifindex":3,"ifname":"tun0","flags":["POINTOPOINT","NOARP"],"mtu":1500,"qdisc":"noop","operstate":"DOWN","group":"default","txqlen":500,"link_type":"none","addr_info":[]}]"#,
+        );
+        let interfaces = ip_command.interfaces().await.unwrap();
+        assert_eq!(
+            ip_command.recorded_commands(),
+            vec![vec!["-json", "address", "show"]]
+        );
+        assert_eq!(interfaces.len(), 2);
+        assert_eq!(interfaces[0].index, 2);
+        assert_eq!(interfaces[0].name, "eth0");
+        assert_eq!(interfaces[0].mtu, 1500);
+        assert_eq!(interfaces[0].state, "UP");
+        assert_eq!(interfaces[0].mac, Some("52:54:00:12:34:56".into()));
+        assert_eq!(interfaces[0].addresses.len(), 1);
+        assert_eq!(interfaces[1].state, "DOWN");
+        assert_eq!(interfaces[1].mac, None);
+        assert!(interfaces[1].addresses.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_version_info() {
+        let ip_command = IpCommand::new().unwrap();
+        let version = ip_command.version_info().await.unwrap();
+        assert!(version.iproute2.is_some());
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            IpVersion::parse("ip utility, iproute2-ss200127\n"),
+            IpVersion {
+                raw: "ip utility, iproute2-ss200127".into(),
+                iproute2: Some("ss200127".into()),
+                snapshot: Some(200127),
+            }
+        );
+        assert_eq!(
+            IpVersion::parse("ip utility, iproute2-6.1.0, libbpf 1.1.2"),
+            IpVersion {
+                raw: "ip utility, iproute2-6.1.0, libbpf 1.1.2".into(),
+                iproute2: Some("6.1.0".into()),
+                snapshot: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_version_is_at_least() {
+        let snapshot = IpVersion::parse("ip utility, iproute2-ss171113");
+        assert!(snapshot.is_at_least(171_113, (4, 14)));
+        assert!(!snapshot.is_at_least(180_129, (4, 15)));
+        let release = IpVersion::parse("ip utility, iproute2-6.1.0, libbpf 1.1.2");
+        assert_eq!(release.release(), Some((6, 1)));
+        assert!(release.is_at_least(180_129, (4, 15)));
+        assert!(!release.is_at_least(180_129, (6, 2)));
+        assert!(IpVersion::parse("ip utility").is_at_least(180_129, (4, 15)));
+    }
+
+    #[tokio::test]
+    async fn test_json_unsupported() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // An ip(8) command which reports a release predating json output.
+        let path = env::temp_dir().join("ip-command-test-legacy-ip");
+        std::fs::write(&path, "#!/bin/sh\necho \"ip utility, iproute2-ss170501\"\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let ip_command = IpCommand {
+            command: path.clone(),
+            ..IpCommand::new().unwrap()
+        };
+
+        let version = ip_command.version().await;
+        let links = ip_command.link().show(None).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(version.unwrap().trim(), "ip utility, iproute2-ss170501");
+        assert!(matches!(
+            links,
+            Err(Error::JsonUnsupportedError { version }) if version == "ip utility, iproute2-ss170501"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_supports() {
+        let ip_command = IpCommand::new().unwrap();
+        assert!(ip_command.supports(Feature::JsonOutput).await.unwrap());
+        assert!(ip_command.supports(Feature::AddressLabel).await.unwrap());
+        assert!(!ip_command.supports_object("unknown").await.unwrap());
+    }
+
+    #[test]
+    fn test_with_env() {
+        let ip_command = IpCommand::new().unwrap();
+        assert_eq!(ip_command.environment.get("LC_ALL"), Some(&"C".to_string()));
+
+        let first = ip_command
+            .with_env("LC_ALL", "en_US.UTF-8")
+            .with_namespace("test");
+        let second = ip_command
+            .with_namespace("test")
+            .with_env("LC_ALL", "en_US.UTF-8");
+        for ip_command in &[first, second] {
+            assert_eq!(ip_command.namespace.as_deref(), Some("test"));
+            assert_eq!(
+                ip_command.environment.get("LC_ALL"),
+                Some(&"en_US.UTF-8".to_string())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_with_env_applied() {
+        let ip_command = IpCommand::new().unwrap().with_env("IP_COMMAND_TEST", "1");
+        let output = ip_command
+            .execute(Path::new("env"), &[] as &[&str], false, false, None)
+            .await
+            .unwrap();
+        let environment = String::from_utf8(output).unwrap();
+        assert!(environment.lines().any(|line| line == "LC_ALL=C"));
+        assert!(environment.lines().any(|line| line == "IP_COMMAND_TEST=1"));
+    }
+
+    #[tokio::test]
+    async fn test_poll_until() {
+        let mut polls = 0;
+        let value = poll_until(Duration::from_millis(1), Duration::from_secs(5), || {
+            polls += 1;
+            let polls = polls;
+            async move { Ok(if polls == 3 { Some(polls) } else { None }) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(value, 3);
+
+        let result: Result<(), Error> = poll_until(
+            Duration::from_millis(1),
+            Duration::from_millis(20),
+            || async { Ok(None) },
+        )
+        .await;
+        assert!(matches!(result, Err(Error::WaitTimeoutError { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let ip_command = IpCommand::new().unwrap().with_namespace("test").dry_run();
+        ip_command
+            .link()
+            .add(LinkAddConfiguration {
+                name: "dummy0".into(),
+                mtu: Some(1400),
+                link_type: "dummy".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(ip_command.link().show(None).await.is_err());
+        assert_eq!(
+            ip_command.recorded_commands(),
+            vec![
+                vec![
+                    "-netns", "test", "link", "add", "name", "dummy0", "mtu", "1400", "type",
+                    "dummy"
+                ],
+                vec!["-json", "-netns", "test", "link", "show"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_concat_args_json() {
+        let ip_command = IpCommand::new().unwrap();
+        let args = ip_command.concat_args(&["link", "show"], true).unwrap();
+        assert_eq!(args, vec!["-json", "link", "show"]);
+        let args = ip_command.concat_args(&["link", "add"], false).unwrap();
+        assert_eq!(args, vec!["link", "add"]);
+    }
+
+    #[test]
+    fn test_concat_args_namespace() {
+        let ip_command = IpCommand::new().unwrap().with_namespace("test");
+        let args = ip_command.concat_args(&["link", "show"], true).unwrap();
+        assert_eq!(args, vec!["-json", "-netns", "test", "link", "show"]);
+    }
+
+    #[tokio::test]
+    async fn test_index_of_and_name_of() {
+        let test_namespace = "ip-command-test-index-namespace";
+        let ip_command = IpCommand::new().unwrap();
+        let links = ip_command.link().show(None).await.unwrap();
+        for link in &links {
+            assert_eq!(
+                ip_command.index_of(&link.name).await.unwrap(),
+                link.interface_index
+            );
+            assert_eq!(
+                ip_command.name_of(link.interface_index).await.unwrap(),
+                link.name
+            );
+        }
+        assert!(matches!(
+            ip_command.index_of("test_missing0").await,
+            Err(Error::NotFoundError {})
+        ));
+        assert!(matches!(
+            ip_command.name_of(u32::MAX).await,
+            Err(Error::NotFoundError {})
+        ));
+        assert!(matches!(
+            ip_command.index_of("../lo").await,
+            Err(Error::InvalidArgumentError { .. })
+        ));
+
+        ip_command.netns().add(test_namespace).await.unwrap();
+        let namespace_client = ip_command.with_namespace(test_namespace);
+        let index = namespace_client.index_of("lo").await;
+        let name = namespace_client.name_of(1).await;
+        let missing = namespace_client.index_of("test_missing0").await;
+        ip_command.netns().delete(test_namespace).await.unwrap();
+
+        assert_eq!(index.unwrap(), 1);
+        assert_eq!(name.unwrap(), "lo");
+        assert!(matches!(missing, Err(Error::NotFoundError {})));
+    }
+
+    #[tokio::test]
+    async fn test_index_of_and_name_of_dry_run() {
+        let ip_command = IpCommand::new().unwrap().dry_run_with_output(
+            r#"[{"ifindex":2,"ifname":"eth0","flags":["BROADCAST","MULTICAST","UP","LOWER_UP"],"mtu":1500,"qdisc":"fq_codel","operstate":"UP","linkmode":"DEFAULT","group":"default","txqlen":1000,"link_type":"ether","address":"02:00:00:00:00:01","broadcast":"ff:ff:ff:ff:ff:ff"}]"#,
+        );
+        assert_eq!(ip_command.index_of("eth0").await.unwrap(), 2);
+        assert_eq!(ip_command.name_of(2).await.unwrap(), "eth0");
+        assert!(matches!(
+            ip_command.name_of(1).await,
+            Err(Error::NotFoundError {})
+        ));
+        assert_eq!(
+            ip_command.recorded_commands(),
+            vec![
+                vec!["-json", "link", "show", "dev", "eth0"],
+                vec!["-json", "link", "show"],
+                vec!["-json", "link", "show"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_concat_args_oneline() {
+        let ip_command = IpCommand::new().unwrap().with_oneline(true);
+        let args = ip_command.concat_args(&["link", "show"], false).unwrap();
+        assert_eq!(args, vec!["-oneline", "link", "show"]);
+        let args = ip_command
+            .concat_args(&["-oneline", "monitor"], false)
+            .unwrap();
+        assert_eq!(args, vec!["-oneline", "monitor"]);
+        let args = ip_command.concat_args(&["route", "show"], true).unwrap();
+        assert_eq!(args, vec!["-json", "route", "show"]);
+    }
+
+    #[test]
+    fn test_permission_denied() {
+        assert!(IpCommand::permission_denied(
+            "RTNETLINK answers: Operation not permitted\n"
+        ));
+        assert!(IpCommand::permission_denied(
+            "mkdir /run/netns failed: Permission denied\n"
+        ));
+        assert!(!IpCommand::permission_denied(
+            "RTNETLINK answers: File exists\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_elevation() {
+        let ip_command = IpCommand::new().unwrap();
+        let program = ip_command.command.clone();
+        assert_eq!(
+            ip_command.elevated_program(&program),
+            (program.clone(), vec![])
+        );
+
+        let ip_command = ip_command
+            .with_elevation(Elevation::Custom(vec!["pkexec".into()]))
+            .dry_run();
+        assert_eq!(
+            ip_command.elevated_program(&program),
+            (
+                PathBuf::from("pkexec"),
+                vec![program.clone().into_os_string()]
+            )
+        );
+        let ip_command = ip_command.with_elevation(Elevation::Sudo);
+        assert_eq!(
+            ip_command.elevated_program(&program),
+            (
+                PathBuf::from("sudo"),
+                vec!["-n".into(), program.clone().into_os_string()]
+            )
+        );
+        // The global options and subcommand follow the program as before.
+        ip_command.link().show(None).await.unwrap_err();
+        assert_eq!(
+            ip_command.recorded_commands(),
+            vec![vec!["-json", "link", "show"]]
+        );
+    }
+
+    #[test]
+    fn test_concat_args_namespace_exec() {
+        let ip_command = IpCommand::new().unwrap().with_namespace("test");
+        let args = ip_command
+            .concat_args(&["netns", "exec", "other", "true"], false)
+            .unwrap();
+        assert_eq!(args, vec!["netns", "exec", "other", "true"]);
+    }
+
+    #[cfg(feature = "streaming")]
+    #[tokio::test]
+    async fn test_streaming_without_stdbuf() {
+        let ip_command = IpCommand::new().unwrap();
+        let process = ip_command
+            .streaming_command(None, &["-Version"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let lines: Vec<ConsoleLine> = ConsoleStream::new(process, false, None, None)
+            .unwrap()
+            .map(|line| line.unwrap())
+            .collect()
+            .await;
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].line.starts_with("ip utility"));
+    }
+
+    #[cfg(feature = "streaming")]
+    #[tokio::test]
+    async fn test_console_stream_source() {
+        let process = Command::new("sh")
+            .args(["-c", "echo data; echo diagnostic >&2"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut lines: Vec<ConsoleLine> = ConsoleStream::new(process, true, None, None)
+            .unwrap()
+            .map(|line| line.unwrap())
+            .collect()
+            .await;
+        lines.sort_by_key(|line| line.source == StreamSource::Stderr);
+        assert_eq!(
+            lines,
+            vec![
+                ConsoleLine {
+                    source: StreamSource::Stdout,
+                    line: "data".into(),
+                },
+                ConsoleLine {
+                    source: StreamSource::Stderr,
+                    line: "diagnostic".into(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_cancelled() {
+        let cancellation = CancellationToken::new();
+        let ip_command = IpCommand::new().unwrap().with_cancellation(&cancellation);
+        let canceller = cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+            canceller.cancel();
+        });
+        // Runs until killed.
+        let started = std::time::Instant::now();
+        let result = ip_command.command(&["monitor"], false, false, None).await;
+        assert!(matches!(result, Err(Error::CancelledError {})));
+        assert!(started.elapsed() < ip_command.timeout);
+
+        let result = ip_command.command(&["-Version"], false, false, None).await;
+        assert!(matches!(result, Err(Error::CancelledError {})));
+    }
+
+    #[cfg(feature = "streaming")]
+    #[tokio::test]
+    async fn test_streaming_cancelled() {
+        let cancellation = CancellationToken::new();
+        let ip_command = IpCommand::new().unwrap().with_cancellation(&cancellation);
+        let stream = ip_command
+            .command_with_streaming_output(&["monitor"], false, false)
+            .await
+            .unwrap();
+        cancellation.cancel();
+        let lines: Vec<_> = stream.collect().await;
+        assert!(lines.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrency() {
+        let ip_command = IpCommand::new()
+            .unwrap()
+            .with_max_concurrency(NonZeroUsize::new(8).unwrap());
+        let results = futures::future::join_all((0..200).map(|_| {
+            let ip_command = ip_command.clone();
+            async move { ip_command.link().show(None).await }
+        }))
+        .await;
+        assert_eq!(results.len(), 200);
+        for links in results {
+            assert!(links.unwrap().iter().any(|link| link.name == "lo"));
+        }
+        assert_eq!(
+            ip_command.concurrency.as_ref().unwrap().available_permits(),
+            8
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrency_peak() {
+        let ip_command = IpCommand::new()
+            .unwrap()
+            .with_max_concurrency(NonZeroUsize::new(8).unwrap());
+        // Each process records the number of processes running (including itself).
+        let running = env::temp_dir().join(format!(
+            "ip-command-test-concurrency-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&running).unwrap();
+        let script = r#"touch "$0/$$"; ls "$0" | wc -l >> "$0.log"; sleep 0.1; rm "$0/$$""#;
+        let results = futures::future::join_all((0..50).map(|_| {
+            let ip_command = ip_command.clone();
+            let running = running.clone();
+            async move {
+                ip_command
+                    .execute_to_completion(
+                        Path::new("sh"),
+                        &["-c", script, &running.to_string_lossy()],
+                        false,
+                        None,
+                    )
+                    .await
+            }
+        }))
+        .await;
+        let log = running.with_extension("log");
+        let counts = std::fs::read_to_string(&log).unwrap();
+        std::fs::remove_file(&log).unwrap();
+        std::fs::remove_dir(&running).unwrap();
+
+        for result in results {
+            assert!(result.unwrap().status.success());
+        }
+        let peak = counts
+            .lines()
+            .map(|count| count.trim().parse::<usize>().unwrap())
+            .max()
+            .unwrap();
+        assert!(peak > 1 && peak <= 8, "peak {}", peak);
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrency_wait_timeout_and_cancellation() {
+        let mut ip_command = IpCommand::new()
+            .unwrap()
+            .with_max_concurrency(NonZeroUsize::new(1).unwrap());
+        let sleep = ip_command.clone();
+        let running = tokio::spawn(async move {
+            sleep
+                .execute_to_completion(Path::new("sleep"), &["1"], false, None)
+                .await
+        });
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+
+        // Waiting for the running command to exit.
+        ip_command.timeout = Duration::from_millis(100);
+        let result = ip_command
+            .execute_to_completion(Path::new("true"), &[] as &[&str], false, None)
+            .await;
+        assert!(matches!(result, Err(Error::CommandTimeoutError { .. })));
+
+        let cancellation = CancellationToken::new();
+        let mut cancellable = ip_command.with_cancellation(&cancellation);
+        cancellable.timeout = Duration::from_secs(5);
+        tokio::spawn(async move {
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+            cancellation.cancel();
+        });
+        let result = cancellable
+            .execute_to_completion(Path::new("true"), &[] as &[&str], false, None)
+            .await;
+        assert!(matches!(result, Err(Error::CancelledError {})));
+
+        running.await.unwrap().unwrap();
+    }
 }